@@ -1,3 +1,4 @@
+use defirelay_backend::types::webhook_events::{webhook_json_schema, WebhookApiVersion};
 use std::fs::File;
 use std::io::Write;
 use std::path::Path;
@@ -22,17 +23,26 @@ mod controllers;
         controllers::api_key_controller::list_api_keys,
         controllers::api_key_controller::delete_api_key,
         
-        // Invoices Controller (newly annotated)
-   
+        // Invoices Controller
+        controllers::invoices_controller::create_invoice,
+        controllers::invoices_controller::list_invoices,
+        controllers::invoices_controller::find_invoice_by_uuid,
          
-        // Invoice Templates Controller (newly annotated)
-        
+        // Invoice Templates Controller
+        controllers::invoice_templates_controller::create_invoice_template,
+        controllers::invoice_templates_controller::list_invoice_templates,
+        controllers::invoice_templates_controller::find_invoice_template_by_uuid,
+        controllers::invoice_templates_controller::update_invoice_template,
+        controllers::invoice_templates_controller::delete_invoice_template,
+        controllers::invoice_templates_controller::find_or_create_invoice_from_template,
         
         // Payments Controller
         controllers::payments_controller::find_payment_by_invoice_uuid,
         controllers::payments_controller::list_payments,
+        controllers::payments_controller::export_payments,
         
         // Token Symbols Controller
+        controllers::token_symbols_controller::list_token_symbols_by_chain,
        
         
      
@@ -60,6 +70,28 @@ fn main() {
     
     // Also print to stdout for convenience
     println!("{}", openapi_json);
+
+    write_webhook_schemas();
+}
+
+/// One JSON Schema per webhook api version, for receivers to validate request bodies against
+fn write_webhook_schemas() {
+    let schema_dir = Path::new("webhook_schemas");
+
+    if let Err(e) = std::fs::create_dir_all(schema_dir) {
+        eprintln!("Error creating {}: {}", schema_dir.display(), e);
+        return;
+    }
+
+    for version in WebhookApiVersion::ALL {
+        let output_path = schema_dir.join(format!("{}.json", version));
+        let schema_json = serde_json::to_string_pretty(&webhook_json_schema(version)).unwrap();
+
+        match std::fs::write(&output_path, schema_json) {
+            Ok(_) => println!("Webhook schema written to {}", output_path.display()),
+            Err(e) => eprintln!("Error writing {}: {}", output_path.display(), e),
+        }
+    }
 }
 
 // Note: To include additional endpoints in the API documentation, each controller endpoint 
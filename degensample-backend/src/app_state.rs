@@ -2,6 +2,9 @@ use std::sync::Arc;
 
 use degen_sql::db::postgres::postgres_db::Database;
 
+use crate::util::account_event_hub::AccountEventHub;
+
 pub struct AppState {
     pub database: Arc<Database>,
+    pub account_events: Arc<AccountEventHub>,
 }
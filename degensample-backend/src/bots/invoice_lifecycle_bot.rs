@@ -0,0 +1,233 @@
+use defirelay_backend::db::postgres::models::bot_cursors_model::BotCursorsModel;
+use defirelay_backend::db::postgres::models::events_model::EventsModel;
+use defirelay_backend::db::postgres::models::invoices_model::{InvoiceStatus, InvoicesModel};
+use defirelay_backend::types::domains::bytes32::DomainBytes32;
+use defirelay_backend::types::domains::eth_address::DomainEthAddress;
+use defirelay_backend::types::domains::h256::DomainH256;
+use ethers::abi::Token;
+use log::info;
+use log::warn;
+
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+
+use vibegraph::event::ContractEvent;
+
+use dotenvy::dotenv;
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tokio::time::Duration;
+
+/*
+
+Matches on-chain CreatedInvoice / PaidInvoice events back to invoice drafts, and expires
+unpaid invoices past their expires_at.
+
+RUST_LOG=info cargo run --bin invoice_lifecycle_bot
+*/
+
+const BOT_NAME: &str = "invoice_lifecycle_bot";
+
+/// Invoices expired per tick
+const EXPIRE_BATCH_SIZE: i64 = 100;
+
+pub struct AppState {
+    pub database: Arc<Mutex<Database>>,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    dotenv().ok();
+    run_invoice_lifecycle_bot().await;
+}
+
+pub async fn run_invoice_lifecycle_bot() {
+    println!("booting invoice lifecycle bot ");
+
+    let db_conn_url = std::env::var("DB_CONN_URL").expect(" DB_CONN_URL must be set in env ");
+
+    let database = match Database::new(db_conn_url.clone(), None) {
+        Ok(db) => Arc::new(Mutex::new(db)),
+        Err(e) => {
+            eprintln!("Failed to connect to database: {}", e);
+            return;
+        }
+    };
+
+    let app_state = AppState {
+        database: Arc::clone(&database),
+    };
+
+    let index_rate: u64 = std::env::var("PULSE_TIME_MS")
+        .ok()
+        .and_then(|i| i.parse().ok())
+        .unwrap_or(4000);
+
+    start(app_state, index_rate).await;
+}
+
+async fn start(app_state: AppState, tick_interval_time_ms: u64) {
+    let mut tick_interval = interval(Duration::from_millis(tick_interval_time_ms));
+
+    loop {
+        select! {
+            _ = tick_interval.tick() => {
+
+                poll_event_and_update_invoice(&app_state).await;
+
+                expire_due_invoices(&app_state).await;
+
+            }
+
+        }
+    }
+}
+
+async fn poll_event_and_update_invoice(app_state: &AppState) {
+    let mut psql_db = app_state.database.lock().await;
+
+    if let Err(e) = apply_next_lifecycle_event(&mut psql_db).await {
+        // the cursor did not move, so the same event is retried next tick
+        warn!("could not update invoice {:?}", e);
+    }
+}
+
+async fn expire_due_invoices(app_state: &AppState) {
+    let psql_db = app_state.database.lock().await;
+
+    // Read before expiring: the cursor only moves forward, so a stale one is safe
+    let applied_up_to = match BotCursorsModel::find_or_create(BOT_NAME, &*psql_db).await {
+        Ok(cursor) => cursor,
+        Err(e) => {
+            warn!("could not read the cursor to expire invoices {:?}", e);
+            return;
+        }
+    };
+
+    match InvoicesModel::expire_due(applied_up_to, EXPIRE_BATCH_SIZE, &psql_db).await {
+        Ok(expired) => {
+            for invoice in &expired {
+                info!("expired invoice {}", invoice.uuid.to_hex());
+            }
+        }
+        Err(e) => warn!("could not expire invoices {:?}", e),
+    }
+}
+
+/// Applies the event after this bot's cursor. The invoice update and the cursor
+/// advance commit together, so each event is applied exactly once.
+async fn apply_next_lifecycle_event(psql_db: &mut Database) -> Result<(), PostgresModelError> {
+    let cursor = BotCursorsModel::find_or_create(BOT_NAME, psql_db).await?;
+
+    let Ok((next_event_id, event_data)) = EventsModel::find_next_event_of_types(
+        vec!["CreatedInvoice".to_string(), "PaidInvoice".to_string()],
+        cursor,
+        psql_db,
+    )
+    .await
+    else {
+        // caught up
+        return Ok(());
+    };
+
+    let mut client = psql_db.connect().await?;
+    let transaction = client.transaction().await?;
+
+    if !BotCursorsModel::advance(BOT_NAME, cursor, next_event_id, &transaction).await? {
+        info!("event {} was already applied", next_event_id);
+        return Ok(());
+    }
+
+    match InvoiceLifecycleEvent::extract(&event_data) {
+        Some(lifecycle_event) => {
+            let updated = match &lifecycle_event {
+                InvoiceLifecycleEvent::Created {
+                    uuid,
+                    chain_id,
+                    tx_hash,
+                } => InvoicesModel::mark_created(uuid, *chain_id, tx_hash, &transaction).await?,
+                InvoiceLifecycleEvent::Paid {
+                    uuid,
+                    chain_id,
+                    paid_by,
+                    tx_hash,
+                } => {
+                    let status =
+                        InvoicesModel::mark_paid(uuid, *chain_id, paid_by, tx_hash, &transaction)
+                            .await?;
+
+                    if status == Some(InvoiceStatus::Expired) {
+                        warn!("invoice {} was paid after it expired", uuid.to_hex());
+                    }
+
+                    status.is_some()
+                }
+            };
+
+            // invoices created directly on-chain have no draft to match
+            if updated {
+                info!("updated invoice from {:?}", lifecycle_event);
+            }
+        }
+        None => warn!("could not extract invoice lifecycle event {:?}", event_data),
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+enum InvoiceLifecycleEvent {
+    Created {
+        uuid: DomainBytes32,
+        chain_id: i64,
+        tx_hash: DomainH256,
+    },
+    Paid {
+        uuid: DomainBytes32,
+        chain_id: i64,
+        paid_by: DomainEthAddress,
+        tx_hash: DomainH256,
+    },
+}
+
+impl InvoiceLifecycleEvent {
+    fn extract(contract_event: &ContractEvent) -> Option<Self> {
+        let chain_id = contract_event.chain_id as i64;
+        let tx_hash = DomainH256(contract_event.transaction_hash?);
+
+        let mut uuid_opt = None;
+        let mut from_address_opt = None;
+
+        for arg in &contract_event.args {
+            match (arg.name.as_str(), &arg.value) {
+                ("uuid", Token::FixedBytes(uuid_bytes)) => {
+                    uuid_opt = <[u8; 32]>::try_from(uuid_bytes.as_slice()).ok()
+                }
+                ("from", Token::Address(from)) => from_address_opt = Some(*from),
+                _ => {}
+            }
+        }
+
+        let uuid = DomainBytes32(uuid_opt?);
+
+        match contract_event.name.as_str() {
+            "CreatedInvoice" => Some(Self::Created {
+                uuid,
+                chain_id,
+                tx_hash,
+            }),
+            "PaidInvoice" => Some(Self::Paid {
+                uuid,
+                chain_id,
+                paid_by: DomainEthAddress(from_address_opt?),
+                tx_hash,
+            }),
+            _ => None,
+        }
+    }
+}
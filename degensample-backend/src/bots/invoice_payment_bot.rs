@@ -0,0 +1,270 @@
+use chrono::{DateTime, Utc};
+use defirelay_backend::config::NetworksConfig;
+use defirelay_backend::db::postgres::models::bot_cursors_model::BotCursorsModel;
+use defirelay_backend::db::postgres::models::events_model::EventsModel;
+use defirelay_backend::db::postgres::models::invoice_payments_model::InvoicePayment;
+use defirelay_backend::db::postgres::models::invoice_payments_model::InvoicePaymentsModel;
+use defirelay_backend::db::postgres::models::payments_model::PaymentStatus;
+use defirelay_backend::types::domains::bytes32::DomainBytes32;
+use defirelay_backend::types::domains::datetime::DomainDatetime;
+use defirelay_backend::types::domains::eth_address::DomainEthAddress;
+use defirelay_backend::types::domains::h256::DomainH256;
+use defirelay_backend::types::domains::uint256::DomainUint256;
+use defirelay_backend::util::payment_summary::{fetch_block_timestamp, PaymentSummaryError};
+use defirelay_backend::util::rpc_provider::RpcProviders;
+use ethers::abi::Address;
+use ethers::abi::Token;
+use ethers::types::H256;
+use ethers::types::U256;
+use ethers::types::U64;
+use log::info;
+use log::warn;
+
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+
+use vibegraph::event::ContractEvent;
+
+use dotenvy::dotenv;
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::Mutex;
+use tokio::time::interval;
+use tokio::time::Duration;
+
+/*
+
+Indexes each InvoicePayment event (one per payee) into the invoice_payments ledger.
+
+RUST_LOG=info cargo run --bin invoice_payment_bot
+*/
+
+const BOT_NAME: &str = "invoice_payment_bot";
+
+pub struct AppState {
+    pub database: Arc<Mutex<Database>>,
+    pub providers: RpcProviders,
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+    dotenv().ok();
+    run_invoice_payment_bot().await;
+}
+
+pub async fn run_invoice_payment_bot() {
+    println!("booting invoice payment bot ");
+
+    let db_conn_url = std::env::var("DB_CONN_URL").expect(" DB_CONN_URL must be set in env ");
+
+    let database = match Database::new(db_conn_url.clone(), None) {
+        Ok(db) => Arc::new(Mutex::new(db)),
+        Err(e) => {
+            eprintln!("Failed to connect to database: {}", e);
+            return;
+        }
+    };
+
+    let app_state = AppState {
+        database: Arc::clone(&database),
+        providers: RpcProviders::from_networks(&NetworksConfig::load()),
+    };
+
+    let index_rate: u64 = std::env::var("PULSE_TIME_MS")
+        .ok()
+        .and_then(|i| i.parse().ok())
+        .unwrap_or(4000);
+
+    start(app_state, index_rate).await;
+}
+
+async fn start(app_state: AppState, tick_interval_time_ms: u64) {
+    let mut tick_interval = interval(Duration::from_millis(tick_interval_time_ms));
+
+    loop {
+        select! {
+            _ = tick_interval.tick() => {
+
+                poll_event_and_index_invoice_payment(&app_state).await;
+
+            }
+
+        }
+    }
+}
+
+async fn poll_event_and_index_invoice_payment(app_state: &AppState) {
+    if let Err(e) = index_next_invoice_payment(app_state).await {
+        // the cursor did not move, so the same event is retried next tick
+        warn!("could not index invoice payment {:?}", e);
+    }
+}
+
+/// Indexes the event after this bot's cursor. The ledger write and the cursor
+/// advance commit together, so each event is applied exactly once. The database
+/// is not held while the block timestamp is fetched.
+async fn index_next_invoice_payment(app_state: &AppState) -> Result<(), PaymentSummaryError> {
+    let mut psql_db = app_state.database.lock().await;
+
+    let cursor = BotCursorsModel::find_or_create(BOT_NAME, &*psql_db)
+        .await
+        .map_err(|e| PaymentSummaryError::DatabaseError(e.to_string()))?;
+
+    let Ok((next_event_id, event_data)) =
+        EventsModel::find_next_event_of_type("InvoicePayment".to_string(), cursor, &mut psql_db)
+            .await
+    else {
+        // caught up
+        return Ok(());
+    };
+
+    drop(psql_db);
+
+    let invoice_payment = match InvoicePaymentData::extract_invoice_payment_data(&event_data) {
+        Some(invoice_payment_data) => {
+            info!("indexing invoice payment {:?}", invoice_payment_data);
+
+            let block_timestamp = invoice_payment_data
+                .fetch_block_timestamp(&app_state.providers)
+                .await?;
+
+            Some(invoice_payment_data.into_invoice_payment(block_timestamp))
+        }
+        None => {
+            warn!("could not extract invoice payment data {:?}", event_data);
+            None
+        }
+    };
+
+    let psql_db = app_state.database.lock().await;
+
+    write_invoice_payment(cursor, next_event_id, invoice_payment, &psql_db)
+        .await
+        .map_err(|e| PaymentSummaryError::DatabaseError(e.to_string()))
+}
+
+async fn write_invoice_payment(
+    cursor: Option<i32>,
+    next_event_id: i32,
+    invoice_payment: Option<InvoicePayment>,
+    psql_db: &Database,
+) -> Result<(), PostgresModelError> {
+    let mut client = psql_db.connect().await?;
+    let transaction = client.transaction().await?;
+
+    if !BotCursorsModel::advance(BOT_NAME, cursor, next_event_id, &transaction).await? {
+        info!("event {} was already indexed", next_event_id);
+        return Ok(());
+    }
+
+    if let Some(invoice_payment) = invoice_payment {
+        InvoicePaymentsModel::insert_or_update_one(invoice_payment, &transaction).await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct InvoicePaymentData {
+    contract_address: Address,
+    chain_id: u64,
+
+    uuid: [u8; 32],
+    token_address: Address,
+    from_address: Address,
+    to_address: Address,
+    amount: U256,
+
+    tx_hash: H256,
+    log_index: U256,
+
+    block_number: Option<U64>,
+    block_hash: Option<H256>,
+}
+
+impl InvoicePaymentData {
+    fn extract_invoice_payment_data(contract_event: &ContractEvent) -> Option<Self> {
+        if contract_event.name != "InvoicePayment" {
+            return None;
+        }
+
+        let tx_hash = contract_event.transaction_hash?;
+        let log_index = contract_event.log_index?;
+
+        let mut uuid_opt = None;
+        let mut token_address_opt = None;
+        let mut from_address_opt = None;
+        let mut to_address_opt = None;
+        let mut amount_opt = None;
+
+        for arg in &contract_event.args {
+            match (arg.name.as_str(), &arg.value) {
+                ("uuid", Token::FixedBytes(uuid_bytes)) => {
+                    uuid_opt = <[u8; 32]>::try_from(uuid_bytes.as_slice()).ok()
+                }
+                ("token", Token::Address(token)) => token_address_opt = Some(*token),
+                ("from", Token::Address(from)) => from_address_opt = Some(*from),
+                ("to", Token::Address(to)) => to_address_opt = Some(*to),
+                ("amount", Token::Uint(amount)) => amount_opt = Some(*amount),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            contract_address: contract_event.address,
+            chain_id: contract_event.chain_id,
+            uuid: uuid_opt?,
+            token_address: token_address_opt?,
+            from_address: from_address_opt?,
+            to_address: to_address_opt?,
+            amount: amount_opt?,
+            tx_hash,
+            log_index,
+            block_number: contract_event.block_number,
+            block_hash: contract_event.block_hash,
+        })
+    }
+
+    /// When the block the event was mined in was produced. None when the chain has
+    /// no rpc endpoints configured.
+    async fn fetch_block_timestamp(
+        &self,
+        providers: &RpcProviders,
+    ) -> Result<Option<DateTime<Utc>>, PaymentSummaryError> {
+        let Some(block_number) = self.block_number else {
+            return Ok(None);
+        };
+
+        let Some(pool) = providers.get(self.chain_id) else {
+            warn!("no rpc provider for chain {}", self.chain_id);
+            return Ok(None);
+        };
+
+        fetch_block_timestamp(block_number, pool).await.map(Some)
+    }
+
+    fn into_invoice_payment(self, block_timestamp: Option<DateTime<Utc>>) -> InvoicePayment {
+        InvoicePayment {
+            uuid: DomainBytes32(self.uuid),
+            chain_id: self.chain_id as i64,
+            payspec_contract_address: DomainEthAddress(self.contract_address),
+            payment_token_address: DomainEthAddress(self.token_address),
+            from_address: DomainEthAddress(self.from_address),
+            to_address: DomainEthAddress(self.to_address),
+            amount: DomainUint256(self.amount),
+            transaction_hash: DomainH256(self.tx_hash),
+            log_index: self.log_index.as_u64() as i64,
+            payment_at_block: self.block_number.map(|x| x.as_u64() as i64),
+            payment_at_block_hash: self.block_hash.map(DomainH256),
+            payment_at_block_timestamp: block_timestamp.map(DomainDatetime),
+            status: PaymentStatus::Detected,
+            token: None,
+            amount_formatted: None,
+            usd_price: None,
+            usd_value: None,
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub mod invoice_lifecycle_bot;
+pub mod invoice_payment_bot;
 pub mod payment_summary_bot;
 pub mod vibegraph_bot;
 pub mod webhook_trigger_bot;
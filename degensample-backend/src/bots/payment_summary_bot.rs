@@ -1,67 +1,46 @@
+use defirelay_backend::config::NetworksConfig;
+use defirelay_backend::db::postgres::models::bot_cursors_model::BotCursorsModel;
+use defirelay_backend::db::postgres::models::domain_events_model::DomainEventsModel;
 use defirelay_backend::db::postgres::models::events_model::EventsModel;
+use defirelay_backend::db::postgres::models::invoice_payments_model::InvoicePaymentsModel;
+use defirelay_backend::db::postgres::models::payments_model::PaymentStatus;
 use defirelay_backend::db::postgres::models::payments_model::PaymentSummary;
 use defirelay_backend::db::postgres::models::payments_model::PaymentsModel;
-use defirelay_backend::types::domains::bytes32::DomainBytes32;
-use defirelay_backend::types::domains::eth_address::DomainEthAddress;
+use defirelay_backend::db::postgres::models::webhook_triggers_model::WebhookTriggersModel;
 use defirelay_backend::types::domains::h256::DomainH256;
-use defirelay_backend::types::domains::pay_to_amounts::DomainPayToAmounts;
-use defirelay_backend::types::domains::pay_to_array::DomainPayToArray;
-use defirelay_backend::types::domains::uint256::DomainUint256;
-use defirelay_backend::util::rpc_network::RpcNetwork;
-use ethers::types::H256;
+use defirelay_backend::types::selected_record::SelectedRecord;
+use defirelay_backend::util::payment_summary::{
+    fetch_payment_summary, PaidInvoiceData, PaymentSummaryConfig, PaymentSummaryError,
+};
 use log::warn;
 
 use ethers_middleware::Middleware;
 
-use chrono::DateTime;
-use chrono::TimeZone;
-use chrono::Utc;
-use defirelay_backend::util::unix_day_index::UnixDayIndex;
-use ethers::contract::abigen;
-use ethers::providers::Http;
-use ethers::providers::Provider;
-use ethers::types::U64;
+use ethers::types::{H256, U64};
 use log::info;
 
-use std::env;
-
 use tokio::sync::Mutex;
 
-use ethers::abi::Address;
-use ethers::abi::Token;
-
+use degen_sql::db::postgres::models::model::PostgresModelError;
 use degen_sql::db::postgres::postgres_db::Database;
 
-use vibegraph::event::ContractEvent;
-
 use dotenvy::dotenv;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::select;
 use tokio::time::interval;
 use tokio::time::Duration;
 
-
 /*
 
 RUST_LOG=info cargo run --bin loan_summary_bot
 */
 
+const BOT_NAME: &str = "payment_summary_bot";
+
 pub struct AppState {
     pub database: Arc<Mutex<Database>>,
-
-    pub indexing_state: IndexingState,
-}
-
-pub struct AppConfig {
-    //  loan_protocol_abi : Abi,
-    rpc_uri_map: HashMap<u64, String>,
-}
-
-#[derive(Default)]
-pub struct IndexingState {
-    //event_offset: u64,
-    event_id_offset: Option<i32>,
 }
 
 #[tokio::main]
@@ -85,19 +64,11 @@ pub async fn run_payment_summary() {
         }
     };
 
-    let networks = vec![RpcNetwork::Mainnet, RpcNetwork::Base];
-
-    let chain_ids: Vec<u64> = networks.iter().map(|n| n.get_chain_id()).collect();
-
-    let rpc_uri_map = load_rpc_uris(&chain_ids);
-
     let app_state = AppState {
         database: Arc::clone(&database),
-
-        indexing_state: IndexingState::default(),
     };
 
-    let app_config = AppConfig { rpc_uri_map };
+    let app_config = PaymentSummaryConfig::from_env(NetworksConfig::load());
 
     // find the next database event to operate on
 
@@ -107,290 +78,365 @@ pub async fn run_payment_summary() {
 
     let index_rate: u64 = std::env::var("PULSE_TIME_MS")
         .ok()
-        .map(|i| i.parse().ok())
-        .flatten()
+        .and_then(|i| i.parse().ok())
         .unwrap_or(4000);
 
-    start(app_state, app_config, index_rate).await;
+    let confirmation_check_rate: u64 = std::env::var("CONFIRMATION_CHECK_MS")
+        .ok()
+        .and_then(|i| i.parse().ok())
+        .unwrap_or(15000);
+
+    start(app_state, app_config, index_rate, confirmation_check_rate).await;
 }
 
-async fn start(mut app_state: AppState, app_config: AppConfig, tick_interval_time_ms: u64) {
+async fn start(
+    app_state: AppState,
+    app_config: PaymentSummaryConfig,
+    tick_interval_time_ms: u64,
+    confirmation_interval_time_ms: u64,
+) {
     let mut tick_interval = interval(Duration::from_millis(tick_interval_time_ms));
+    let mut confirmation_interval = interval(Duration::from_millis(confirmation_interval_time_ms));
 
     loop {
         select! {
             _ = tick_interval.tick() => {
 
 
-                app_state = poll_event_and_fetch_payment_summary(
-                    app_state,
-                    &app_config
-                ).await;
+                poll_event_and_fetch_payment_summary(&app_state, &app_config).await;
 
             }
 
-        }
-    }
-}
-
-async fn poll_event_and_fetch_payment_summary(
-    mut app_state: AppState,
-    app_config: &AppConfig,
-) -> AppState {
-    let offset = app_state.indexing_state.event_id_offset.clone();
-
-    info!(
-        "app_state.indexing_state.event_offset  {:?}",
-        app_state.indexing_state.event_id_offset
-    );
-
-    let mut psql_db = app_state.database.lock().await;
-
-    //may need a reconnection scheme ?
-
-    let next_event =
-        EventsModel::find_next_event_of_type("PaidInvoice".to_string(), offset, &mut psql_db).await;
-
-    //  drop (psql_db);
+            _ = confirmation_interval.tick() => {
 
-    info!("found PAID INVOICE ");
-
-    match next_event {
-        Err(_) => {
-            // reset event offset
-
-            info!(" reset event offset  ");
-
-            app_state.indexing_state.event_id_offset = None;
-        }
-
-        Ok((next_event_id, ref event_data)) => {
-            if let Some(paid_invoice_data) = PaidInvoiceData::extract_paid_invoice_data(event_data)
-            {
-                // let contract_add_result = add_contract_to_watchlist( watchlist_id,  &deployed_pool_data ).await;
-                match fetch_payment_summary(&paid_invoice_data, app_config).await {
-                    Ok(loan_summary) => {
-                        println!(" PaymentsModel::insert_or_update_one {:?} ", loan_summary);
-
-                        let _inserted =
-                            PaymentsModel::insert_or_update_one(loan_summary, &mut psql_db).await;
-                    }
-
-                    Err(e) => {
-                        warn!("{:?}", e);
-                    }
+                if let Err(e) = check_payment_confirmations(&app_state, &app_config).await {
+                    warn!("payment confirmation check failed {:?}", e);
                 }
 
-                app_state.indexing_state.event_id_offset = Some(next_event_id.clone());
-                println!("increment event offset  ");
-            }else {
-
-                warn!("could not extract extract_paid_invoice_data {:?}", event_data );
-
             }
+
         }
     }
-
-    drop(psql_db);
-    drop(next_event);
-
-    app_state
 }
 
-#[derive(Debug, Clone)]
-struct PaidInvoiceData {
-    contract_address: Address,
-
-    uuid: Vec<u8>,
-    chain_id: u64,
-
-    tx_hash: H256,
-
-    from_address: Address,
-
-    block_number: Option<U64>,
+/// Max number of unfinalized payments re-checked against the canonical chain per tick
+const CONFIRMATION_CHECK_BATCH_SIZE: i64 = 100;
+
+/// Where the canonical chain puts an unfinalized payment
+enum ConfirmationCheck {
+    /// The block the payment was recorded in is no longer canonical
+    Reorged { orphaned_block_hash: H256 },
+    Canonical {
+        status: PaymentStatus,
+        confirmations: u64,
+        block_hash: H256,
+    },
 }
 
-impl PaidInvoiceData {
-    fn extract_paid_invoice_data(contract_event: &ContractEvent) -> Option<Self> {
-        println!("extract {:?}", contract_event);
-        let event_name = &contract_event.name;
+/// Walks unfinalized payments and compares their stored block hash against the canonical chain.
+/// Payments whose block was orphaned are marked reorged along with their webhook triggers,
+/// the rest advance through detected -> confirmed -> finalized based on the network's depths.
+/// The database is not held during rpc calls, so a payment that moved to another block in the
+/// meantime is left for the next tick.
+async fn check_payment_confirmations(
+    app_state: &AppState,
+    app_config: &PaymentSummaryConfig,
+) -> Result<(), PaymentSummaryError> {
+    // payments on chains that cannot be checked would fill every batch
+    let checkable_chain_ids: Vec<i64> = app_config
+        .providers
+        .chain_ids()
+        .into_iter()
+        .filter(|chain_id| app_config.networks.get(*chain_id).is_some())
+        .map(|chain_id| chain_id as i64)
+        .collect();
+
+    let unfinalized_payments = {
+        let psql_db = app_state.database.lock().await;
+
+        PaymentsModel::find_unfinalized_payments(
+            &checkable_chain_ids,
+            CONFIRMATION_CHECK_BATCH_SIZE,
+            &psql_db,
+        )
+        .await
+        .map_err(|e| PaymentSummaryError::DatabaseError(e.to_string()))?
+    };
 
-        if event_name != "PaidInvoice" {
-            return None;
+    // None for a chain whose head could not be read this tick
+    let mut head_block_numbers: HashMap<u64, Option<U64>> = HashMap::new();
+    let mut checks = Vec::new();
+
+    for payment in unfinalized_payments {
+        let chain_id = payment.entry.chain_id as u64;
+
+        let (Some(network), Some(providers), Some(payment_at_block)) = (
+            app_config.networks.get(chain_id),
+            app_config.providers.get(chain_id),
+            payment.entry.payment_at_block,
+        ) else {
+            continue;
+        };
+
+        if let Entry::Vacant(entry) = head_block_numbers.entry(chain_id) {
+            let head_block_number = providers
+                .call(|provider| async move { provider.get_block_number().await })
+                .await
+                .map_err(|e| warn!("could not read the head block of chain {}: {}", chain_id, e))
+                .ok();
+
+            entry.insert(head_block_number);
         }
 
-        let chain_id = contract_event.chain_id;
-        let contract_address = contract_event.address;
-
-        let tx_hash = contract_event.transaction_hash?;
-
-        let block_number = contract_event.block_number;
-
-        let mut uuid_opt = None;
-
-        let mut from_address_opt = None;
-
-        for arg in &contract_event.args {
-            if arg.name == "uuid" {
-                match &arg.value {
-                    Token::FixedBytes(uuid_bytes) => uuid_opt = Some(uuid_bytes),
-                    _ => {}
-                }
+        let Some(head_block_number) = head_block_numbers[&chain_id] else {
+            continue;
+        };
+
+        let canonical_block_hash = match providers
+            .call_critical(|provider| async move {
+                provider
+                    .get_block(payment_at_block as u64)
+                    .await
+                    .map(|block| block.and_then(|block| block.hash))
+            })
+            .await
+        {
+            Ok(Some(block_hash)) => block_hash,
+
+            // the node has not caught up to this block yet
+            Ok(None) => continue,
+
+            Err(e) => {
+                warn!(
+                    "could not read block {} of chain {} for payment {:?}: {}",
+                    payment_at_block, chain_id, payment.id, e
+                );
+                continue;
             }
-
-            if arg.name == "from" {
-                match &arg.value {
-                    Token::Address(from_addr) => from_address_opt = Some(from_addr),
-                    _ => {}
+        };
+
+        let stored_block_hash = payment.entry.payment_at_block_hash.as_ref().map(|h| h.0);
+
+        let check = match stored_block_hash.filter(|h| *h != canonical_block_hash) {
+            Some(orphaned_block_hash) => ConfirmationCheck::Reorged {
+                orphaned_block_hash,
+            },
+            None => {
+                let confirmations = head_block_number
+                    .as_u64()
+                    .saturating_sub(payment_at_block as u64)
+                    + 1;
+
+                ConfirmationCheck::Canonical {
+                    status: PaymentStatus::from_confirmations(
+                        confirmations,
+                        network.confirmation_depth,
+                        network.finality_depth,
+                    ),
+                    confirmations,
+                    block_hash: canonical_block_hash,
                 }
             }
-        }
-
-        // let bid_id = U256::zero(); // FIX
-
-        if let Some(uuid) = uuid_opt {
-            if let Some(from_address) = from_address_opt {
-                return Some(Self {
-                    contract_address,
-                    chain_id,
-                    uuid: uuid.to_vec(),
-                    tx_hash,
-                    block_number,
-                    from_address: *from_address,
-                });
-            }
-        }
+        };
 
-        return None;
+        checks.push((payment, check));
     }
-}
-
-#[derive(Debug, thiserror::Error)]
-pub enum PaymentSummaryError {
-    #[error("Failed to fetch summary from contract: {0}")]
-    ContractCallError(#[from] ethers::contract::ContractError<Provider<Http>>),
-
-    #[error("Failed to parse RPC URL for chain {0}")]
-    RpcUrlParseError(String),
 
-    #[error("Network provider initialization failed: {0}")]
-    ProviderError(#[from] ethers::providers::ProviderError),
+    let psql_db = app_state.database.lock().await;
 
-    #[error("abi parse failed: {0}")]
-    ParseError(#[from] url::ParseError),
+    for (payment, check) in checks {
+        if let Err(e) = apply_confirmation_check(&payment, check, &psql_db).await {
+            warn!(
+                "could not update confirmations of payment {:?}: {}",
+                payment.id, e
+            );
+        }
+    }
 
-    #[error("Reqwest error: {0}")]
-    ReqwestError(#[from] reqwest::Error),
+    Ok(())
 }
 
-// pass in the chain_id and contract_address
-async fn fetch_payment_summary(
-    payment_data: &PaidInvoiceData,
-    app_config: &AppConfig,
-) -> Result<PaymentSummary, PaymentSummaryError> {
-    abigen!(
-        Payspec,
-        "./abi/payspec.json",
-        event_derives(serde::Deserialize, serde::Serialize)
-    );
-
-    info!("fetch_payment_summary {:?}", payment_data);
-
-    let payspec_contract_address = &payment_data.contract_address;
-
-    let chain_id = &payment_data.chain_id;
-
-    let block_number = &payment_data.block_number;
-
-    let transaction_hash = &payment_data.tx_hash;
-
-    let rpc_url = app_config
-        .rpc_uri_map
-        .get(chain_id)
-        .ok_or_else(|| PaymentSummaryError::RpcUrlParseError(chain_id.to_string()))?;
-
-    let provider =
-        Provider::<Http>::try_from(rpc_url).map_err(|e| PaymentSummaryError::ParseError(e))?;
-
-    let from_address = &payment_data.from_address;
-    let uuid: &Vec<u8> = &payment_data.uuid;
-
-    // ----
-
-    let payment_at_block = block_number.clone();
-
-    let mut payment_at_block_timestamp = None;
-    let mut payment_at_unix_days_index = None;
+async fn apply_confirmation_check(
+    payment: &SelectedRecord<PaymentSummary>,
+    check: ConfirmationCheck,
+    psql_db: &Database,
+) -> Result<(), PostgresModelError> {
+    match check {
+        ConfirmationCheck::Reorged {
+            orphaned_block_hash,
+        } => {
+            let mut client = psql_db.connect().await?;
+            let transaction = client.transaction().await?;
+
+            let reorged = PaymentsModel::mark_reorged(
+                payment.id.0,
+                &DomainH256(orphaned_block_hash),
+                &transaction,
+            )
+            .await?;
+
+            // moved to another block since it was read
+            if !reorged {
+                return Ok(());
+            }
 
-    if let Some(payment_at_block) = payment_at_block {
-        payment_at_block_timestamp = fetch_block_timestamp(payment_at_block, &provider)
-            .await
-            .ok();
+            warn!(
+                "payment {:?} at block {:?} was reorged out of chain {}",
+                payment.id, payment.entry.payment_at_block, payment.entry.chain_id
+            );
+
+            InvoicePaymentsModel::update_status_for_transaction(
+                &payment.entry.transaction_hash,
+                payment.entry.chain_id,
+                &PaymentStatus::Reorged,
+                &transaction,
+            )
+            .await?;
+
+            let transaction_hash = format!("{:?}", payment.entry.transaction_hash.0);
+            let orphaned_block_hash = format!("{:?}", orphaned_block_hash);
+
+            // events not fanned out yet are skipped first, a fan-out already holding them
+            // commits its triggers before this goes on to mark them
+            DomainEventsModel::skip_unpublished_for_reorged_payment(
+                &transaction_hash,
+                payment.entry.chain_id,
+                &orphaned_block_hash,
+                &transaction,
+            )
+            .await?;
+
+            WebhookTriggersModel::mark_reorged_for_payment(
+                &transaction_hash,
+                payment.entry.chain_id,
+                &orphaned_block_hash,
+                &transaction,
+            )
+            .await?;
+
+            transaction.commit().await?;
+        }
+        ConfirmationCheck::Canonical {
+            status,
+            confirmations,
+            block_hash,
+        } => {
+            let updated = PaymentsModel::update_confirmations(
+                payment.id.0,
+                &status,
+                confirmations as i64,
+                &DomainH256(block_hash),
+                psql_db,
+            )
+            .await?;
+
+            if !updated {
+                return Ok(());
+            }
 
-        if let Some(accepted_at_block_timestamp) = payment_at_block_timestamp {
-            payment_at_unix_days_index =
-                payment_at_block_timestamp.map(|t| UnixDayIndex::from_timestamp(t));
+            // per-recipient ledger rows follow the status of the payment they belong to
+            InvoicePaymentsModel::update_status_for_transaction(
+                &payment.entry.transaction_hash,
+                payment.entry.chain_id,
+                &status,
+                psql_db,
+            )
+            .await?;
         }
     }
 
-    //  info!("accepted_at_block {:?}" , payment_at_block );
-
-    //  info!("accepted_at_unix_days_index {:?}" , payment_at_unix_days_index );
-
-    // ----
-
-    let payspec_protocol_contract = Payspec::new(payment_data.contract_address, provider.into());
-
-    // Ensure we have exactly 32 bytes for the UUID
-    let mut uuid_bytes = [0u8; 32];
-    let copy_len = std::cmp::min(uuid.len(), 32);
+    Ok(())
+}
 
-    // If the UUID is less than 32 bytes, it will be padded with zeros
-    // If it's longer, it will be truncated
-    uuid_bytes[..copy_len].copy_from_slice(&uuid[..copy_len]);
+async fn poll_event_and_fetch_payment_summary(
+    app_state: &AppState,
+    app_config: &PaymentSummaryConfig,
+) {
+    let mut psql_db = app_state.database.lock().await;
 
-    let domain_uuid_bytes = DomainBytes32(uuid_bytes);
+    if let Err(e) = index_next_payment_summary(&mut psql_db, app_config).await {
+        // the cursor did not move, so the same event is retried next tick
+        warn!("could not index payment summary {:?}", e);
+    }
+}
 
-    let payment_details = payspec_protocol_contract
-        .get_invoice_payment_details(domain_uuid_bytes.0)
-        .await?;
-    info!("payment_details {:?}", payment_details);
+/// Indexes the PaidInvoice event after this bot's cursor. The payment row and the
+/// cursor advance commit together, so each event is applied exactly once.
+async fn index_next_payment_summary(
+    psql_db: &mut Database,
+    app_config: &PaymentSummaryConfig,
+) -> Result<(), PaymentSummaryError> {
+    let cursor = BotCursorsModel::find_or_create(BOT_NAME, psql_db)
+        .await
+        .map_err(|e| PaymentSummaryError::DatabaseError(e.to_string()))?;
+
+    let Ok((next_event_id, event_data)) =
+        EventsModel::find_next_event_of_type("PaidInvoice".to_string(), cursor, psql_db).await
+    else {
+        // caught up
+        return Ok(());
+    };
 
-    //  access the tuple elements:
-    let paid_by = payment_details.0;
-    let payment_token_address = payment_details.1;
-    let total_amount = payment_details.2;
+    info!("found PAID INVOICE {}", next_event_id);
 
-    let nonce = payment_details.3;
+    let payment_summary = match PaidInvoiceData::extract_paid_invoice_data(&event_data) {
+        Some(paid_invoice_data) => {
+            match fetch_payment_summary(&paid_invoice_data, app_config, psql_db).await {
+                Ok(payment_summary) => Some(payment_summary),
 
-    let pay_to_array = payment_details.4;
-    let pay_to_amounts = payment_details.5;
+                // a chain without an rpc will never succeed, skip past it
+                Err(PaymentSummaryError::RpcUrlParseError(chain_id)) => {
+                    warn!("no rpc configured for chain {}", chain_id);
+                    None
+                }
 
-    let payment_summary = PaymentSummary {
-        uuid: domain_uuid_bytes.clone(),
+                Err(e) => return Err(e),
+            }
+        }
+        None => {
+            warn!(
+                "could not extract extract_paid_invoice_data {:?}",
+                event_data
+            );
+            None
+        }
+    };
 
-        chain_id: *chain_id as i64,
+    commit_payment_summary(psql_db, cursor, next_event_id, payment_summary)
+        .await
+        .map_err(|e| PaymentSummaryError::DatabaseError(e.to_string()))
+}
 
-        //  paid_by  , ...
-        from_address: from_address.clone().into(),
+async fn commit_payment_summary(
+    psql_db: &Database,
+    cursor: Option<i32>,
+    next_event_id: i32,
+    payment_summary: Option<PaymentSummary>,
+) -> Result<(), PostgresModelError> {
+    let mut client = psql_db.connect().await?;
+    let transaction = client.transaction().await?;
+
+    if !BotCursorsModel::advance(BOT_NAME, cursor, next_event_id, &transaction).await? {
+        info!("event {} was already indexed", next_event_id);
+        return Ok(());
+    }
 
-        payment_token_address: DomainEthAddress(payment_token_address),
-        //   totalAmount: DomainUint256(total_amount),
-        pay_to_array: DomainPayToArray(pay_to_array),
-        pay_to_amounts: DomainPayToAmounts(pay_to_amounts),
+    if let Some(payment_summary) = payment_summary {
+        let upsert =
+            PaymentsModel::insert_or_update_one(payment_summary.clone(), &transaction).await?;
 
-        payment_at_block: payment_at_block.map(|x| x.as_u64() as i64),
-        payment_at_block_timestamp: payment_at_block_timestamp.map(|x| x.into()),
-        payment_at_unix_days_index,
+        // recipients hear about a payment when it is first recorded, and again when it is
+        // mined in another block after a reorg
+        if upsert.needs_event() {
+            PaymentsModel::publish_payment_event(&payment_summary, &transaction).await?;
 
-        payspec_contract_address: DomainEthAddress(payspec_contract_address.clone()),
-        nonce: DomainUint256(nonce),
+            info!("published payment event for event {}", next_event_id);
+        }
+    }
 
-        transaction_hash: DomainH256(transaction_hash.clone()),
-    };
+    transaction.commit().await?;
 
-    Ok(payment_summary)
+    Ok(())
 }
 
 /*
@@ -422,35 +468,3 @@ pub struct PaymentSummary {
 
 }
 */
-
-pub fn load_rpc_uris(chain_ids: &[u64]) -> HashMap<u64, String> {
-    let mut rpc_uri_map = HashMap::new();
-
-    for &chain_id in chain_ids {
-        let rpc_network = RpcNetwork::from_chain_id(chain_id);
-        if let Some(network) = rpc_network {
-            let rpc_url_env_var = network.get_rpc_url_env_var();
-            if let Ok(rpc_url) = env::var(rpc_url_env_var) {
-                rpc_uri_map.insert(chain_id, rpc_url);
-            }
-        }
-    }
-
-    rpc_uri_map
-}
-
-pub async fn fetch_block_timestamp(
-    block_number: U64,
-    provider: &Provider<Http>,
-) -> Result<DateTime<Utc>, Box<dyn std::error::Error>> {
-    // Fetch the block details
-    if let Some(block) = provider.get_block(block_number).await? {
-        if let Some(timestamp) = block.timestamp.try_into().ok() {
-            // Convert timestamp to DateTime<Utc>
-            let datetime = DateTime::from_timestamp(timestamp, 0).ok_or("Invalid timestamp")?;
-            return Ok(datetime);
-        }
-    }
-
-    Err("Block timestamp not found".into())
-}
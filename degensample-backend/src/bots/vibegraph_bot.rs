@@ -1,11 +1,15 @@
-use defirelay_backend::util::rpc_network::RpcNetwork;
+use defirelay_backend::config::NetworksConfig;
+use defirelay_backend::db::postgres::models::event_indexers_model::EventIndexersModel;
+use defirelay_backend::util::rpc_provider::RpcProviders;
+use defirelay_backend::util::rpc_proxy::start_rpc_proxy;
+use degen_sql::db::postgres::postgres_db::Database;
+use log::{info, warn};
 
 //use degen_sql::db::postgres::postgres_db::DatabaseCredentials;
 
 use ethers::abi::Abi;
 use serde_json::from_str;
 use std::collections::HashMap;
-use std::env;
 use std::fs;
 use vibegraph::{AppConfig, IndexingConfig, Vibegraph};
 
@@ -30,14 +34,9 @@ pub async fn run_vibegraph_bot() {
     /* let rpc_uri =  std::env::var("RPC_URL")
     .expect("RPC_URL must be set");*/
 
-    let networks = vec![
-        RpcNetwork::Mainnet,
-        RpcNetwork::Base,
-        //  RpcNetwork::Arbitrum,
-        //  RpcNetwork::Polygon,
-    ];
+    let networks = NetworksConfig::load();
 
-    let chain_ids: Vec<u64> = networks.iter().map(|n| n.get_chain_id()).collect();
+    let chain_ids: Vec<u64> = networks.chain_ids();
 
     let indexing_config = IndexingConfig {
         // rpc_uri,
@@ -57,7 +56,10 @@ pub async fn run_vibegraph_bot() {
 
     //  let (contract_config_map, chain_ids) = load_contract_configs(&config_folder_path);
     let contract_abi_map = load_contract_abis(&abi_folder_path);
-    let rpc_uri_map = load_rpc_uris(&chain_ids);
+    // vibegraph builds its own providers from urls, so it is handed local ones that send
+    // each request through the rpc pool of the chain
+    let rpc_uri_map = start_rpc_proxy(RpcProviders::from_networks(&networks))
+        .expect("Could not start the rpc proxy");
 
     let event_indexer_table_name = Some("event_indexers".to_string());
 
@@ -66,11 +68,13 @@ pub async fn run_vibegraph_bot() {
         contract_abi_map,
         rpc_uri_map,
 
-        db_conn_url,
+        db_conn_url: db_conn_url.clone(),
 
         event_indexer_table_name,
     };
 
+    register_payspec_indexers(&networks, &db_conn_url).await;
+
     println!("booting scrape bot  ");
 
     Vibegraph::init(&app_config).await;
@@ -114,18 +118,39 @@ pub fn load_contract_abis(config_folder_path: &str) -> HashMap<String, Abi> {
     contract_abi_map
 }
 
-pub fn load_rpc_uris(chain_ids: &[u64]) -> HashMap<u64, String> {
-    let mut rpc_uri_map = HashMap::new();
+/// Makes sure every payspec contract of an enabled network has an event indexer,
+/// so enabling a network in config/networks.json is all it takes to start indexing it
+async fn register_payspec_indexers(networks: &NetworksConfig, db_conn_url: &str) {
+    let database = match Database::new(db_conn_url.to_string(), None) {
+        Ok(database) => database,
+        Err(e) => {
+            warn!("could not connect to register event indexers: {}", e);
+            return;
+        }
+    };
 
-    for &chain_id in chain_ids {
-        let rpc_network = RpcNetwork::from_chain_id(chain_id);
-        if let Some(network) = rpc_network {
-            let rpc_url_env_var = network.get_rpc_url_env_var();
-            if let Ok(rpc_url) = env::var(rpc_url_env_var) {
-                rpc_uri_map.insert(chain_id, rpc_url);
+    for network in networks.enabled() {
+        for contract_address in &network.payspec_contracts {
+            match EventIndexersModel::register(
+                &format!("payspec_{}", network.name),
+                "payspec",
+                *contract_address,
+                network.chain_id,
+                network.start_block,
+                &database,
+            )
+            .await
+            {
+                Ok(true) => info!(
+                    "registered payspec indexer for {:?} on {}",
+                    contract_address, network.name
+                ),
+                Ok(false) => {}
+                Err(e) => warn!(
+                    "could not register payspec indexer for {:?} on {}: {}",
+                    contract_address, network.name, e
+                ),
             }
         }
     }
-
-    rpc_uri_map
 }
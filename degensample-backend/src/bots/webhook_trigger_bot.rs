@@ -1,9 +1,24 @@
+use defirelay_backend::db::postgres::models::webhook_deliveries_model::{
+    DeliveryErrorKind, WebhookDeliveriesModel, WebhookDelivery,
+};
+use defirelay_backend::db::postgres::models::webhook_triggers_model::WebhookTriggerJoined;
 use defirelay_backend::db::postgres::models::webhook_triggers_model::WebhookTriggerStatus;
 use defirelay_backend::db::postgres::models::webhook_triggers_model::WebhookTriggersModel;
+use defirelay_backend::db::postgres::models::webhook_urls_model::WebhookUrlsModel;
 use defirelay_backend::types::defi_relay_webhook_payload::DefiRelayWebhookPayload;
-use defirelay_backend::util::http_request::perform_req;
+use defirelay_backend::types::selected_record::SelectedRecord;
+use defirelay_backend::types::webhook_batches::{WebhookBatchEvent, WebhookBatchPayload};
+use defirelay_backend::util::delivery_sink::QueueSinks;
+use defirelay_backend::util::webhook_delivery::{
+    deliver_to_queue, deliver_webhook, WebhookHttpClient,
+};
+use defirelay_backend::util::webhook_fanout::fan_out_domain_events;
+use defirelay_backend::util::webhook_retry::WebhookRetryPolicy;
+use defirelay_backend::util::webhook_url_guard::WebhookUrlPolicy;
+use chrono::Utc;
 use dotenvy::dotenv;
-use reqwest::StatusCode;
+use futures::future::join_all;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::select;
@@ -13,26 +28,38 @@ use tokio::time::interval;
 
 
 Finds webhook triggers that have not been acknowledged and sends POST !
+Urls that point at a message broker are published to instead, see util/delivery_sink.rs
+
+Several replicas of this bot can run at once: each claims a batch of triggers
+with FOR UPDATE SKIP LOCKED and delivers the batch concurrently.
 
 
 RUST_LOG=info cargo run --bin webhook_trigger_bot
 
+WEBHOOK_WORKERS=16                   triggers claimed and delivered at once
+WEBHOOK_REQUEST_TIMEOUT_SECS=10      per request or broker publish, connecting included
+WEBHOOK_ALLOW_PRIVATE_TARGETS=false  see util/webhook_url_guard.rs
+
 
 */
 
+use degen_sql::db::postgres::models::model::PostgresModelError;
 use degen_sql::db::postgres::postgres_db::Database;
-use tokio::sync::Mutex;
+use log::{info, warn};
 
-#[derive(Default)]
-pub struct IndexingState {
-    //event_offset: u64,
-    event_id_offset: Option<i32>,
-}
+/// A claim outlives the request timeout by this much, so a trigger is never claimed
+/// twice while its delivery is still being recorded
+const CLAIM_LEASE_MARGIN: Duration = Duration::from_secs(30);
 
-struct AppState {
-    pub database: Arc<Mutex<Database>>,
+const DOMAIN_EVENT_BATCH_SIZE: i64 = 100;
 
-    pub indexing_state: IndexingState,
+struct AppState {
+    pub database: Arc<Database>,
+    pub http_client: WebhookHttpClient,
+    pub queue_sinks: QueueSinks,
+    pub retry_policy: WebhookRetryPolicy,
+    pub workers: i64,
+    pub claim_lease: Duration,
 }
 
 #[tokio::main]
@@ -49,23 +76,40 @@ pub async fn run_webhook_trigger_bot() {
 
     // Attach database with proper error handling
     let database = match Database::new(db_conn_url.clone(), None) {
-        Ok(db) => Arc::new(Mutex::new(db)), // Wrap in Arc<Mutex<T>> properly
+        Ok(db) => Arc::new(db),
         Err(e) => {
             eprintln!("Failed to connect to database: {}", e);
             return;
         }
     };
 
-    // let networks = vec![RpcNetwork::Mainnet, RpcNetwork::Base];
+    let request_timeout = Duration::from_secs(
+        std::env::var("WEBHOOK_REQUEST_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10),
+    );
 
-    // let chain_ids: Vec<u64> = networks.iter().map(|n| n.get_chain_id()).collect();
+    let workers: i64 = std::env::var("WEBHOOK_WORKERS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(16);
 
-    // let rpc_uri_map = load_rpc_uris(&chain_ids);
+    let http_client = match WebhookHttpClient::new(request_timeout, WebhookUrlPolicy::from_env()) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("Failed to build http client: {}", e);
+            return;
+        }
+    };
 
     let app_state = AppState {
-        database: Arc::clone(&database),
-
-        indexing_state: IndexingState::default(),
+        database,
+        http_client,
+        queue_sinks: QueueSinks::new(request_timeout, WebhookUrlPolicy::from_env()),
+        retry_policy: WebhookRetryPolicy::from_env(),
+        workers: workers.max(1),
+        claim_lease: request_timeout + CLAIM_LEASE_MARGIN,
     };
 
     //   let app_config = AppConfig {  };
@@ -79,7 +123,7 @@ pub async fn run_webhook_trigger_bot() {
     start(app_state, index_rate).await;
 }
 
-async fn start(mut app_state: AppState, tick_interval_time_ms: u64) {
+async fn start(app_state: AppState, tick_interval_time_ms: u64) {
     let mut tick_interval = interval(Duration::from_millis(tick_interval_time_ms));
 
     loop {
@@ -87,10 +131,11 @@ async fn start(mut app_state: AppState, tick_interval_time_ms: u64) {
             _ = tick_interval.tick() => {
 
 
-                app_state = poll_trigger_and_emit_post(
-                    app_state,
-                  //  &app_config
-                ).await;
+                publish_domain_events(&app_state).await;
+
+                drain_due_triggers(&app_state).await;
+
+                drain_due_batches(&app_state).await;
 
             }
 
@@ -98,90 +143,374 @@ async fn start(mut app_state: AppState, tick_interval_time_ms: u64) {
     }
 }
 
-/*
+/// Turns events published by the models into triggers, see util/webhook_fanout.rs
+async fn publish_domain_events(app_state: &AppState) {
+    loop {
+        match fan_out_domain_events(DOMAIN_EVENT_BATCH_SIZE, app_state.database.as_ref()).await {
+            Ok(published) if (published as i64) < DOMAIN_EVENT_BATCH_SIZE => return,
+            Ok(_) => {}
+            Err(e) => {
+                warn!("could not fan out domain events {:?}", e);
+                return;
+            }
+        }
+    }
+}
 
-Find the next webhook_trigger who is 'pending' and has < 3 attempts
-  Increment its attempts and make an attempt (POST) !
+/*
 
+Claim the oldest due 'pending' trigger of each webhook url and POST them all at once
+  Any 2xx marks it sent. Otherwise it is scheduled again with exponential backoff,
+  and moves to 'dead_letter' after WEBHOOK_MAX_ATTEMPTS attempts
+  Later triggers for the same url wait until the one before them is settled
+  A Retry-After header pushes the next attempt back to at least the time asked for
+  After WEBHOOK_DISABLE_AFTER_FAILURES failures in a row, or a 410 Gone, the url is disabled
+  and a 'webhook_url_disabled' event is published to the owner. Its pending triggers
+  wait until the owner re-enables it
+  Urls that opted in to batching get their due triggers in one POST instead, once a batch
+  is full or has waited long enough, see types/webhook_batches.rs
 
 */
 
-async fn poll_trigger_and_emit_post(
-    mut app_state: AppState,
-    // app_config: &AppConfig,
-) -> AppState {
-    let offset = app_state.indexing_state.event_id_offset.clone();
-
-    let psql_db = app_state.database.lock().await;
+/// Delivers batches until fewer triggers are due than there are workers
+async fn drain_due_triggers(app_state: &AppState) {
+    loop {
+        let claimed = match WebhookTriggersModel::claim_due_triggers(
+            app_state.workers,
+            app_state.claim_lease.as_secs_f64(),
+            app_state.database.as_ref(),
+        )
+        .await
+        {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                warn!("could not claim webhook triggers {:?}", e);
+                return;
+            }
+        };
 
-    let next_unacked_trigger =
-        WebhookTriggersModel::find_next_pending_trigger_with_offset(offset, &psql_db).await;
-    drop(psql_db);
+        let claimed_count = claimed.len() as i64;
 
-    match next_unacked_trigger {
-        Err(_) => {
-            //reset?
+        let results = join_all(
+            claimed
+                .into_iter()
+                .map(|trigger| deliver_trigger(app_state, trigger)),
+        )
+        .await;
 
-            app_state.indexing_state.event_id_offset = None;
+        for result in results {
+            if let Err(e) = result {
+                warn!("could not process webhook trigger {:?}", e);
+            }
         }
 
-        Ok(Some(selected_trig_record)) => {
-            let trig_id = selected_trig_record.id.clone();
-
-            app_state.indexing_state.event_id_offset = Some(trig_id.clone().into());
+        if claimed_count < app_state.workers {
+            return;
+        }
+    }
+}
 
-            // let trig =  & selected_trig_record.entry;
+/// Posts a claimed trigger and records the outcome. The POST itself cannot be rolled back;
+/// if recording fails the claim expires and the trigger is delivered again, which receivers
+/// handle through the stable event id.
+async fn deliver_trigger(
+    app_state: &AppState,
+    selected_trig_record: SelectedRecord<WebhookTriggerJoined>,
+) -> Result<(), PostgresModelError> {
+    let psql_db = app_state.database.as_ref();
+
+    let trig_id: i32 = selected_trig_record.id.clone().into();
+    let webhook_url_id = selected_trig_record.entry.webhook_trigger.webhook_id;
+    let attempts_made = selected_trig_record.entry.webhook_trigger.attempts + 1;
+
+    //do the POST thing !
+
+    let webhook_url = selected_trig_record.entry.webhook_url.clone();
+
+    let payload = DefiRelayWebhookPayload::from_webhook_trigger_joined(selected_trig_record);
+
+    let now = Utc::now();
+    let request =
+        match payload.to_signed_request(&webhook_url.active_signing_secrets(now), now.timestamp()) {
+            Ok(request) => request,
+            Err(e) => {
+                // retrying cannot fix a stored event that has no body in the url's version
+                warn!("webhook trigger {} cannot be sent: {}", trig_id, e);
+                WebhookTriggersModel::update_status(trig_id, WebhookTriggerStatus::Failed, psql_db)
+                    .await?;
+                return Ok(());
+            }
+        };
+
+    let delivery = match webhook_url.queue_target() {
+        Some(target) => {
+            deliver_to_queue(
+                &app_state.queue_sinks,
+                &target,
+                &request,
+                trig_id,
+                webhook_url_id,
+                attempts_made,
+                payload.redelivery,
+            )
+            .await
+        }
+        None => {
+            deliver_webhook(
+                &app_state.http_client,
+                &request,
+                trig_id,
+                webhook_url_id,
+                attempts_made,
+                payload.redelivery,
+            )
+            .await
+        }
+    };
 
-            println!("poll 3 ");
+    info!(
+        "webhook trigger {} res {:?} {:?} in {}ms",
+        trig_id, delivery.response_status, delivery.error_kind, delivery.latency_ms
+    );
 
-            //do the POST thing !
+    record_trigger_attempt(app_state, trig_id, attempts_made, &delivery).await?;
+    record_url_attempt(app_state, webhook_url_id, &delivery).await
+}
 
-            let payload =
-                DefiRelayWebhookPayload::from_webhook_trigger_joined(selected_trig_record);
+/// Delivers batches until fewer urls have a batch ready than there are workers
+async fn drain_due_batches(app_state: &AppState) {
+    loop {
+        let claimed = match WebhookTriggersModel::claim_due_batches(
+            app_state.workers,
+            app_state.claim_lease.as_secs_f64(),
+            app_state.database.as_ref(),
+        )
+        .await
+        {
+            Ok(claimed) => claimed,
+            Err(e) => {
+                warn!("could not claim webhook batches {:?}", e);
+                return;
+            }
+        };
+
+        let mut batches: BTreeMap<i32, Vec<SelectedRecord<WebhookTriggerJoined>>> = BTreeMap::new();
+        for trigger in claimed {
+            batches
+                .entry(trigger.entry.webhook_trigger.webhook_id)
+                .or_default()
+                .push(trigger);
+        }
 
-            //  let post_endpoint_data = payload.to_endpoint_url_and_data();
+        let batch_count = batches.len() as i64;
 
-            let webhook_response = perform_req(&payload).await;
-            // increment attempt !
+        let results = join_all(
+            batches
+                .into_values()
+                .map(|triggers| deliver_batch(app_state, triggers)),
+        )
+        .await;
 
-            println!("webhook res {:?} ", webhook_response);
+        for result in results {
+            if let Err(e) = result {
+                warn!("could not process webhook batch {:?}", e);
+            }
+        }
 
-            let mut webhook_succeeded = false;
+        if batch_count < app_state.workers {
+            return;
+        }
+    }
+}
 
-            if let Ok(webhook_response) = webhook_response {
-                if webhook_response.status() == StatusCode::OK {
-                    // 200
+/// Posts the claimed triggers of one url in a single request, then settles each trigger
+/// on its own: events a 2xx response did not acknowledge are retried like failed ones.
+async fn deliver_batch(
+    app_state: &AppState,
+    triggers: Vec<SelectedRecord<WebhookTriggerJoined>>,
+) -> Result<(), PostgresModelError> {
+    let psql_db = app_state.database.as_ref();
 
-                    webhook_succeeded = true;
-                }
+    let Some(first) = triggers.first() else {
+        return Ok(());
+    };
+    let webhook_url = first.entry.webhook_url.clone();
+    let webhook_url_id = first.entry.webhook_trigger.webhook_id;
+
+    let mut events = Vec::new();
+    // trigger id, attempt and whether it is a redelivery, for each event in the batch
+    let mut attempts = Vec::new();
+    let mut custom_headers = Vec::new();
+
+    for record in triggers {
+        let trig_id: i32 = record.id.clone().into();
+        let attempts_made = record.entry.webhook_trigger.attempts + 1;
+
+        let payload = DefiRelayWebhookPayload::from_webhook_trigger_joined(record);
+
+        match payload.body() {
+            Ok(body) => {
+                events.push(WebhookBatchEvent {
+                    id: payload.event_id(),
+                    body,
+                });
+                attempts.push((trig_id, attempts_made, payload.redelivery));
+                custom_headers = payload.custom_headers.to_pairs();
             }
-
-            if webhook_succeeded {
-                let psql_db = app_state.database.lock().await;
-                let _incremented = WebhookTriggersModel::update_status(
-                    trig_id.clone().into(),
-                    WebhookTriggerStatus::Sent,
-                    &psql_db,
-                )
-                .await;
-                drop(psql_db);
-            } else {
-                let psql_db = app_state.database.lock().await;
-                let _incremented = WebhookTriggersModel::increment_trigger_attempts(
-                    trig_id.clone().into(),
-                    &psql_db,
-                )
-                .await;
-                drop(psql_db);
+            Err(e) => {
+                warn!("webhook trigger {} cannot be sent: {}", trig_id, e);
+                WebhookTriggersModel::update_status(trig_id, WebhookTriggerStatus::Failed, psql_db)
+                    .await?;
             }
         }
+    }
 
-        Ok(None) => {
-            //reset?
+    let Some(&(first_trig_id, first_attempt, first_redelivery)) = attempts.first() else {
+        return Ok(());
+    };
 
-            app_state.indexing_state.event_id_offset = None;
+    let event_ids: Vec<String> = events.iter().map(|event| event.id.clone()).collect();
+
+    let now = Utc::now();
+    let request = WebhookBatchPayload::new(webhook_url_id, events).to_signed_request(
+        webhook_url.webhook_url.clone(),
+        custom_headers,
+        &webhook_url.active_signing_secrets(now),
+        now.timestamp(),
+    );
+
+    let delivery = deliver_webhook(
+        &app_state.http_client,
+        &request,
+        first_trig_id,
+        webhook_url_id,
+        first_attempt,
+        first_redelivery,
+    )
+    .await;
+
+    info!(
+        "webhook batch of {} res {:?} {:?} in {}ms",
+        attempts.len(),
+        delivery.response_status,
+        delivery.error_kind,
+        delivery.latency_ms
+    );
+
+    for ((trig_id, attempts_made, redelivery), event_id) in attempts.into_iter().zip(event_ids) {
+        let mut event_delivery = delivery.clone();
+        event_delivery.webhook_trigger_id = trig_id;
+        event_delivery.attempt = attempts_made;
+        event_delivery.redelivery = redelivery;
+
+        let unacknowledged = delivery
+            .acknowledged
+            .as_ref()
+            .is_some_and(|acknowledged| !acknowledged.contains(&event_id));
+
+        if delivery.succeeded() && unacknowledged {
+            event_delivery.error_kind = Some(DeliveryErrorKind::NotAcknowledged.to_string());
         }
+
+        record_trigger_attempt(app_state, trig_id, attempts_made, &event_delivery).await?;
+    }
+
+    record_url_attempt(app_state, webhook_url_id, &delivery).await
+}
+
+/// Stores an attempt at a trigger, then marks it sent or schedules it again with backoff
+async fn record_trigger_attempt(
+    app_state: &AppState,
+    trig_id: i32,
+    attempts_made: i32,
+    delivery: &WebhookDelivery,
+) -> Result<(), PostgresModelError> {
+    let psql_db = app_state.database.as_ref();
+    let retry_policy = &app_state.retry_policy;
+
+    WebhookDeliveriesModel::insert_one(delivery, psql_db).await?;
+
+    if delivery.succeeded() {
+        WebhookTriggersModel::update_status(trig_id, WebhookTriggerStatus::Sent, psql_db).await?;
+        return Ok(());
+    }
+
+    let next_attempt_at = Utc::now()
+        + chrono::Duration::from_std(
+            retry_policy.delay_after_response(attempts_made, delivery.retry_after),
+        )
+        .unwrap_or(chrono::Duration::MAX);
+
+    let status = WebhookTriggersModel::record_failed_attempt(
+        trig_id,
+        retry_policy.max_attempts,
+        next_attempt_at,
+        psql_db,
+    )
+    .await?;
+
+    if status == WebhookTriggerStatus::DeadLetter {
+        warn!(
+            "webhook trigger {} dead-lettered after {} attempts",
+            trig_id, attempts_made
+        );
+    }
+
+    Ok(())
+}
+
+/// Counts a request towards the health of its url
+async fn record_url_attempt(
+    app_state: &AppState,
+    webhook_url_id: i32,
+    delivery: &WebhookDelivery,
+) -> Result<(), PostgresModelError> {
+    if delivery.succeeded() {
+        return WebhookUrlsModel::record_delivery_success(
+            webhook_url_id,
+            app_state.database.as_ref(),
+        )
+        .await;
+    }
+
+    // an endpoint asking us to back off is up, so throttling does not count towards disabling it
+    let throttled = delivery.retry_after.is_some() && !delivery.endpoint_gone();
+
+    if !throttled {
+        record_url_failure(app_state, webhook_url_id, delivery.endpoint_gone()).await?;
+    }
+
+    Ok(())
+}
+
+async fn record_url_failure(
+    app_state: &AppState,
+    webhook_url_id: i32,
+    gone: bool,
+) -> Result<(), PostgresModelError> {
+    let mut client = app_state.database.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let disabled = WebhookUrlsModel::record_delivery_failure(
+        webhook_url_id,
+        app_state.retry_policy.disable_after_failures,
+        gone,
+        &transaction,
+    )
+    .await?;
+
+    if let Some(disabled) = &disabled {
+        WebhookUrlsModel::publish_disabled_event(webhook_url_id, disabled, &transaction).await?;
+    }
+
+    transaction.commit().await?;
+
+    if let Some(disabled) = disabled {
+        warn!(
+            "webhook url {} disabled ({:?}) after {} consecutive failures",
+            webhook_url_id, disabled.disabled_reason, disabled.consecutive_failures
+        );
     }
 
-    app_state
+    Ok(())
 }
@@ -1,5 +1,7 @@
 mod bots;
 
+use bots::invoice_lifecycle_bot::run_invoice_lifecycle_bot;
+use bots::invoice_payment_bot::run_invoice_payment_bot;
 use bots::payment_summary_bot::run_payment_summary;
 use bots::vibegraph_bot::run_vibegraph_bot;
 
@@ -17,6 +19,8 @@ async fn main() {
     let result = tokio::try_join!(
         tokio::spawn(run_vibegraph_bot()),
         tokio::spawn(run_payment_summary()),
+        tokio::spawn(run_invoice_payment_bot()),
+        tokio::spawn(run_invoice_lifecycle_bot()),
         tokio::spawn(run_webhook_trigger_bot()) ,
         
     );
@@ -1,35 +1,116 @@
 use crate::types::domains::eth_address::DomainEthAddress;
+use crate::db::postgres::models::token_metadata_model::TokenMetadata;
 use ethers::types::Address;
+use serde::Deserialize;
 use serde_json;
 use std::collections::HashMap;
-use std::str::FromStr;
 
-pub struct PayspecContractsConfig(pub HashMap<i64, DomainEthAddress>);
+/*
 
-impl PayspecContractsConfig {
+NETWORKS_CONFIG_PATH=./config/networks.json
+TOKENS_CONFIG_PATH=./config/tokens.json
+
+RPC urls of the form "${MAINNET_RPC_URL}" are read from the environment so keys stay out of the file.
+
+*/
+
+/// One chain the relay operates on
+#[derive(Debug, Clone, Deserialize)]
+pub struct NetworkConfig {
+    pub chain_id: u64,
+    pub name: String,
+    pub rpc_urls: Vec<String>,
+    /// Cross-check critical reads such as payment details against two rpc endpoints
+    #[serde(default)]
+    pub rpc_quorum: bool,
+    #[serde(default)]
+    pub payspec_contracts: Vec<Address>,
+    /// Block the event indexers start from on a fresh database
+    #[serde(default)]
+    pub start_block: u64,
+    /// Blocks built on top of a payment before it is reported as confirmed
+    pub confirmation_depth: u64,
+    /// Blocks built on top of a payment before it is treated as final and no longer checked for reorgs
+    pub finality_depth: u64,
+    /// Block explorer of the chain, for linking transactions
+    pub explorer_url: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl NetworkConfig {
+    /// Rpc urls with env placeholders substituted. Placeholders whose variable is unset are dropped.
+    pub fn resolved_rpc_urls(&self) -> Vec<String> {
+        self.rpc_urls
+            .iter()
+            .filter_map(|url| match url.strip_prefix("${").and_then(|u| u.strip_suffix('}')) {
+                Some(env_var) => std::env::var(env_var).ok(),
+                None => Some(url.clone()),
+            })
+            .filter(|url| !url.is_empty())
+            .collect()
+    }
+}
+
+/// Every chain the relay knows about, loaded at runtime so a network can be added without a rebuild
+#[derive(Debug, Clone)]
+pub struct NetworksConfig(pub Vec<NetworkConfig>);
+
+impl NetworksConfig {
     pub fn load() -> Self {
-        // Read the JSON file as a string
-        let config_data_raw = include_str!("../../config/payspec_contracts.json");
+        let path = std::env::var("NETWORKS_CONFIG_PATH")
+            .unwrap_or_else(|_| "./config/networks.json".to_string());
 
-        // Parse the JSON string into a temporary HashMap with string keys
-        let config_map: HashMap<String, String> =
-            serde_json::from_str(config_data_raw).expect("Failed to parse payspec_contracts.json");
+        let config_data_raw = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
 
-        // Convert to our target format with i64 keys and DomainEthAddress values
-        let mut contracts_map = HashMap::new();
+        Self::from_json_str(&config_data_raw)
+            .unwrap_or_else(|e| panic!("Failed to parse {}: {}", path, e))
+    }
 
-        for (chain_id_str, address_str) in config_map {
-            // Parse chain ID
-            let chain_id = chain_id_str
-                .parse::<i64>()
-                .expect(&format!("Invalid chain ID: {}", chain_id_str));
+    pub fn from_json_str(raw: &str) -> Result<Self, serde_json::Error> {
+        let networks: Vec<NetworkConfig> = serde_json::from_str(raw)?;
 
-            // Parse Ethereum address
-            let eth_address = Address::from_str(&address_str)
-                .expect(&format!("Invalid Ethereum address: {}", address_str));
+        Ok(Self(networks))
+    }
 
-            contracts_map.insert(chain_id, DomainEthAddress(eth_address));
-        }
+    pub fn enabled(&self) -> impl Iterator<Item = &NetworkConfig> {
+        self.0.iter().filter(|network| network.enabled)
+    }
+
+    pub fn chain_ids(&self) -> Vec<u64> {
+        self.enabled().map(|network| network.chain_id).collect()
+    }
+
+    /// Looks up a network whether or not it is enabled, so payments on a
+    /// disabled chain can still be followed to finality
+    pub fn get(&self, chain_id: u64) -> Option<&NetworkConfig> {
+        self.0.iter().find(|network| network.chain_id == chain_id)
+    }
+}
+
+/// The payspec contract invoices are issued against on each enabled network
+pub struct PayspecContractsConfig(pub HashMap<i64, DomainEthAddress>);
+
+impl PayspecContractsConfig {
+    pub fn load() -> Self {
+        Self::from_networks(&NetworksConfig::load())
+    }
+
+    pub fn from_networks(networks: &NetworksConfig) -> Self {
+        let contracts_map = networks
+            .enabled()
+            .filter_map(|network| {
+                network
+                    .payspec_contracts
+                    .first()
+                    .map(|address| (network.chain_id as i64, DomainEthAddress(*address)))
+            })
+            .collect();
 
         PayspecContractsConfig(contracts_map)
     }
@@ -38,3 +119,123 @@ impl PayspecContractsConfig {
         self.0.get(&chain_id)
     }
 }
+
+#[derive(Deserialize)]
+struct TokenConfigEntry {
+    address: Address,
+    symbol: String,
+    name: Option<String>,
+    decimals: i32,
+}
+
+/// Well-known tokens per chain, used to seed the token registry. Loaded at runtime so a
+/// token can be added without a rebuild.
+pub struct TokenMetadataConfig(pub HashMap<i64, Vec<TokenMetadata>>);
+
+impl TokenMetadataConfig {
+    pub fn load() -> Self {
+        let path = std::env::var("TOKENS_CONFIG_PATH")
+            .unwrap_or_else(|_| "./config/tokens.json".to_string());
+
+        let config_data_raw = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", path, e));
+
+        Self::from_json_str(&config_data_raw)
+            .unwrap_or_else(|e| panic!("Failed to parse {}: {}", path, e))
+    }
+
+    pub fn from_json_str(raw: &str) -> Result<Self, serde_json::Error> {
+        let config_map: HashMap<i64, Vec<TokenConfigEntry>> = serde_json::from_str(raw)?;
+
+        let tokens_map = config_map
+            .into_iter()
+            .map(|(chain_id, entries)| {
+                let tokens = entries
+                    .into_iter()
+                    .map(|entry| TokenMetadata {
+                        chain_id,
+                        token_address: DomainEthAddress(entry.address),
+                        symbol: entry.symbol,
+                        name: entry.name,
+                        decimals: entry.decimals,
+                    })
+                    .collect();
+
+                (chain_id, tokens)
+            })
+            .collect();
+
+        Ok(TokenMetadataConfig(tokens_map))
+    }
+
+    pub fn list_by_chain(&self, chain_id: i64) -> Vec<TokenMetadata> {
+        self.0.get(&chain_id).cloned().unwrap_or_default()
+    }
+
+    pub fn get_token(
+        &self,
+        chain_id: i64,
+        token_address: &DomainEthAddress,
+    ) -> Option<TokenMetadata> {
+        self.0
+            .get(&chain_id)?
+            .iter()
+            .find(|t| t.token_address == *token_address)
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_networks_config_file_parses() {
+        let networks =
+            NetworksConfig::from_json_str(include_str!("../../config/networks.json")).unwrap();
+
+        assert!(networks.get(1).is_some());
+        assert!(networks.chain_ids().contains(&8453));
+        assert_eq!(
+            networks.get(8453).unwrap().explorer_url.as_deref(),
+            Some("https://basescan.org")
+        );
+    }
+
+    #[test]
+    fn test_tokens_config_file_parses() {
+        let tokens =
+            TokenMetadataConfig::from_json_str(include_str!("../../config/tokens.json")).unwrap();
+
+        let usdc = DomainEthAddress(
+            "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(tokens.get_token(8453, &usdc).unwrap().symbol, "USDC");
+        assert!(tokens.list_by_chain(1).len() > 1);
+    }
+
+    #[test]
+    fn test_networks_config_skips_disabled_and_unset_rpc_urls() {
+        let networks = NetworksConfig::from_json_str(
+            r#"[
+                { "chain_id": 10, "name": "a", "rpc_urls": ["${NETWORKS_TEST_UNSET_RPC}", "https://rpc.a"],
+                  "payspec_contracts": ["0x1082d89364765ED958c830F4E77D001837984E31"],
+                  "confirmation_depth": 1, "finality_depth": 2 },
+                { "chain_id": 20, "name": "b", "rpc_urls": ["https://rpc.b"],
+                  "payspec_contracts": ["0x1082d89364765ED958c830F4E77D001837984E31"],
+                  "confirmation_depth": 1, "finality_depth": 2, "enabled": false }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(networks.chain_ids(), vec![10]);
+        assert_eq!(networks.get(10).unwrap().resolved_rpc_urls(), vec!["https://rpc.a"]);
+        assert!(networks.get(20).is_some());
+
+        let contracts = PayspecContractsConfig::from_networks(&networks);
+        assert!(contracts.get_contract_address(10).is_some());
+        assert!(contracts.get_contract_address(20).is_none());
+    }
+}
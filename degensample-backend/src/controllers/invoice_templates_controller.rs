@@ -0,0 +1,480 @@
+use actix_web::HttpResponse;
+use actix_web::Responder;
+
+use defirelay_backend::app_state::AppState;
+use defirelay_backend::config::PayspecContractsConfig;
+use defirelay_backend::db::postgres::models::api_key_model::validate_api_key_or_session_token;
+use defirelay_backend::db::postgres::models::invoice_templates_model::InvoiceTemplate;
+use defirelay_backend::db::postgres::models::invoice_templates_model::InvoiceTemplatesModel;
+use defirelay_backend::db::postgres::models::invoices_model::Invoice;
+use defirelay_backend::db::postgres::models::invoices_model::InvoicesModel;
+use defirelay_backend::types::domains::bytes32::DomainBytes32;
+use defirelay_backend::types::domains::eth_address::DomainEthAddress;
+use defirelay_backend::types::domains::json::DomainJson;
+use defirelay_backend::types::domains::pay_to_amounts::DomainPayToAmounts;
+use defirelay_backend::types::domains::pay_to_array::DomainPayToArray;
+use defirelay_backend::types::pagination::PaginatedResponse;
+use degen_sql::pagination::PaginationData;
+use serde::Deserialize;
+
+use actix_web::web::{self, Data, Json, ServiceConfig};
+
+use utoipa::ToSchema;
+
+use super::web_controller::AuthResponse;
+use super::web_controller::WebController;
+
+pub struct InvoiceTemplatesController {}
+
+impl WebController for InvoiceTemplatesController {
+    fn config(cfg: &mut ServiceConfig) {
+        cfg.service(
+            web::scope("/api/invoice_templates")
+                .route("/create", web::post().to(create_invoice_template))
+                .route("/list", web::post().to(list_invoice_templates))
+                .route("/find_by_uuid", web::get().to(find_invoice_template_by_uuid))
+                .route("/update", web::post().to(update_invoice_template))
+                .route("/delete", web::post().to(delete_invoice_template))
+                .route(
+                    "/find_or_create_invoice_from_template",
+                    web::post().to(find_or_create_invoice_from_template),
+                ),
+        );
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateInvoiceTemplateInput {
+    pub session_token: String,
+    pub chain_id: i64,
+    pub token_address: DomainEthAddress,
+    pub pay_to_array: DomainPayToArray,
+    pub pay_to_amounts: DomainPayToAmounts,
+    pub description: Option<String>,
+    pub metadata: Option<DomainJson>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invoice_templates/create",
+    request_body = CreateInvoiceTemplateInput,
+    responses(
+        (status = 200, description = "Invoice template created", body = AuthResponse<InvoiceTemplate>),
+        (status = 400, description = "Invalid template", body = AuthResponse<String>),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn create_invoice_template(
+    input: Json<CreateInvoiceTemplateInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(token_data) =
+        validate_api_key_or_session_token(&input.session_token, &app_state).await
+    else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let template = InvoiceTemplate::new(
+        DomainEthAddress(token_data.owner_public_address),
+        input.chain_id,
+        input.token_address.clone(),
+        input.pay_to_array.clone(),
+        input.pay_to_amounts.clone(),
+        input.description.clone(),
+        input.metadata.clone(),
+    );
+
+    let template = match template {
+        Ok(template) => template,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
+
+    match InvoiceTemplatesModel::insert_one(&template, &app_state.database).await {
+        Ok(_) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(template),
+            error: None,
+        }),
+        Err(e) => {
+            eprintln!("Error creating invoice template: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ListInvoiceTemplatesInput {
+    pub session_token: String,
+    pub pagination: Option<PaginationData>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invoice_templates/list",
+    request_body = ListInvoiceTemplatesInput,
+    responses(
+        (status = 200, description = "Invoice templates owned by the user", body = AuthResponse<PaginatedResponse<InvoiceTemplate>>),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn list_invoice_templates(
+    input: Json<ListInvoiceTemplatesInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(token_data) =
+        validate_api_key_or_session_token(&input.session_token, &app_state).await
+    else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let owner_address = DomainEthAddress(token_data.owner_public_address);
+    let pagination = input.pagination.clone().unwrap_or_default();
+
+    let result = InvoiceTemplatesModel::find_by_owner_address_paginated(
+        &owner_address,
+        &pagination,
+        &app_state.database,
+    )
+    .await;
+
+    match result {
+        Ok((records, total_count)) => {
+            let items: Vec<InvoiceTemplate> = records.into_iter().map(|r| r.entry).collect();
+
+            HttpResponse::Ok().json(AuthResponse {
+                success: true,
+                data: Some(PaginatedResponse::from_pagination_data(
+                    &pagination,
+                    items,
+                    total_count,
+                )),
+                error: None,
+            })
+        }
+        Err(e) => {
+            eprintln!("Error fetching invoice templates: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to fetch invoice templates: {}", e)),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FindInvoiceTemplateByUuidQuery {
+    pub template_uuid: DomainBytes32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/invoice_templates/find_by_uuid",
+    params(
+        ("template_uuid" = DomainBytes32, Query, description = "UUID of the invoice template")
+    ),
+    responses(
+        (status = 200, description = "Invoice template found", body = AuthResponse<InvoiceTemplate>),
+        (status = 404, description = "Invoice template not found", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn find_invoice_template_by_uuid(
+    web::Query(query): web::Query<FindInvoiceTemplateByUuidQuery>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    match InvoiceTemplatesModel::find_by_uuid(&query.template_uuid, &app_state.database).await {
+        Ok(Some(template)) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(template.entry),
+            error: None,
+        }),
+        Ok(None) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invoice template not found".to_string()),
+        }),
+        Err(e) => {
+            eprintln!("Error fetching invoice template by uuid: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Failed to fetch invoice template".to_string()),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateInvoiceTemplateInput {
+    pub session_token: String,
+    pub template_uuid: DomainBytes32,
+    pub chain_id: i64,
+    pub token_address: DomainEthAddress,
+    pub pay_to_array: DomainPayToArray,
+    pub pay_to_amounts: DomainPayToAmounts,
+    pub description: Option<String>,
+    pub metadata: Option<DomainJson>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invoice_templates/update",
+    request_body = UpdateInvoiceTemplateInput,
+    responses(
+        (status = 200, description = "Invoice template updated", body = AuthResponse<InvoiceTemplate>),
+        (status = 400, description = "Invalid template", body = AuthResponse<String>),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 404, description = "Invoice template not found", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn update_invoice_template(
+    input: Json<UpdateInvoiceTemplateInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(token_data) =
+        validate_api_key_or_session_token(&input.session_token, &app_state).await
+    else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let template = InvoiceTemplate::new(
+        DomainEthAddress(token_data.owner_public_address),
+        input.chain_id,
+        input.token_address.clone(),
+        input.pay_to_array.clone(),
+        input.pay_to_amounts.clone(),
+        input.description.clone(),
+        input.metadata.clone(),
+    );
+
+    let mut template = match template {
+        Ok(template) => template,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
+    template.template_uuid = input.template_uuid.clone();
+
+    match InvoiceTemplatesModel::update_one(&template, &app_state.database).await {
+        Ok(true) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(template),
+            error: None,
+        }),
+        Ok(false) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invoice template not found".to_string()),
+        }),
+        Err(e) => {
+            eprintln!("Error updating invoice template: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DeleteInvoiceTemplateInput {
+    pub session_token: String,
+    pub template_uuid: DomainBytes32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invoice_templates/delete",
+    request_body = DeleteInvoiceTemplateInput,
+    responses(
+        (status = 200, description = "Invoice template deleted", body = AuthResponse<bool>),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 404, description = "Invoice template not found", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn delete_invoice_template(
+    input: Json<DeleteInvoiceTemplateInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(token_data) =
+        validate_api_key_or_session_token(&input.session_token, &app_state).await
+    else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let owner_address = DomainEthAddress(token_data.owner_public_address);
+
+    match InvoiceTemplatesModel::delete_by_uuid(
+        &input.template_uuid,
+        &owner_address,
+        &app_state.database,
+    )
+    .await
+    {
+        Ok(true) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(true),
+            error: None,
+        }),
+        Ok(false) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invoice template not found".to_string()),
+        }),
+        Err(e) => {
+            eprintln!("Error deleting invoice template: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FindOrCreateInvoiceFromTemplateInput {
+    pub template_uuid: DomainBytes32,
+
+    /// When set, an unpaid invoice already drafted for this payer is reused
+    pub create_for_address: Option<DomainEthAddress>,
+}
+
+/// Public so payment buttons can stamp out invoices for customers without a session
+#[utoipa::path(
+    post,
+    path = "/api/invoice_templates/find_or_create_invoice_from_template",
+    request_body = FindOrCreateInvoiceFromTemplateInput,
+    responses(
+        (status = 200, description = "Invoice drafted from the template", body = AuthResponse<Invoice>),
+        (status = 400, description = "Template is not payable", body = AuthResponse<String>),
+        (status = 404, description = "Invoice template not found", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn find_or_create_invoice_from_template(
+    input: Json<FindOrCreateInvoiceFromTemplateInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let template =
+        match InvoiceTemplatesModel::find_by_uuid(&input.template_uuid, &app_state.database).await
+        {
+            Ok(Some(template)) => template.entry,
+            Ok(None) => {
+                return HttpResponse::NotFound().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some("Invoice template not found".to_string()),
+                })
+            }
+            Err(e) => {
+                eprintln!("Error fetching invoice template by uuid: {}", e);
+                return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some("Failed to fetch invoice template".to_string()),
+                });
+            }
+        };
+
+    if let Some(create_for_address) = &input.create_for_address {
+        match InvoicesModel::find_draft_for_template(
+            &template.template_uuid,
+            create_for_address,
+            &app_state.database,
+        )
+        .await
+        {
+            Ok(Some(existing)) => {
+                return HttpResponse::Ok().json(AuthResponse {
+                    success: true,
+                    data: Some(existing.entry),
+                    error: None,
+                })
+            }
+            Ok(None) => {}
+            Err(e) => {
+                eprintln!("Error finding invoice for template: {}", e);
+                return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some("Database error".to_string()),
+                });
+            }
+        }
+    }
+
+    let payspec_contracts = PayspecContractsConfig::load();
+
+    let Some(contract_address) = payspec_contracts.get_contract_address(template.chain_id) else {
+        return HttpResponse::BadRequest().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some(format!("Unsupported chain_id {}", template.chain_id)),
+        });
+    };
+
+    let invoice =
+        match template.build_invoice(contract_address.clone(), input.create_for_address.clone()) {
+            Ok(invoice) => invoice,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                })
+            }
+        };
+
+    match InvoicesModel::insert_one(&invoice, &app_state.database).await {
+        Ok(_) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(invoice),
+            error: None,
+        }),
+        Err(e) => {
+            eprintln!("Error creating invoice from template: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
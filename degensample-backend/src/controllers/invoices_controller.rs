@@ -0,0 +1,275 @@
+use actix_web::HttpResponse;
+use actix_web::Responder;
+
+use chrono::{DateTime, Utc};
+use defirelay_backend::app_state::AppState;
+use defirelay_backend::config::PayspecContractsConfig;
+use defirelay_backend::db::postgres::models::api_key_model::validate_api_key_or_session_token;
+use defirelay_backend::db::postgres::models::invoices_model::Invoice;
+use defirelay_backend::db::postgres::models::invoices_model::InvoicesModel;
+use defirelay_backend::types::domains::bytes32::DomainBytes32;
+use defirelay_backend::types::domains::datetime::DomainDatetime;
+use defirelay_backend::types::domains::eth_address::DomainEthAddress;
+use defirelay_backend::types::domains::json::DomainJson;
+use defirelay_backend::types::domains::pay_to_amounts::DomainPayToAmounts;
+use defirelay_backend::types::domains::pay_to_array::DomainPayToArray;
+use defirelay_backend::types::domains::uint256::DomainUint256;
+use defirelay_backend::types::evm_types::RawTx;
+use defirelay_backend::types::pagination::PaginatedResponse;
+use degen_sql::pagination::PaginationData;
+use serde::{Deserialize, Serialize};
+
+use actix_web::web::{self, Data, Json, ServiceConfig};
+
+use utoipa::ToSchema;
+
+use super::web_controller::AuthResponse;
+use super::web_controller::WebController;
+
+/*
+
+curl -X POST http://localhost:8080/api/invoices/create \
+     -H "Content-Type: application/json" \
+     -d '{ "session_token": "f97169e34730ca74ced6d59ee684d91e", "chain_id": 8453, "token_address": "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "pay_to_array": ["0x810E096DDa9ae3Ae2b55a9c45068F9FE8eeea6db"], "pay_to_amounts": ["1000000"] }'
+
+*/
+
+pub struct InvoicesController {}
+
+impl WebController for InvoicesController {
+    fn config(cfg: &mut ServiceConfig) {
+        cfg.service(
+            web::scope("/api/invoices")
+                .route("/create", web::post().to(create_invoice))
+                .route("/list", web::post().to(list_invoices))
+                .route("/find_by_uuid", web::get().to(find_invoice_by_uuid)),
+        );
+    }
+}
+
+/// An invoice together with the transaction a payer submits to settle it
+#[derive(Serialize, ToSchema)]
+pub struct InvoiceWithRawTx {
+    pub invoice: Invoice,
+    pub raw_tx: Option<RawTx>,
+}
+
+impl From<Invoice> for InvoiceWithRawTx {
+    fn from(invoice: Invoice) -> Self {
+        let raw_tx = invoice.build_raw_tx().ok();
+
+        Self { invoice, raw_tx }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateInvoiceInput {
+    pub session_token: String,
+    pub chain_id: i64,
+    pub token_address: DomainEthAddress,
+    pub pay_to_array: DomainPayToArray,
+    pub pay_to_amounts: DomainPayToAmounts,
+
+    /// Defaults to a random nonce
+    pub nonce: Option<DomainUint256>,
+    pub metadata: Option<DomainJson>,
+    /// Unix seconds. Still unpaid by then, the invoice expires and `invoice_expired` is sent.
+    pub expires_at: Option<i64>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invoices/create",
+    request_body = CreateInvoiceInput,
+    responses(
+        (status = 200, description = "Draft invoice created", body = AuthResponse<InvoiceWithRawTx>),
+        (status = 400, description = "Invalid invoice", body = AuthResponse<String>),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn create_invoice(
+    input: Json<CreateInvoiceInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(token_data) =
+        validate_api_key_or_session_token(&input.session_token, &app_state).await
+    else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let payspec_contracts = PayspecContractsConfig::load();
+
+    let Some(contract_address) = payspec_contracts.get_contract_address(input.chain_id) else {
+        return HttpResponse::BadRequest().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some(format!("Unsupported chain_id {}", input.chain_id)),
+        });
+    };
+
+    let invoice = Invoice::new(
+        DomainEthAddress(token_data.owner_public_address),
+        input.chain_id,
+        contract_address.clone(),
+        input.token_address.clone(),
+        input.pay_to_array.clone(),
+        input.pay_to_amounts.clone(),
+        input.nonce.clone().unwrap_or_else(Invoice::random_nonce),
+        input.metadata.clone(),
+    );
+
+    let mut invoice = match invoice {
+        Ok(invoice) => invoice,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
+
+    if let Some(expires_at) = input.expires_at {
+        match DateTime::from_timestamp(expires_at, 0) {
+            Some(expires_at) if expires_at > Utc::now() => {
+                invoice.expires_at = Some(DomainDatetime(expires_at));
+            }
+            _ => {
+                return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some("expires_at must be a unix timestamp in the future".to_string()),
+                })
+            }
+        }
+    }
+
+    match InvoicesModel::insert_one(&invoice, &app_state.database).await {
+        Ok(_) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(InvoiceWithRawTx::from(invoice)),
+            error: None,
+        }),
+        Err(e) => {
+            eprintln!("Error creating invoice: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ListInvoicesInput {
+    pub session_token: String,
+    pub pagination: Option<PaginationData>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/invoices/list",
+    request_body = ListInvoicesInput,
+    responses(
+        (status = 200, description = "Invoices owned by the user", body = AuthResponse<PaginatedResponse<Invoice>>),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn list_invoices(
+    input: Json<ListInvoicesInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(token_data) =
+        validate_api_key_or_session_token(&input.session_token, &app_state).await
+    else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let owner_address = DomainEthAddress(token_data.owner_public_address);
+    let pagination = input.pagination.clone().unwrap_or_default();
+
+    let result = InvoicesModel::find_by_owner_address_paginated(
+        &owner_address,
+        &pagination,
+        &app_state.database,
+    )
+    .await;
+
+    match result {
+        Ok((records, total_count)) => {
+            let items: Vec<Invoice> = records.into_iter().map(|r| r.entry).collect();
+
+            HttpResponse::Ok().json(AuthResponse {
+                success: true,
+                data: Some(PaginatedResponse::from_pagination_data(
+                    &pagination,
+                    items,
+                    total_count,
+                )),
+                error: None,
+            })
+        }
+        Err(e) => {
+            eprintln!("Error fetching invoices: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(format!("Failed to fetch invoices: {}", e)),
+            })
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct FindInvoiceByUuidQuery {
+    pub uuid: DomainBytes32,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/invoices/find_by_uuid",
+    params(
+        ("uuid" = DomainBytes32, Query, description = "UUID of the invoice")
+    ),
+    responses(
+        (status = 200, description = "Invoice found", body = AuthResponse<InvoiceWithRawTx>),
+        (status = 404, description = "Invoice not found", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+pub async fn find_invoice_by_uuid(
+    web::Query(query): web::Query<FindInvoiceByUuidQuery>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    match InvoicesModel::find_by_uuid(&query.uuid, &app_state.database).await {
+        Ok(Some(invoice)) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(InvoiceWithRawTx::from(invoice.entry)),
+            error: None,
+        }),
+        Ok(None) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invoice not found".to_string()),
+        }),
+        Err(e) => {
+            eprintln!("Error fetching invoice by uuid: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Failed to fetch invoice".to_string()),
+            })
+        }
+    }
+}
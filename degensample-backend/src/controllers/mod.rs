@@ -4,7 +4,12 @@ pub mod payments_controller;
 
 pub mod api_key_controller;
 
+pub mod invoice_templates_controller;
+pub mod invoices_controller;
+
 pub mod session_controller;
+pub mod stream_controller;
+pub mod token_symbols_controller;
  
   
 pub mod users_controller;
@@ -7,9 +7,15 @@ use actix_web::{
 use degen_sql::pagination::PaginationData;
 
 use defirelay_backend::db::postgres::models::api_key_model::validate_api_key_or_session_token;
+use defirelay_backend::db::postgres::models::invoice_payments_model::{
+    InvoicePayment, InvoicePaymentsModel,
+};
 use defirelay_backend::db::postgres::models::payments_model::PaymentsModel;
+use defirelay_backend::db::postgres::models::token_metadata_model::TokenMetadataModel;
 use defirelay_backend::types::domains::bytes32::DomainBytes32;
 use defirelay_backend::types::pagination::PaginatedResponse;
+use defirelay_backend::types::selected_record::SelectedRecord;
+use degen_sql::db::postgres::models::model::PostgresModelError;
 use defirelay_backend::{app_state::AppState, types::domains::eth_address::DomainEthAddress};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -27,6 +33,12 @@ pub struct FindByInvoiceUuidQuery {
     pub invoice_uuid: DomainBytes32,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct ExportPaymentsQuery {
+    pub session_token: String,
+    pub chain_id: Option<i64>,
+}
+
 #[derive(Serialize, ToSchema)]
 struct AuthResponse<T> {
     success: bool,
@@ -37,6 +49,62 @@ struct AuthResponse<T> {
 
 
 
+/// Token metadata first, since USD values are computed from the token's decimals
+async fn attach_amounts(
+    payments: &mut [SelectedRecord<InvoicePayment>],
+    app_state: &AppState,
+) -> Result<(), PostgresModelError> {
+    InvoicePaymentsModel::attach_token_metadata(payments, &app_state.database).await?;
+    InvoicePaymentsModel::attach_usd_values(payments, &app_state.database).await?;
+
+    Ok(())
+}
+
+const PAYMENTS_CSV_HEADER: &str = "transaction_hash,log_index,chain_id,block_number,block_timestamp,from_address,to_address,token_address,token_symbol,amount,amount_formatted,usd_price,usd_value,status";
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn payments_to_csv(payments: &[SelectedRecord<InvoicePayment>]) -> String {
+    let mut csv = String::from(PAYMENTS_CSV_HEADER);
+    csv.push('\n');
+
+    for payment in payments {
+        let p = &payment.entry;
+
+        let fields = [
+            format!("{:?}", p.transaction_hash.0),
+            p.log_index.to_string(),
+            p.chain_id.to_string(),
+            p.payment_at_block.map(|b| b.to_string()).unwrap_or_default(),
+            p.payment_at_block_timestamp
+                .as_ref()
+                .map(|t| t.0.to_rfc3339())
+                .unwrap_or_default(),
+            format!("{:?}", p.from_address.0),
+            format!("{:?}", p.to_address.0),
+            format!("{:?}", p.payment_token_address.0),
+            p.token.as_ref().map(|t| t.symbol.clone()).unwrap_or_default(),
+            p.amount.0.to_string(),
+            p.amount_formatted.clone().unwrap_or_default(),
+            p.usd_price.as_ref().map(|v| v.0.to_string()).unwrap_or_default(),
+            p.usd_value.as_ref().map(|v| v.0.to_string()).unwrap_or_default(),
+            p.status.to_string(),
+        ];
+
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
 pub struct PaymentsController {}
 
 impl WebController for PaymentsController {
@@ -44,7 +112,8 @@ impl WebController for PaymentsController {
         cfg.service(
             web::scope("/api/payments")
                 .route("/list", web::post().to(Self::list_payments))
-                .route("/find_by_invoice_uuid", web::get().to(Self::find_payment_by_invoice_uuid)),
+                .route("/find_by_invoice_uuid", web::get().to(Self::find_payment_by_invoice_uuid))
+                .route("/export", web::post().to(Self::export_payments)),
         );
     }
 }
@@ -55,10 +124,22 @@ impl PaymentsController {
         app_state: Data<AppState>,
     ) -> impl Responder {
         match PaymentsModel::find_by_uuid(&query.invoice_uuid, &app_state.database).await {
-            Ok(Some(payment)) => HttpResponse::Ok().json(json!({
+            Ok(Some(mut payment)) => {
+                if let Ok(Some(token)) = TokenMetadataModel::resolve(
+                    payment.entry.chain_id,
+                    &payment.entry.payment_token_address,
+                    &app_state.database,
+                )
+                .await
+                {
+                    payment.entry.apply_token_metadata(token);
+                }
+
+                HttpResponse::Ok().json(json!({
                 "success": true,
                 "data": payment
-            })),
+                }))
+            }
             Ok(None) => HttpResponse::NotFound().json(json!({
                 "success": false,
                 "error": "Payment not found"
@@ -73,6 +154,50 @@ impl PaymentsController {
         }
     }
 
+    async fn export_payments(
+        query: Json<ExportPaymentsQuery>,
+        app_state: Data<AppState>,
+    ) -> impl Responder {
+        let Some(token_data) =
+            validate_api_key_or_session_token(&query.session_token, &app_state).await
+        else {
+            return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Invalid session".to_string()),
+            });
+        };
+
+        let wallet_address = DomainEthAddress(token_data.owner_public_address);
+
+        let mut payments = match InvoicePaymentsModel::find_by_to_address(
+            &wallet_address,
+            query.chain_id,
+            &app_state.database,
+        )
+        .await
+        {
+            Ok(payments) => payments,
+            Err(e) => {
+                eprintln!("Error exporting payments: {}", e);
+                return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some(format!("Failed to fetch payments: {}", e)),
+                });
+            }
+        };
+
+        if let Err(e) = attach_amounts(&mut payments, &app_state).await {
+            eprintln!("Error resolving payment amounts: {}", e);
+        }
+
+        HttpResponse::Ok()
+            .content_type("text/csv")
+            .insert_header(("Content-Disposition", "attachment; filename=\"payments.csv\""))
+            .body(payments_to_csv(&payments))
+    }
+
     async fn list_payments(
         query: Json<ListPaymentsQuery>,
         app_state: Data<AppState>,
@@ -91,35 +216,26 @@ impl PaymentsController {
         // Get the wallet address
         let wallet_address = DomainEthAddress(token_data.owner_public_address);
 
-        // Check if pagination is requested
-        if query.pagination.is_some() {
-            // Get pagination options
-            let pagination = query.pagination.clone().unwrap_or_default();
-
-            let result = if let Some(chain_id) = query.chain_id {
-                // With chain_id and pagination
-                PaymentsModel::find_by_pay_to_address_and_chain_id_paginated(
-                    &wallet_address,
-                    chain_id,
-                    &pagination,
-                    &app_state.database,
-                )
-                .await
-            } else {
-                // Without chain_id but with pagination
-                PaymentsModel::find_by_pay_to_address_paginated(
-                    &wallet_address,
-                    &pagination,
-                    &app_state.database,
-                )
-                .await
-            };
+        // Payments are read from the per-recipient ledger so split invoices
+        // show up as one entry per transfer to this wallet
+        if let Some(pagination) = &query.pagination {
+            let result = InvoicePaymentsModel::find_by_to_address_paginated(
+                &wallet_address,
+                query.chain_id,
+                pagination,
+                &app_state.database,
+            )
+            .await;
 
             match result {
-                Ok((items, total_count)) => {
+                Ok((mut items, total_count)) => {
+                    if let Err(e) = attach_amounts(&mut items, &app_state).await {
+                        eprintln!("Error resolving payment amounts: {}", e);
+                    }
+
                     // Create paginated response using the helper method
                     let paginated_response =
-                        PaginatedResponse::from_pagination_data(&pagination, items, total_count);
+                        PaginatedResponse::from_pagination_data(pagination, items, total_count);
 
                     HttpResponse::Ok().json(AuthResponse {
                         success: true,
@@ -138,23 +254,25 @@ impl PaymentsController {
             }
         } else {
             // Non-paginated response (backward compatibility)
-            let result = if let Some(chain_id) = query.chain_id {
-                PaymentsModel::find_by_pay_to_address_and_chain_id(
-                    &wallet_address,
-                    chain_id,
-                    &app_state.database,
-                )
-                .await
-            } else {
-                PaymentsModel::find_by_pay_to_address(&wallet_address, &app_state.database).await
-            };
+            let result = InvoicePaymentsModel::find_by_to_address(
+                &wallet_address,
+                query.chain_id,
+                &app_state.database,
+            )
+            .await;
 
             match result {
-                Ok(payments) => HttpResponse::Ok().json(AuthResponse {
-                    success: true,
-                    data: Some(payments),
-                    error: None,
-                }),
+                Ok(mut payments) => {
+                    if let Err(e) = attach_amounts(&mut payments, &app_state).await {
+                        eprintln!("Error resolving payment amounts: {}", e);
+                    }
+
+                    HttpResponse::Ok().json(AuthResponse {
+                        success: true,
+                        data: Some(payments),
+                        error: None,
+                    })
+                }
                 Err(e) => {
                     eprintln!("Error fetching payments: {}", e);
                     HttpResponse::InternalServerError().json(AuthResponse::<String> {
@@ -187,6 +305,23 @@ async fn find_payment_by_invoice_uuid(
     PaymentsController::find_payment_by_invoice_uuid(web::Query(query), app_state).await
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/payments/export",
+    request_body = ExportPaymentsQuery,
+    responses(
+        (status = 200, description = "CSV of payments received, with token amounts and USD values at block time", body = String, content_type = "text/csv"),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+async fn export_payments(
+    query: Json<ExportPaymentsQuery>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    PaymentsController::export_payments(query, app_state).await
+}
+
 #[utoipa::path(
     post,
     path = "/api/payments/list",
@@ -0,0 +1,196 @@
+use actix_web::web::{self, Bytes, Data, ServiceConfig};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use actix_ws::Message;
+use defirelay_backend::app_state::AppState;
+use defirelay_backend::db::postgres::models::api_key_model::validate_api_key_or_session_token;
+use defirelay_backend::types::account_stream::parse_last_event_id;
+use defirelay_backend::util::account_event_hub::AccountEventSubscription;
+use serde::Deserialize;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use super::web_controller::{AuthResponse, WebController};
+
+/*
+
+Live account events for dashboards, instead of polling `/api/payments/list`.
+
+  curl -N "http://localhost:8080/api/stream/events?session_token=f97169e34730ca74ced6d59ee684d91e"
+
+*/
+
+/// Sent while no events are, so proxies keep idle connections open
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long a disconnected `EventSource` waits before reconnecting
+const SSE_RETRY_MILLIS: u64 = 3000;
+
+pub struct StreamController {}
+
+impl WebController for StreamController {
+    fn config(cfg: &mut ServiceConfig) {
+        cfg.service(
+            web::scope("/api/stream")
+                .route("/events", web::get().to(stream_events))
+                .route("/ws", web::get().to(stream_websocket)),
+        );
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct StreamQuery {
+    /// Session token or API key. Browsers cannot set headers on an `EventSource` or a
+    /// WebSocket, so it is passed in the query.
+    pub session_token: String,
+    /// Resume after this event id. For server-sent events the `Last-Event-ID` header
+    /// takes precedence.
+    pub last_event_id: Option<String>,
+}
+
+/// Authenticates the caller and subscribes to their wallet's events
+async fn subscribe(
+    req: &HttpRequest,
+    query: &StreamQuery,
+    app_state: &Data<AppState>,
+) -> Option<AccountEventSubscription> {
+    let token_valid = validate_api_key_or_session_token(&query.session_token, app_state).await?;
+
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .or(query.last_event_id.as_deref())
+        .and_then(parse_last_event_id);
+
+    Some(
+        app_state
+            .account_events
+            .subscribe(token_valid.owner_public_address, last_event_id),
+    )
+}
+
+fn unauthorized() -> HttpResponse {
+    HttpResponse::Unauthorized().json(AuthResponse::<String> {
+        success: false,
+        data: None,
+        error: Some("Invalid session".to_string()),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/stream/events",
+    params(
+        ("session_token" = String, Query, description = "Session token or API key"),
+        ("last_event_id" = Option<String>, Query, description = "Resume after this event id")
+    ),
+    responses(
+        (status = 200, description = "A text/event-stream of the wallet's payment, invoice and webhook url events", content_type = "text/event-stream", body = String),
+        (status = 401, description = "Invalid session or API key", body = AuthResponse<String>),
+    )
+)]
+async fn stream_events(
+    req: HttpRequest,
+    web::Query(query): web::Query<StreamQuery>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(subscription) = subscribe(&req, &query, &app_state).await else {
+        return unauthorized();
+    };
+
+    let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+    // The first tick is immediate, the retry frame already opens the stream
+    keepalive.reset();
+
+    let opening = futures::stream::once(futures::future::ready(Ok::<_, actix_web::Error>(
+        Bytes::from(format!("retry: {}\n\n", SSE_RETRY_MILLIS)),
+    )));
+
+    // `next` is cancel safe, so losing the race to a keepalive drops no event
+    let events = futures::stream::unfold(
+        (subscription, keepalive),
+        |(mut subscription, mut keepalive)| async move {
+            let frame = tokio::select! {
+                event = subscription.next() => Bytes::from(event?.to_sse_frame()),
+                _ = keepalive.tick() => Bytes::from_static(b": keepalive\n\n"),
+            };
+
+            Some((Ok::<_, actix_web::Error>(frame), (subscription, keepalive)))
+        },
+    );
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(futures::StreamExt::chain(opening, events))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/stream/ws",
+    params(
+        ("session_token" = String, Query, description = "Session token or API key"),
+        ("last_event_id" = Option<String>, Query, description = "Resume after this event id")
+    ),
+    responses(
+        (status = 101, description = "A WebSocket sending each of the wallet's payment, invoice and webhook url events as a JSON text message"),
+        (status = 401, description = "Invalid session or API key", body = AuthResponse<String>),
+    )
+)]
+async fn stream_websocket(
+    req: HttpRequest,
+    body: web::Payload,
+    web::Query(query): web::Query<StreamQuery>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let Some(mut subscription) = subscribe(&req, &query, &app_state).await else {
+        return unauthorized();
+    };
+
+    let (response, mut session, mut messages) = match actix_ws::handle(&req, body) {
+        Ok(handshake) => handshake,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
+
+    actix_web::rt::spawn(async move {
+        let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+        keepalive.reset();
+
+        loop {
+            tokio::select! {
+                event = subscription.next() => {
+                    let Some(event) = event else { break };
+                    if session.text(event.to_json()).await.is_err() {
+                        return;
+                    }
+                }
+                message = messages.recv() => match message {
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    // The stream only sends, anything else from the client is ignored
+                    Some(Ok(_)) => {}
+                },
+                _ = keepalive.tick() => {
+                    if session.ping(b"").await.is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+
+        let _ = session.close(None).await;
+    });
+
+    response
+}
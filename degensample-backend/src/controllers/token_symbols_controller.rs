@@ -0,0 +1,102 @@
+use actix_web::HttpResponse;
+use actix_web::Responder;
+
+use defirelay_backend::app_state::AppState;
+use defirelay_backend::db::postgres::models::api_key_model::validate_api_key_or_session_token;
+use defirelay_backend::db::postgres::models::token_metadata_model::TokenMetadataModel;
+use defirelay_backend::types::domains::eth_address::DomainEthAddress;
+use serde::{Deserialize, Serialize};
+
+use actix_web::web::{self, Data, Json, ServiceConfig};
+
+use utoipa::ToSchema;
+
+use super::web_controller::AuthResponse;
+use super::web_controller::WebController;
+
+/*
+
+curl -X POST http://localhost:8080/api/token_symbols/list_by_chain \
+     -H "Content-Type: application/json" \
+     -d '{ "session_token": "f97169e34730ca74ced6d59ee684d91e", "chain_id": 8453 }'
+
+*/
+
+pub struct TokenSymbolsController {}
+
+impl WebController for TokenSymbolsController {
+    fn config(cfg: &mut ServiceConfig) {
+        cfg.service(
+            web::scope("/api/token_symbols")
+                .route("/list_by_chain", web::post().to(list_token_symbols_by_chain)),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ListTokenSymbolsInput {
+    session_token: String,
+    chain_id: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TokenSymbolOutput {
+    pub token_symbol: String,
+    pub token_name: Option<String>,
+    pub token_address: DomainEthAddress,
+    pub token_decimals: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/token_symbols/list_by_chain",
+    request_body = ListTokenSymbolsInput,
+    responses(
+        (status = 200, description = "Known tokens on a chain with their symbols and decimals", body = AuthResponse<Vec<TokenSymbolOutput>>),
+        (status = 401, description = "Unauthorized", body = AuthResponse<String>),
+        (status = 500, description = "Internal server error", body = AuthResponse<String>)
+    )
+)]
+async fn list_token_symbols_by_chain(
+    input: Json<ListTokenSymbolsInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    if validate_api_key_or_session_token(&input.session_token, &app_state)
+        .await
+        .is_none()
+    {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    }
+
+    match TokenMetadataModel::find_by_chain_id(input.chain_id, &app_state.database).await {
+        Ok(tokens) => {
+            let output: Vec<TokenSymbolOutput> = tokens
+                .into_iter()
+                .map(|token| TokenSymbolOutput {
+                    token_symbol: token.symbol,
+                    token_name: token.name,
+                    token_address: token.token_address,
+                    token_decimals: token.decimals,
+                })
+                .collect();
+
+            HttpResponse::Ok().json(AuthResponse {
+                success: true,
+                data: Some(output),
+                error: None,
+            })
+        }
+        Err(e) => {
+            eprintln!("Error fetching token symbols: {}", e);
+            HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+}
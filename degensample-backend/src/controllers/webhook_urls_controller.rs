@@ -3,19 +3,39 @@ use actix_web::HttpResponse;
 use actix_web::Responder;
 use defirelay_backend::db::postgres::models::api_key_model::validate_api_key_or_session_token;
 use defirelay_backend::db::postgres::models::payments_model::PaymentSummary;
+use chrono::{DateTime, Duration, Utc};
+use degen_sql::pagination::PaginationData;
 use ethers::types::Address;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use utoipa::ToSchema;
 
 use defirelay_backend::app_state::AppState;
 use defirelay_backend::db::postgres::models::auth_sessions_model::validate_session_token;
+use defirelay_backend::db::postgres::models::webhook_deliveries_model::{
+    WebhookDeliveriesModel, WebhookDelivery,
+};
 use defirelay_backend::db::postgres::models::webhook_triggers_model::{
     WebhookTrigger, WebhookTriggerStatus, WebhookTriggersModel,
 };
 use defirelay_backend::db::postgres::models::webhook_urls_model::{WebhookUrl, WebhookUrlsModel};
 use defirelay_backend::types::domains::eth_address::DomainEthAddress;
 use defirelay_backend::types::domains::json::DomainJson;
+use defirelay_backend::types::pagination::PaginatedResponse;
 use defirelay_backend::types::selected_record::SelectedRecord;
+use defirelay_backend::types::webhook_batches::{WebhookBatching, WebhookBatchingError};
+use defirelay_backend::types::webhook_events::{webhook_json_schema, WebhookApiVersion};
+use defirelay_backend::types::webhook_scopes::WebhookScopes;
+use defirelay_backend::types::webhook_templates::{
+    WebhookCustomHeaders, WebhookPayloadTemplate, WebhookTemplateError,
+};
+use defirelay_backend::types::defi_relay_webhook_payload::{
+    DefiRelayWebhookPayload, WebhookVerificationPayload,
+};
+use defirelay_backend::util::delivery_sink::{QueueSinks, QueueTarget, SinkCredentials, SinkMessage};
+use defirelay_backend::util::webhook_delivery::{send_verification_challenge, WebhookHttpClient};
+use defirelay_backend::util::webhook_signature::generate_signing_secret;
+use defirelay_backend::util::webhook_url_guard::WebhookUrlPolicy;
 
 use super::web_controller::{AuthResponse, WebController};
 
@@ -37,6 +57,18 @@ impl WebController for WebhookUrlsController {
                 //  .route("/get", web::get().to(get_webhook_url))
                 .route("/list", web::post().to(list_webhook_urls))
                 .route("/delete", web::post().to(delete_webhook_url))
+                .route("/verify", web::post().to(verify_webhook_url))
+                .route("/enable", web::post().to(enable_webhook_url))
+                .route("/api_version", web::post().to(set_webhook_api_version))
+                .route("/batching", web::post().to(set_webhook_batching))
+                .route("/schema/{api_version}", web::get().to(get_webhook_schema))
+                .route("/template", web::post().to(set_webhook_payload_template))
+                .route("/template/preview", web::post().to(preview_webhook_payload_template))
+                .route("/rotate_secret", web::post().to(rotate_webhook_secret))
+                .route("/triggers", web::post().to(list_webhook_triggers))
+                .route("/deliveries", web::post().to(list_webhook_deliveries))
+                .route("/redeliver", web::post().to(redeliver_webhook_trigger))
+                .route("/replay", web::post().to(replay_webhook_triggers))
                 .route("/test_trigger", web::post().to(test_trigger_webhook))
                 .route("/ack", web::post().to(acknowledge_webhook_trigger)),
         );
@@ -48,13 +80,31 @@ pub struct CreateWebhookUrlInput {
     session_token: String,
     // wallet_public_address: String,
     webhook_url: String,
+    /// Which events the URL receives, see `WebhookScopes`. Omit to receive everything.
     scopes: Option<serde_json::Value>,
+    /// Body version the URL receives, v0 or v1. Defaults to the latest.
+    api_version: Option<String>,
+    /// Replaces the body, e.g. `{"preset": "slack"}`, see `WebhookPayloadTemplate`
+    payload_template: Option<serde_json::Value>,
+    /// Extra request headers, by name
+    custom_headers: Option<BTreeMap<String, String>>,
+    /// Broker login for nats, redis and amqp urls
+    sink_credentials: Option<SinkCredentials>,
+    /// Send events in batches instead of one request each
+    batching: Option<WebhookBatching>,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 struct WebhookUrlCreatedOutput {
     id: i32,
     webhook_url: String,
+    /// Used to verify delivery signatures. Returned only here, store it now.
+    signing_secret: Option<String>,
+    /// Whether the URL echoed the verification challenge. Unverified URLs receive no events
+    /// until `/api/webhooks/verify` succeeds.
+    verified: bool,
+    api_version: WebhookApiVersion,
+    payload_template: Option<WebhookPayloadTemplate>,
 }
 
 #[utoipa::path(
@@ -81,25 +131,117 @@ async fn create_webhook_url(
 
     let wallet_address = token_valid.owner_public_address.clone();
 
+    // Scopes are stored in their parsed form so stray fields never reach the database
+    let scopes_domain = match input.scopes.clone().map(WebhookScopes::from_json) {
+        Some(Ok(scopes)) => Some(DomainJson::new(
+            serde_json::to_value(scopes).unwrap_or_default(),
+        )),
+        Some(Err(e)) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+        None => None,
+    };
+
+    let requested_version = input.api_version.as_deref().map(str::parse);
+
+    let api_version: WebhookApiVersion = match requested_version {
+        Some(Ok(api_version)) => api_version,
+        Some(Err(e)) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+        None => WebhookApiVersion::LATEST,
+    };
+
+    let (payload_template, custom_headers) =
+        match parse_payload_template(input.payload_template.clone(), input.custom_headers.clone())
+        {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                })
+            }
+        };
+
+    let url_policy = WebhookUrlPolicy::from_env();
+
+    if let Err(e) = url_policy.check_url_resolved(&input.webhook_url).await {
+        return HttpResponse::BadRequest().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some(e.to_string()),
+        });
+    }
+
+    let queue_target = match QueueTarget::from_url(&input.webhook_url, input.sink_credentials.clone())
+    {
+        Ok(queue_target) => queue_target,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
+
+    let batching = match input
+        .batching
+        .map(|batching| validate_batching(batching, queue_target.is_some()))
+        .transpose()
+    {
+        Ok(batching) => batching,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
+
     // Create new webhook URL
-    let scopes_domain = input
-        .scopes
-        .as_ref()
-        .map(|json_value| DomainJson::new(json_value.clone()));
 
     let new_webhook_url = WebhookUrl::new(
         DomainEthAddress(wallet_address),
         input.webhook_url.clone(),
         scopes_domain,
-    );
+    )
+    .with_api_version(api_version)
+    .with_payload_template(
+        payload_template.as_ref().map(to_domain_json),
+        custom_headers.as_ref().map(to_domain_json),
+    )
+    .with_sink_credentials(input.sink_credentials.as_ref().map(to_domain_json))
+    .with_batching(batching);
 
-    let inserted = WebhookUrlsModel::insert_one(new_webhook_url, &app_state.database).await;
+    let signing_secret = new_webhook_url.signing_secret.clone();
+
+    let inserted =
+        WebhookUrlsModel::insert_one(new_webhook_url.clone(), &app_state.database).await;
 
     match inserted {
         Ok(new_id) => {
+            let verified =
+                send_verification(new_id, &new_webhook_url, url_policy, &app_state).await;
+
             let webhook_created_output = WebhookUrlCreatedOutput {
                 id: new_id,
                 webhook_url: input.webhook_url.clone(),
+                signing_secret,
+                verified,
+                api_version,
+                payload_template,
             };
 
             HttpResponse::Ok().json(AuthResponse {
@@ -116,36 +258,75 @@ async fn create_webhook_url(
     }
 }
 
+/// How long a receiver has to answer the verification challenge
+const VERIFICATION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Sends the verification challenge of a webhook url and marks it verified when the
+/// receiver echoes it back. A broker cannot answer, so there the challenge only has to
+/// be accepted.
+async fn send_verification(
+    webhook_url_id: i32,
+    webhook_url: &WebhookUrl,
+    url_policy: WebhookUrlPolicy,
+    app_state: &AppState,
+) -> bool {
+    let Some(challenge) = webhook_url.verification_challenge.clone() else {
+        return webhook_url.verified_at.is_some();
+    };
+
+    let now = Utc::now();
+    let request = WebhookVerificationPayload::new(webhook_url_id, challenge.clone())
+        .to_signed_request(
+            webhook_url.webhook_url.clone(),
+            &webhook_url.active_signing_secrets(now),
+            now.timestamp(),
+        );
+
+    let answered = match webhook_url.queue_target() {
+        Some(target) => QueueSinks::new(VERIFICATION_TIMEOUT, url_policy)
+            .publish(&target, &SinkMessage::from_request(&request))
+            .await
+            .is_ok(),
+        None => match WebhookHttpClient::new(VERIFICATION_TIMEOUT, url_policy) {
+            Ok(client) => send_verification_challenge(&client, &request, &challenge).await,
+            Err(_) => false,
+        },
+    };
+
+    if !answered {
+        return false;
+    }
+
+    WebhookUrlsModel::mark_verified(webhook_url_id, &app_state.database)
+        .await
+        .unwrap_or(false)
+}
+
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
-pub struct ListWebhookUrlsInput {
-    session_token: String, // or api key
-                           // wallet_public_address: String,
+pub struct VerifyWebhookUrlInput {
+    session_token: String,
+    webhook_url_id: i32,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
-pub struct GetWebhookUrlParams {
+struct VerifyWebhookUrlOutput {
     id: i32,
-    session_token: String,
+    verified: bool,
 }
 
-/*
 #[utoipa::path(
-    get,
-    path = "/api/webhook/get",
-    params(
-        ("id" = i32, Path, description = "Webhook URL ID"),
-        ("session_token" = String, Query, description = "Session token for authentication")
-    ),
+    post,
+    path = "/api/webhooks/verify",
+    request_body = VerifyWebhookUrlInput,
     responses(
-        (status = 200, description = "Returns a single webhook URL", body = AuthResponse<SelectedRecord<WebhookUrl>>),
+        (status = 200, description = "Sends the verification challenge again. The URL starts receiving events once it echoes the challenge.", body = AuthResponse<VerifyWebhookUrlOutput>),
     )
 )]
-async fn get_webhook_url(
-    web::Query(params): web::Query<GetWebhookUrlParams>,
+async fn verify_webhook_url(
+    input: Json<VerifyWebhookUrlInput>,
     app_state: Data<AppState>,
 ) -> impl Responder {
-    // Validate session token
-    let token_valid = validate_api_key_or_session_token(&params.session_token, &app_state).await;
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
 
     let Some(token_valid) = token_valid else {
         return HttpResponse::Unauthorized().json(AuthResponse::<String> {
@@ -155,64 +336,73 @@ async fn get_webhook_url(
         });
     };
 
-    // Get the webhook URL from the database
-    let webhook_result = WebhookUrlsModel::find_by_id(params.id, &app_state.database).await;
+    let webhook = match WebhookUrlsModel::find_by_id(input.webhook_url_id, &app_state.database)
+        .await
+    {
+        Ok(Some(webhook))
+            if webhook.entry.owner_wallet_address.0 == token_valid.owner_public_address =>
+        {
+            webhook
+        }
+        Ok(_) => {
+            return HttpResponse::NotFound().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Webhook URL not found".to_string()),
+            })
+        }
+        Err(_) => {
+            return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    };
 
-    match webhook_result {
-        Ok(Some(webhook)) => {
-            // Verify ownership if the token is a user session
-            let wallet_address = token_valid.owner_public_address;
-            if webhook.entry.owner_wallet_address.0 != wallet_address {
-                return HttpResponse::Forbidden().json(AuthResponse::<String> {
-                    success: false,
-                    data: None,
-                    error: Some("You do not have permission to view this webhook".to_string()),
-                });
-            }
+    let verified = send_verification(
+        input.webhook_url_id,
+        &webhook.entry,
+        WebhookUrlPolicy::from_env(),
+        &app_state,
+    )
+    .await;
 
-            HttpResponse::Ok().json(AuthResponse {
-                success: true,
-                data: Some(webhook),
-                error: None,
-            })
-        },
-        Ok(None) => HttpResponse::NotFound().json(AuthResponse::<String> {
-            success: false,
-            data: None,
-            error: Some("Webhook URL not found".to_string()),
-        }),
-        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
-            success: false,
-            data: None,
-            error: Some("Database error".to_string()),
+    HttpResponse::Ok().json(AuthResponse {
+        success: true,
+        data: Some(VerifyWebhookUrlOutput {
+            id: input.webhook_url_id,
+            verified,
         }),
-    }
+        error: None,
+    })
 }
 
-*/
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct EnableWebhookUrlInput {
+    session_token: String,
+    webhook_url_id: i32,
+}
 
-/*
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
-pub struct WebhookUrlOutput {
-    pub id: i32,
-    pub webhook_url: String,
-    pub scopes: Option<serde_json::Value>,
-    pub created_at: i64,
-}*/
+struct EnableWebhookUrlOutput {
+    id: i32,
+    /// Triggers that queued up while the url was disabled and are now sent in order
+    pending_triggers: u64,
+}
 
 #[utoipa::path(
     post,
-    path = "/api/webhook/list",
-    request_body = ListWebhookUrlsInput,
+    path = "/api/webhooks/enable",
+    request_body = EnableWebhookUrlInput,
     responses(
-        (status = 200, description = "Lists all webhook URLs for a user", body = AuthResponse<Vec<SelectedRecord<WebhookUrl>>>),
+        (status = 200, description = "Re-enables a webhook URL that was disabled for failing. Delivery resumes with its pending triggers.", body = AuthResponse<EnableWebhookUrlOutput>),
     )
 )]
-async fn list_webhook_urls(
-    input: Json<ListWebhookUrlsInput>,
+async fn enable_webhook_url(
+    input: Json<EnableWebhookUrlInput>,
     app_state: Data<AppState>,
 ) -> impl Responder {
-    // Validate session token
     let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
 
     let Some(token_valid) = token_valid else {
@@ -223,19 +413,27 @@ async fn list_webhook_urls(
         });
     };
 
-    let wallet_address = &token_valid.owner_public_address;
-
-    // Fetch webhook URLs for the wallet address
-    let domain_address = DomainEthAddress(wallet_address.clone());
-    let webhook_urls =
-        WebhookUrlsModel::find_by_owner_address(&domain_address, &app_state.database).await;
+    let enabled = WebhookUrlsModel::enable(
+        input.webhook_url_id,
+        &DomainEthAddress(token_valid.owner_public_address),
+        &app_state.database,
+    )
+    .await;
 
-    match webhook_urls {
-        Ok(urls) => HttpResponse::Ok().json(AuthResponse {
+    match enabled {
+        Ok(Some(pending_triggers)) => HttpResponse::Ok().json(AuthResponse {
             success: true,
-            data: Some(urls),
+            data: Some(EnableWebhookUrlOutput {
+                id: input.webhook_url_id,
+                pending_triggers,
+            }),
             error: None,
         }),
+        Ok(None) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Webhook URL not found".to_string()),
+        }),
         Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
             success: false,
             data: None,
@@ -245,30 +443,31 @@ async fn list_webhook_urls(
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
-pub struct DeleteWebhookUrlInput {
+pub struct SetWebhookApiVersionInput {
     session_token: String,
-    //wallet_public_address: String,
     webhook_url_id: i32,
+    /// v0 or v1
+    api_version: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
-struct DeleteWebhookUrlOutput {
-    deleted: bool,
+struct SetWebhookApiVersionOutput {
+    id: i32,
+    api_version: WebhookApiVersion,
 }
 
 #[utoipa::path(
     post,
-    path = "/api/webhook/delete",
-    request_body = DeleteWebhookUrlInput,
+    path = "/api/webhooks/api_version",
+    request_body = SetWebhookApiVersionInput,
     responses(
-        (status = 200, description = "Deletes a webhook URL by ID", body = AuthResponse<DeleteWebhookUrlOutput>),
+        (status = 200, description = "Pins a webhook URL to a body version. Deliveries from then on, retries included, use the new version.", body = AuthResponse<SetWebhookApiVersionOutput>),
     )
 )]
-async fn delete_webhook_url(
-    input: Json<DeleteWebhookUrlInput>,
+async fn set_webhook_api_version(
+    input: Json<SetWebhookApiVersionInput>,
     app_state: Data<AppState>,
 ) -> impl Responder {
-    // Validate session token
     let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
 
     let Some(token_valid) = token_valid else {
@@ -279,16 +478,39 @@ async fn delete_webhook_url(
         });
     };
 
-    // Delete the webhook URL
-    let delete_result =
-        WebhookUrlsModel::delete_by_id(input.webhook_url_id, &app_state.database).await;
+    let api_version: WebhookApiVersion = match input.api_version.parse() {
+        Ok(api_version) => api_version,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
 
-    match delete_result {
-        Ok(deleted) => HttpResponse::Ok().json(AuthResponse {
+    let updated = WebhookUrlsModel::set_api_version(
+        input.webhook_url_id,
+        &DomainEthAddress(token_valid.owner_public_address),
+        api_version,
+        &app_state.database,
+    )
+    .await;
+
+    match updated {
+        Ok(true) => HttpResponse::Ok().json(AuthResponse {
             success: true,
-            data: Some(DeleteWebhookUrlOutput { deleted }),
+            data: Some(SetWebhookApiVersionOutput {
+                id: input.webhook_url_id,
+                api_version,
+            }),
             error: None,
         }),
+        Ok(false) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Webhook URL not found".to_string()),
+        }),
         Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
             success: false,
             data: None,
@@ -297,22 +519,47 @@ async fn delete_webhook_url(
     }
 }
 
+/// Brokers take events one message at a time, so only http urls can batch
+fn validate_batching(
+    batching: WebhookBatching,
+    is_queue_target: bool,
+) -> Result<WebhookBatching, WebhookBatchingError> {
+    if is_queue_target {
+        return Err(WebhookBatchingError::UnsupportedTarget);
+    }
+
+    batching.validate()
+}
+
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
-pub struct TestTriggerWebhookUrlInput {
+pub struct SetWebhookBatchingInput {
     session_token: String,
     webhook_url_id: i32,
-    // event_type: Option<String>,
-    //event_data: Option<serde_json::Value>,
+    /// Omit to send every event on its own again
+    batching: Option<WebhookBatching>,
 }
 
-async fn test_trigger_webhook(
-    input: Json<TestTriggerWebhookUrlInput>,
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SetWebhookBatchingOutput {
+    id: i32,
+    batching: Option<WebhookBatching>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/batching",
+    request_body = SetWebhookBatchingInput,
+    responses(
+        (status = 200, description = "Switches batched deliveries on or off for a webhook URL. Pending events are sent the new way.", body = AuthResponse<SetWebhookBatchingOutput>),
+    )
+)]
+async fn set_webhook_batching(
+    input: Json<SetWebhookBatchingInput>,
     app_state: Data<AppState>,
 ) -> impl Responder {
-    // Validate session token
     let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
 
-    let Some(_) = token_valid else {
+    let Some(token_valid) = token_valid else {
         return HttpResponse::Unauthorized().json(AuthResponse::<String> {
             success: false,
             data: None,
@@ -320,31 +567,69 @@ async fn test_trigger_webhook(
         });
     };
 
-    let payment_data: PaymentSummary = PaymentSummary::generate_test_payment_summary();
-    let webhook_trigger = WebhookTrigger::with_event_data(input.webhook_url_id, payment_data);
+    let webhook = match WebhookUrlsModel::find_by_id(input.webhook_url_id, &app_state.database)
+        .await
+    {
+        Ok(Some(webhook))
+            if webhook.entry.owner_wallet_address.0 == token_valid.owner_public_address =>
+        {
+            webhook
+        }
+        Ok(_) => {
+            return HttpResponse::NotFound().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Webhook URL not found".to_string()),
+            })
+        }
+        Err(_) => {
+            return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    };
 
-    // Create webhook trigger with payment data or custom data
-    /*  let webhook_trigger = if let (Some(event_type), Some(event_data)) = (&input.event_type, &input.event_data) {
-        // If custom event data is provided, use it
-        let mut trigger = WebhookTrigger::new(input.webhook_url_id);
-        trigger.event_type = Some(event_type.clone());
-        trigger.event_data = Some(DomainJson::new(event_data.clone()));
-        trigger
-    } else {
-        // Otherwise use default payment summary data
-        let payment_data: PaymentSummary = PaymentSummary::generate_test_payment_summary();
-        WebhookTrigger::with_event_data(input.webhook_url_id, payment_data)
-    };*/
+    let is_queue_target = webhook.entry.queue_target().is_some();
 
-    let trigger_result =
-        WebhookTriggersModel::insert_one(webhook_trigger, &app_state.database).await;
+    let batching = match input
+        .batching
+        .map(|batching| validate_batching(batching, is_queue_target))
+        .transpose()
+    {
+        Ok(batching) => batching,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some(e.to_string()),
+            })
+        }
+    };
 
-    match trigger_result {
-        Ok(triggered) => HttpResponse::Ok().json(AuthResponse {
+    let updated = WebhookUrlsModel::set_batching(
+        input.webhook_url_id,
+        &DomainEthAddress(token_valid.owner_public_address),
+        batching,
+        &app_state.database,
+    )
+    .await;
+
+    match updated {
+        Ok(true) => HttpResponse::Ok().json(AuthResponse {
             success: true,
-            data: Some(triggered),
+            data: Some(SetWebhookBatchingOutput {
+                id: input.webhook_url_id,
+                batching,
+            }),
             error: None,
         }),
+        Ok(false) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Webhook URL not found".to_string()),
+        }),
         Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
             success: false,
             data: None,
@@ -353,19 +638,596 @@ async fn test_trigger_webhook(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/webhooks/schema/{api_version}",
+    params(
+        ("api_version" = String, Path, description = "v0 or v1")
+    ),
+    responses(
+        (status = 200, description = "JSON Schema of the webhook request body in an api version"),
+    )
+)]
+async fn get_webhook_schema(api_version: web::Path<String>) -> impl Responder {
+    match api_version.parse::<WebhookApiVersion>() {
+        Ok(api_version) => HttpResponse::Ok().json(webhook_json_schema(api_version)),
+        Err(e) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+/// Validates a template and custom headers sent by a client
+fn parse_payload_template(
+    payload_template: Option<serde_json::Value>,
+    custom_headers: Option<BTreeMap<String, String>>,
+) -> Result<(Option<WebhookPayloadTemplate>, Option<WebhookCustomHeaders>), WebhookTemplateError> {
+    let payload_template = payload_template
+        .map(WebhookPayloadTemplate::from_json)
+        .transpose()?;
+    let custom_headers = custom_headers.map(WebhookCustomHeaders::new).transpose()?;
+
+    Ok((payload_template, custom_headers))
+}
+
+fn to_domain_json<T: Serialize>(value: &T) -> DomainJson {
+    DomainJson::new(serde_json::to_value(value).unwrap_or_default())
+}
+
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
-pub struct AcknowledgeTriggerWebhookUrlInput {
+pub struct SetWebhookPayloadTemplateInput {
     session_token: String,
-    webhook_trigger_id: i32,
+    webhook_url_id: i32,
+    /// e.g. `{"preset": "discord"}`. Omit to send the body of the URL's api version.
+    payload_template: Option<serde_json::Value>,
+    /// Omit to send no extra headers
+    custom_headers: Option<BTreeMap<String, String>>,
 }
 
-async fn acknowledge_webhook_trigger(
-    input: Json<AcknowledgeTriggerWebhookUrlInput>,
-    app_state: Data<AppState>,
-) -> impl Responder {
-    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct SetWebhookPayloadTemplateOutput {
+    id: i32,
+    payload_template: Option<WebhookPayloadTemplate>,
+    /// Names only, values are never returned
+    custom_headers: Vec<String>,
+}
 
-    let Some(_) = token_valid else {
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/template",
+    request_body = SetWebhookPayloadTemplateInput,
+    responses(
+        (status = 200, description = "Replaces the payload template and custom headers of a webhook URL. Deliveries from then on, retries included, use them.", body = AuthResponse<SetWebhookPayloadTemplateOutput>),
+    )
+)]
+async fn set_webhook_payload_template(
+    input: Json<SetWebhookPayloadTemplateInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let (payload_template, custom_headers) =
+        match parse_payload_template(input.payload_template.clone(), input.custom_headers.clone())
+        {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                })
+            }
+        };
+
+    let updated = WebhookUrlsModel::set_payload_template(
+        input.webhook_url_id,
+        &DomainEthAddress(token_valid.owner_public_address),
+        payload_template.as_ref().map(to_domain_json),
+        custom_headers.as_ref().map(to_domain_json),
+        &app_state.database,
+    )
+    .await;
+
+    match updated {
+        Ok(true) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(SetWebhookPayloadTemplateOutput {
+                id: input.webhook_url_id,
+                payload_template,
+                custom_headers: custom_headers.map(|headers| headers.names()).unwrap_or_default(),
+            }),
+            error: None,
+        }),
+        Ok(false) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Webhook URL not found".to_string()),
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct PreviewWebhookPayloadTemplateInput {
+    session_token: String,
+    /// Start from this URL's api version, template and headers
+    webhook_url_id: Option<i32>,
+    /// Rendered instead of the URL's template
+    payload_template: Option<serde_json::Value>,
+    /// Sent instead of the URL's custom headers
+    custom_headers: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct PreviewWebhookPayloadTemplateOutput {
+    /// The exact body that would be sent
+    body: serde_json::Value,
+    /// Names of the custom headers that would be sent
+    custom_headers: Vec<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/template/preview",
+    request_body = PreviewWebhookPayloadTemplateInput,
+    responses(
+        (status = 200, description = "Renders a payload template against a sample payment_summary event without sending anything.", body = AuthResponse<PreviewWebhookPayloadTemplateOutput>),
+    )
+)]
+async fn preview_webhook_payload_template(
+    input: Json<PreviewWebhookPayloadTemplateInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let (payload_template, custom_headers) =
+        match parse_payload_template(input.payload_template.clone(), input.custom_headers.clone())
+        {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return HttpResponse::BadRequest().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some(e.to_string()),
+                })
+            }
+        };
+
+    let webhook = match input.webhook_url_id {
+        None => None,
+        Some(webhook_url_id) => {
+            match WebhookUrlsModel::find_by_id(webhook_url_id, &app_state.database).await {
+                Ok(Some(webhook))
+                    if webhook.entry.owner_wallet_address.0 == token_valid.owner_public_address =>
+                {
+                    Some(webhook)
+                }
+                Ok(_) => {
+                    return HttpResponse::NotFound().json(AuthResponse::<String> {
+                        success: false,
+                        data: None,
+                        error: Some("Webhook URL not found".to_string()),
+                    })
+                }
+                Err(_) => {
+                    return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                        success: false,
+                        data: None,
+                        error: Some("Database error".to_string()),
+                    })
+                }
+            }
+        }
+    };
+
+    let payment = PaymentSummary::generate_test_payment_summary();
+
+    let payload = DefiRelayWebhookPayload {
+        webhook_trigger_id: 0,
+        webhook_id: input.webhook_url_id.unwrap_or_default(),
+        webhook_url: webhook
+            .as_ref()
+            .map(|webhook| webhook.entry.webhook_url.clone())
+            .unwrap_or_default(),
+        event_type: Some("payment_summary".to_string()),
+        event_data: Some(to_domain_json(&payment)),
+        redelivery: false,
+        api_version: webhook
+            .as_ref()
+            .map(|webhook| webhook.entry.api_version)
+            .unwrap_or(WebhookApiVersion::LATEST),
+        created_at: Some(Utc::now()),
+        payload_template: payload_template.or_else(|| {
+            webhook.as_ref().and_then(|webhook| {
+                WebhookPayloadTemplate::from_stored(webhook.entry.payload_template.as_ref())
+            })
+        }),
+        custom_headers: custom_headers.unwrap_or_else(|| {
+            WebhookCustomHeaders::from_stored(
+                webhook
+                    .as_ref()
+                    .and_then(|webhook| webhook.entry.custom_headers.as_ref()),
+            )
+        }),
+    };
+
+    match payload.body() {
+        Ok(body) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(PreviewWebhookPayloadTemplateOutput {
+                body,
+                custom_headers: payload.custom_headers.names(),
+            }),
+            error: None,
+        }),
+        Err(e) => HttpResponse::BadRequest().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ListWebhookUrlsInput {
+    session_token: String, // or api key
+                           // wallet_public_address: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct GetWebhookUrlParams {
+    id: i32,
+    session_token: String,
+}
+
+/*
+#[utoipa::path(
+    get,
+    path = "/api/webhook/get",
+    params(
+        ("id" = i32, Path, description = "Webhook URL ID"),
+        ("session_token" = String, Query, description = "Session token for authentication")
+    ),
+    responses(
+        (status = 200, description = "Returns a single webhook URL", body = AuthResponse<SelectedRecord<WebhookUrl>>),
+    )
+)]
+async fn get_webhook_url(
+    web::Query(params): web::Query<GetWebhookUrlParams>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    // Validate session token
+    let token_valid = validate_api_key_or_session_token(&params.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    // Get the webhook URL from the database
+    let webhook_result = WebhookUrlsModel::find_by_id(params.id, &app_state.database).await;
+
+    match webhook_result {
+        Ok(Some(webhook)) => {
+            // Verify ownership if the token is a user session
+            let wallet_address = token_valid.owner_public_address;
+            if webhook.entry.owner_wallet_address.0 != wallet_address {
+                return HttpResponse::Forbidden().json(AuthResponse::<String> {
+                    success: false,
+                    data: None,
+                    error: Some("You do not have permission to view this webhook".to_string()),
+                });
+            }
+
+            HttpResponse::Ok().json(AuthResponse {
+                success: true,
+                data: Some(webhook),
+                error: None,
+            })
+        },
+        Ok(None) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Webhook URL not found".to_string()),
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+*/
+
+/*
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct WebhookUrlOutput {
+    pub id: i32,
+    pub webhook_url: String,
+    pub scopes: Option<serde_json::Value>,
+    pub created_at: i64,
+}*/
+
+#[utoipa::path(
+    post,
+    path = "/api/webhook/list",
+    request_body = ListWebhookUrlsInput,
+    responses(
+        (status = 200, description = "Lists all webhook URLs for a user", body = AuthResponse<Vec<SelectedRecord<WebhookUrl>>>),
+    )
+)]
+async fn list_webhook_urls(
+    input: Json<ListWebhookUrlsInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    // Validate session token
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let wallet_address = &token_valid.owner_public_address;
+
+    // Fetch webhook URLs for the wallet address
+    let domain_address = DomainEthAddress(wallet_address.clone());
+    let webhook_urls =
+        WebhookUrlsModel::find_by_owner_address(&domain_address, app_state.database.as_ref()).await;
+
+    match webhook_urls {
+        Ok(urls) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(urls),
+            error: None,
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct DeleteWebhookUrlInput {
+    session_token: String,
+    //wallet_public_address: String,
+    webhook_url_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct DeleteWebhookUrlOutput {
+    deleted: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhook/delete",
+    request_body = DeleteWebhookUrlInput,
+    responses(
+        (status = 200, description = "Deletes a webhook URL by ID", body = AuthResponse<DeleteWebhookUrlOutput>),
+    )
+)]
+async fn delete_webhook_url(
+    input: Json<DeleteWebhookUrlInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    // Validate session token
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    // Delete the webhook URL
+    let delete_result =
+        WebhookUrlsModel::delete_by_id(input.webhook_url_id, &app_state.database).await;
+
+    match delete_result {
+        Ok(deleted) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(DeleteWebhookUrlOutput { deleted }),
+            error: None,
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+/// How long the old secret keeps signing deliveries after a rotation, unless the caller says otherwise
+const DEFAULT_SECRET_GRACE_PERIOD_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RotateWebhookSecretInput {
+    session_token: String,
+    webhook_url_id: i32,
+    /// Seconds the previous secret stays valid, defaults to 24 hours. 0 revokes it immediately.
+    grace_period_secs: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct RotateWebhookSecretOutput {
+    id: i32,
+    signing_secret: String,
+    /// Unix timestamp after which deliveries are no longer signed with the old secret
+    previous_secret_expires_at: i64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/rotate_secret",
+    request_body = RotateWebhookSecretInput,
+    responses(
+        (status = 200, description = "Issues a new signing secret for a webhook URL. The old one keeps working for the grace period.", body = AuthResponse<RotateWebhookSecretOutput>),
+    )
+)]
+async fn rotate_webhook_secret(
+    input: Json<RotateWebhookSecretInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let grace_period_secs = input
+        .grace_period_secs
+        .unwrap_or(DEFAULT_SECRET_GRACE_PERIOD_SECS);
+
+    if grace_period_secs < 0 {
+        return HttpResponse::BadRequest().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("grace_period_secs cannot be negative".to_string()),
+        });
+    }
+
+    let signing_secret = generate_signing_secret();
+    let previous_secret_expires_at = Utc::now() + Duration::seconds(grace_period_secs);
+
+    let rotated = WebhookUrlsModel::rotate_signing_secret(
+        input.webhook_url_id,
+        &DomainEthAddress(token_valid.owner_public_address),
+        &signing_secret,
+        previous_secret_expires_at,
+        &app_state.database,
+    )
+    .await;
+
+    match rotated {
+        Ok(true) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(RotateWebhookSecretOutput {
+                id: input.webhook_url_id,
+                signing_secret,
+                previous_secret_expires_at: previous_secret_expires_at.timestamp(),
+            }),
+            error: None,
+        }),
+        Ok(false) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Webhook URL not found".to_string()),
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TestTriggerWebhookUrlInput {
+    session_token: String,
+    webhook_url_id: i32,
+    // event_type: Option<String>,
+    //event_data: Option<serde_json::Value>,
+}
+
+async fn test_trigger_webhook(
+    input: Json<TestTriggerWebhookUrlInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    // Validate session token
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(_) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let payment_data: PaymentSummary = PaymentSummary::generate_test_payment_summary();
+    let webhook_trigger = WebhookTrigger::with_event_data(input.webhook_url_id, payment_data);
+
+    // Create webhook trigger with payment data or custom data
+    /*  let webhook_trigger = if let (Some(event_type), Some(event_data)) = (&input.event_type, &input.event_data) {
+        // If custom event data is provided, use it
+        let mut trigger = WebhookTrigger::new(input.webhook_url_id);
+        trigger.event_type = Some(event_type.clone());
+        trigger.event_data = Some(DomainJson::new(event_data.clone()));
+        trigger
+    } else {
+        // Otherwise use default payment summary data
+        let payment_data: PaymentSummary = PaymentSummary::generate_test_payment_summary();
+        WebhookTrigger::with_event_data(input.webhook_url_id, payment_data)
+    };*/
+
+    let trigger_result =
+        WebhookTriggersModel::insert_one(webhook_trigger, &app_state.database).await;
+
+    match trigger_result {
+        Ok(triggered) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(triggered),
+            error: None,
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct AcknowledgeTriggerWebhookUrlInput {
+    session_token: String,
+    webhook_trigger_id: i32,
+}
+
+async fn acknowledge_webhook_trigger(
+    input: Json<AcknowledgeTriggerWebhookUrlInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(_) = token_valid else {
         return HttpResponse::Unauthorized().json(AuthResponse::<String> {
             success: false,
             data: None,
@@ -376,7 +1238,7 @@ async fn acknowledge_webhook_trigger(
     let trigger_result = WebhookTriggersModel::update_status(
         input.webhook_trigger_id,
         WebhookTriggerStatus::Acknowledged,
-        &app_state.database,
+        app_state.database.as_ref(),
     )
     .await;
 
@@ -393,3 +1255,387 @@ async fn acknowledge_webhook_trigger(
         }),
     }
 }
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ListWebhookTriggersInput {
+    session_token: String,
+    webhook_url_id: i32,
+    pagination: Option<PaginationData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct WebhookTriggerOutput {
+    id: i32,
+    webhook_url_id: i32,
+    status: String,
+    event_type: Option<String>,
+    event_data: Option<DomainJson>,
+    attempts: i32,
+    /// Unix timestamp of the latest attempt
+    last_triggered_at: Option<i64>,
+    /// Unix timestamp before which a failed trigger is not retried
+    next_attempt_at: Option<i64>,
+}
+
+impl From<SelectedRecord<WebhookTrigger>> for WebhookTriggerOutput {
+    fn from(record: SelectedRecord<WebhookTrigger>) -> Self {
+        let trigger = record.entry;
+
+        Self {
+            id: record.id.into(),
+            webhook_url_id: trigger.webhook_id,
+            status: trigger.status,
+            event_type: trigger.event_type,
+            event_data: trigger.event_data,
+            attempts: trigger.attempts,
+            last_triggered_at: trigger.last_triggered_at.map(|t| t.timestamp()),
+            next_attempt_at: trigger.next_attempt_at.map(|t| t.timestamp()),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/triggers",
+    request_body = ListWebhookTriggersInput,
+    responses(
+        (status = 200, description = "Lists the triggers of a webhook URL", body = AuthResponse<PaginatedResponse<WebhookTriggerOutput>>),
+    )
+)]
+async fn list_webhook_triggers(
+    input: Json<ListWebhookTriggersInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let webhook = WebhookUrlsModel::find_by_id(input.webhook_url_id, &app_state.database).await;
+
+    match webhook {
+        Ok(Some(webhook))
+            if webhook.entry.owner_wallet_address.0 == token_valid.owner_public_address => {}
+        Ok(_) => {
+            return HttpResponse::NotFound().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Webhook URL not found".to_string()),
+            })
+        }
+        Err(_) => {
+            return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+
+    let pagination = input.pagination.clone().unwrap_or_default();
+
+    let triggers = WebhookTriggersModel::find_by_webhook_id_paginated(
+        input.webhook_url_id,
+        &pagination,
+        &app_state.database,
+    )
+    .await;
+
+    match triggers {
+        Ok((records, total_count)) => {
+            let items: Vec<WebhookTriggerOutput> =
+                records.into_iter().map(WebhookTriggerOutput::from).collect();
+
+            HttpResponse::Ok().json(AuthResponse {
+                success: true,
+                data: Some(PaginatedResponse::from_pagination_data(
+                    &pagination,
+                    items,
+                    total_count,
+                )),
+                error: None,
+            })
+        }
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ListWebhookDeliveriesInput {
+    session_token: String,
+    webhook_trigger_id: i32,
+    pagination: Option<PaginationData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct WebhookDeliveryOutput {
+    id: i32,
+    webhook_trigger_id: i32,
+    attempt: i32,
+    request_url: String,
+    request_headers: DomainJson,
+    response_status: Option<i32>,
+    /// First 4096 bytes of what the endpoint answered
+    response_body: Option<String>,
+    latency_ms: i64,
+    /// http_status, timeout, connect or request when the attempt failed
+    error_kind: Option<String>,
+    /// Sent because the trigger was redelivered or replayed
+    redelivery: bool,
+    /// Wallet that asked for the redelivery, on the attempt 0 row logging the request
+    replay_requested_by: Option<DomainEthAddress>,
+    /// single (/redeliver) or range (/replay), on the attempt 0 row logging the request
+    replay_scope: Option<String>,
+    /// Unix timestamp of the attempt
+    created_at: i64,
+}
+
+impl From<SelectedRecord<WebhookDelivery>> for WebhookDeliveryOutput {
+    fn from(record: SelectedRecord<WebhookDelivery>) -> Self {
+        let delivery = record.entry;
+
+        Self {
+            id: record.id.into(),
+            webhook_trigger_id: delivery.webhook_trigger_id,
+            attempt: delivery.attempt,
+            request_url: delivery.request_url,
+            request_headers: delivery.request_headers,
+            response_status: delivery.response_status,
+            response_body: delivery.response_body,
+            latency_ms: delivery.latency_ms,
+            error_kind: delivery.error_kind,
+            redelivery: delivery.redelivery,
+            replay_requested_by: delivery.replay_requested_by,
+            replay_scope: delivery.replay_scope,
+            created_at: delivery.created_at.timestamp(),
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/deliveries",
+    request_body = ListWebhookDeliveriesInput,
+    responses(
+        (status = 200, description = "Lists the delivery attempts of a webhook trigger", body = AuthResponse<PaginatedResponse<WebhookDeliveryOutput>>),
+    )
+)]
+async fn list_webhook_deliveries(
+    input: Json<ListWebhookDeliveriesInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let trigger = WebhookTriggersModel::find_webhook_trigger_joined_by_id(
+        input.webhook_trigger_id,
+        &app_state.database,
+    )
+    .await;
+
+    match trigger {
+        Ok(Some(trigger))
+            if trigger.entry.webhook_url.owner_wallet_address.0
+                == token_valid.owner_public_address => {}
+        Ok(_) => {
+            return HttpResponse::NotFound().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Webhook trigger not found".to_string()),
+            })
+        }
+        Err(_) => {
+            return HttpResponse::InternalServerError().json(AuthResponse::<String> {
+                success: false,
+                data: None,
+                error: Some("Database error".to_string()),
+            })
+        }
+    }
+
+    let pagination = input.pagination.clone().unwrap_or_default();
+
+    let deliveries = WebhookDeliveriesModel::find_by_trigger_id_paginated(
+        input.webhook_trigger_id,
+        &pagination,
+        &app_state.database,
+    )
+    .await;
+
+    match deliveries {
+        Ok((records, total_count)) => {
+            let items: Vec<WebhookDeliveryOutput> = records
+                .into_iter()
+                .map(WebhookDeliveryOutput::from)
+                .collect();
+
+            HttpResponse::Ok().json(AuthResponse {
+                success: true,
+                data: Some(PaginatedResponse::from_pagination_data(
+                    &pagination,
+                    items,
+                    total_count,
+                )),
+                error: None,
+            })
+        }
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct RedeliverWebhookTriggerInput {
+    session_token: String,
+    webhook_trigger_id: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+struct RedeliverWebhookTriggersOutput {
+    /// How many triggers were put back in the queue
+    requeued: u64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/redeliver",
+    request_body = RedeliverWebhookTriggerInput,
+    responses(
+        (status = 200, description = "Queues a sent, acknowledged or dead-lettered trigger to be delivered again", body = AuthResponse<RedeliverWebhookTriggersOutput>),
+    )
+)]
+async fn redeliver_webhook_trigger(
+    input: Json<RedeliverWebhookTriggerInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let reset = WebhookTriggersModel::reset_for_redelivery(
+        input.webhook_trigger_id,
+        &DomainEthAddress(token_valid.owner_public_address),
+        &app_state.database,
+    )
+    .await;
+
+    match reset {
+        Ok(true) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(RedeliverWebhookTriggersOutput { requeued: 1 }),
+            error: None,
+        }),
+        Ok(false) => HttpResponse::NotFound().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("No redeliverable webhook trigger found".to_string()),
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ReplayWebhookTriggersInput {
+    session_token: String,
+    webhook_url_id: i32,
+    /// Unix timestamp, triggers created at or after this are replayed
+    from: i64,
+    /// Unix timestamp, triggers created at or before this are replayed
+    to: i64,
+    /// Only replay triggers in this status, e.g. dead_letter
+    status: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/webhooks/replay",
+    request_body = ReplayWebhookTriggersInput,
+    responses(
+        (status = 200, description = "Queues every settled trigger of a webhook URL created in a time window to be delivered again", body = AuthResponse<RedeliverWebhookTriggersOutput>),
+    )
+)]
+async fn replay_webhook_triggers(
+    input: Json<ReplayWebhookTriggersInput>,
+    app_state: Data<AppState>,
+) -> impl Responder {
+    let token_valid = validate_api_key_or_session_token(&input.session_token, &app_state).await;
+
+    let Some(token_valid) = token_valid else {
+        return HttpResponse::Unauthorized().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Invalid session".to_string()),
+        });
+    };
+
+    let (Some(from), Some(to)) = (
+        DateTime::from_timestamp(input.from, 0),
+        DateTime::from_timestamp(input.to, 0),
+    ) else {
+        return HttpResponse::BadRequest().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("from and to must be unix timestamps".to_string()),
+        });
+    };
+
+    if from > to {
+        return HttpResponse::BadRequest().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("from must not be after to".to_string()),
+        });
+    }
+
+    let reset = WebhookTriggersModel::reset_range_for_redelivery(
+        input.webhook_url_id,
+        &DomainEthAddress(token_valid.owner_public_address),
+        from,
+        to,
+        input.status.clone().map(WebhookTriggerStatus::from),
+        &app_state.database,
+    )
+    .await;
+
+    match reset {
+        Ok(requeued) => HttpResponse::Ok().json(AuthResponse {
+            success: true,
+            data: Some(RedeliverWebhookTriggersOutput { requeued }),
+            error: None,
+        }),
+        Err(_) => HttpResponse::InternalServerError().json(AuthResponse::<String> {
+            success: false,
+            data: None,
+            error: Some("Database error".to_string()),
+        }),
+    }
+}
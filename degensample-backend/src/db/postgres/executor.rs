@@ -0,0 +1,80 @@
+use std::future::Future;
+
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Row, Transaction};
+
+/// Anything a model can run statements against: the pooled `Database`, or an open
+/// `Transaction` when a write has to commit atomically with other writes.
+pub trait DbExecutor: Sync {
+    fn query(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> impl Future<Output = Result<Vec<Row>, PostgresModelError>> + Send;
+
+    fn query_one(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> impl Future<Output = Result<Row, PostgresModelError>> + Send;
+
+    fn execute(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> impl Future<Output = Result<u64, PostgresModelError>> + Send;
+}
+
+impl DbExecutor for Database {
+    async fn query(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, PostgresModelError> {
+        Database::query(self, query, params).await
+    }
+
+    async fn query_one(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Row, PostgresModelError> {
+        Database::query_one(self, query, params).await
+    }
+
+    async fn execute(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, PostgresModelError> {
+        Database::execute(self, query, params).await
+    }
+}
+
+impl DbExecutor for Transaction<'_> {
+    async fn query(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Vec<Row>, PostgresModelError> {
+        Ok(Transaction::query(self, query, params).await?)
+    }
+
+    async fn query_one(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<Row, PostgresModelError> {
+        Ok(Transaction::query_one(self, query, params).await?)
+    }
+
+    async fn execute(
+        &self,
+        query: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, PostgresModelError> {
+        Ok(Transaction::execute(self, query, params).await?)
+    }
+}
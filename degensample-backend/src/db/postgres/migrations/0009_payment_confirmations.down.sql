@@ -0,0 +1,8 @@
+DROP INDEX IF EXISTS idx_payments_status;
+
+UPDATE payments SET status = 'paid' WHERE status IN ('detected', 'confirmed', 'finalized');
+
+ALTER TABLE payments
+    DROP COLUMN block_hash,
+    DROP COLUMN confirmations,
+    DROP COLUMN status_updated_at;
@@ -0,0 +1,8 @@
+ALTER TABLE payments
+    ADD COLUMN block_hash VARCHAR(255),
+    ADD COLUMN confirmations BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN status_updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+UPDATE payments SET status = 'detected' WHERE status = 'paid';
+
+CREATE INDEX idx_payments_status ON payments (status);
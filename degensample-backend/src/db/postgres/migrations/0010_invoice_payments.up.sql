@@ -0,0 +1,28 @@
+CREATE TABLE invoice_payments (
+    id SERIAL PRIMARY KEY,
+
+    uuid VARCHAR(255) NOT NULL,
+    chain_id BIGINT NOT NULL,
+
+    contract_address VARCHAR(255) NOT NULL,
+    token_address VARCHAR(255) NOT NULL,
+
+    from_address VARCHAR(255) NOT NULL,
+    to_address VARCHAR(255) NOT NULL,
+    amount TEXT NOT NULL,
+
+    transaction_hash VARCHAR(255) NOT NULL,
+    log_index BIGINT NOT NULL,
+
+    block_number BIGINT,
+    block_hash VARCHAR(255),
+
+    status VARCHAR(255) NOT NULL DEFAULT 'detected',
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    UNIQUE (transaction_hash, log_index)
+);
+
+CREATE INDEX idx_invoice_payments_to_address ON invoice_payments (to_address, chain_id);
+CREATE INDEX idx_invoice_payments_uuid ON invoice_payments (uuid);
@@ -0,0 +1,30 @@
+CREATE TABLE invoices (
+    id SERIAL PRIMARY KEY,
+
+    uuid VARCHAR(255) UNIQUE NOT NULL,
+
+    owner_address VARCHAR(255) NOT NULL,
+
+    chain_id BIGINT NOT NULL,
+    contract_address VARCHAR(255) NOT NULL,
+    token_address VARCHAR(255) NOT NULL,
+
+    pay_to_array TEXT[] NOT NULL,
+    pay_to_amounts TEXT[] NOT NULL,
+
+    nonce TEXT NOT NULL,
+    total_amount TEXT NOT NULL,
+
+    metadata JSONB,
+
+    status VARCHAR(255) NOT NULL DEFAULT 'draft',
+
+    created_tx_hash VARCHAR(255),
+    paid_tx_hash VARCHAR(255),
+    paid_by VARCHAR(255),
+
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_invoices_owner_address ON invoices (owner_address);
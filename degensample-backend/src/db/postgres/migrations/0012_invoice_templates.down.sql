@@ -0,0 +1,10 @@
+ALTER TABLE IF EXISTS api_workspaces
+    DROP COLUMN IF EXISTS invoice_template_uuid;
+
+DROP INDEX IF EXISTS idx_invoices_template_uuid;
+
+ALTER TABLE invoices
+    DROP COLUMN template_uuid,
+    DROP COLUMN created_for_address;
+
+DROP TABLE invoice_templates;
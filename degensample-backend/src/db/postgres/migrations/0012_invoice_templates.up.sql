@@ -0,0 +1,30 @@
+CREATE TABLE invoice_templates (
+    id SERIAL PRIMARY KEY,
+
+    template_uuid VARCHAR(255) UNIQUE NOT NULL,
+
+    owner_address VARCHAR(255) NOT NULL,
+
+    chain_id BIGINT NOT NULL,
+    token_address VARCHAR(255) NOT NULL,
+
+    pay_to_array TEXT[] NOT NULL,
+    pay_to_amounts TEXT[] NOT NULL,
+
+    description TEXT,
+    metadata JSONB,
+
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_invoice_templates_owner_address ON invoice_templates (owner_address);
+
+ALTER TABLE invoices
+    ADD COLUMN template_uuid VARCHAR(255),
+    ADD COLUMN created_for_address VARCHAR(255);
+
+CREATE INDEX idx_invoices_template_uuid ON invoices (template_uuid, created_for_address);
+
+ALTER TABLE IF EXISTS api_workspaces
+    ADD COLUMN IF NOT EXISTS invoice_template_uuid VARCHAR(255);
@@ -0,0 +1,4 @@
+ALTER TABLE payments
+    DROP COLUMN IF EXISTS total_amount;
+
+DROP TABLE IF EXISTS token_metadata;
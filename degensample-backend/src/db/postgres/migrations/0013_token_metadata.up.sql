@@ -0,0 +1,17 @@
+CREATE TABLE token_metadata (
+    id SERIAL PRIMARY KEY,
+
+    chain_id BIGINT NOT NULL,
+    token_address VARCHAR(255) NOT NULL,
+
+    symbol VARCHAR(255) NOT NULL,
+    name VARCHAR(255),
+    decimals INT NOT NULL,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    UNIQUE (chain_id, token_address)
+);
+
+ALTER TABLE payments
+    ADD COLUMN IF NOT EXISTS total_amount TEXT;
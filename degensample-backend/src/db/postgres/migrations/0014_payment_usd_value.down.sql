@@ -0,0 +1,3 @@
+ALTER TABLE payments
+    DROP COLUMN IF EXISTS usd_price,
+    DROP COLUMN IF EXISTS usd_value;
@@ -0,0 +1,3 @@
+ALTER TABLE payments
+    ADD COLUMN usd_price NUMERIC,
+    ADD COLUMN usd_value NUMERIC;
@@ -0,0 +1,7 @@
+CREATE TABLE bot_cursors (
+    bot_name VARCHAR(255) PRIMARY KEY,
+
+    last_event_id INT,
+
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
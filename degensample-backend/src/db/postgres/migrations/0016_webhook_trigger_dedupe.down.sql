@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS webhook_triggers_dedupe_key_idx;
+
+ALTER TABLE webhook_triggers
+    DROP COLUMN IF EXISTS dedupe_key;
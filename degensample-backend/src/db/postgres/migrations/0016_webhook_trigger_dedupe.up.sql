@@ -0,0 +1,5 @@
+ALTER TABLE webhook_triggers
+    ADD COLUMN dedupe_key TEXT;
+
+-- one trigger per (event, webhook url); triggers created without a key are never deduplicated
+CREATE UNIQUE INDEX webhook_triggers_dedupe_key_idx ON webhook_triggers (dedupe_key);
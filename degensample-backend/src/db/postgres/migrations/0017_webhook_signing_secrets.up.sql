@@ -0,0 +1,5 @@
+-- urls created before signing existed stay unsigned until their secret is rotated
+ALTER TABLE webhook_urls
+    ADD COLUMN signing_secret TEXT,
+    ADD COLUMN previous_signing_secret TEXT,
+    ADD COLUMN previous_secret_expires_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS webhook_triggers_due_idx;
+
+ALTER TABLE webhook_triggers
+    DROP COLUMN IF EXISTS next_attempt_at;
@@ -0,0 +1,4 @@
+ALTER TABLE webhook_triggers
+    ADD COLUMN next_attempt_at TIMESTAMPTZ;
+
+CREATE INDEX webhook_triggers_due_idx ON webhook_triggers (status, next_attempt_at);
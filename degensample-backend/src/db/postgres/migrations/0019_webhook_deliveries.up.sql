@@ -0,0 +1,19 @@
+CREATE TABLE webhook_deliveries (
+    id SERIAL PRIMARY KEY,
+
+    webhook_trigger_id INT NOT NULL REFERENCES webhook_triggers (id) ON DELETE CASCADE,
+    webhook_url_id INT NOT NULL,
+    attempt INT NOT NULL,
+
+    request_url TEXT NOT NULL,
+    request_headers JSONB NOT NULL,
+
+    response_status INT,
+    response_body TEXT,
+    latency_ms BIGINT NOT NULL,
+    error_kind VARCHAR(255),
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX webhook_deliveries_trigger_idx ON webhook_deliveries (webhook_trigger_id, created_at);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS webhook_triggers_pending_by_url_idx;
+
+ALTER TABLE webhook_triggers
+    DROP COLUMN IF EXISTS claimed_until;
@@ -0,0 +1,5 @@
+-- a dispatcher owns a trigger until its claim expires, so a crashed replica's triggers are picked up again
+ALTER TABLE webhook_triggers
+    ADD COLUMN claimed_until TIMESTAMPTZ;
+
+CREATE INDEX webhook_triggers_pending_by_url_idx ON webhook_triggers (webhook_id, id) WHERE status = 'pending';
@@ -0,0 +1,5 @@
+ALTER TABLE webhook_deliveries
+    DROP COLUMN IF EXISTS redelivery;
+
+ALTER TABLE webhook_triggers
+    DROP COLUMN IF EXISTS redelivery_count;
@@ -0,0 +1,5 @@
+ALTER TABLE webhook_triggers
+    ADD COLUMN redelivery_count INT NOT NULL DEFAULT 0;
+
+ALTER TABLE webhook_deliveries
+    ADD COLUMN redelivery BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+ALTER TABLE webhook_urls
+    DROP COLUMN IF EXISTS verification_challenge,
+    DROP COLUMN IF EXISTS verified_at;
@@ -0,0 +1,6 @@
+ALTER TABLE webhook_urls
+    ADD COLUMN verification_challenge TEXT,
+    ADD COLUMN verified_at TIMESTAMPTZ;
+
+-- urls registered before the handshake existed stay active
+UPDATE webhook_urls SET verified_at = NOW();
@@ -0,0 +1,4 @@
+ALTER TABLE webhook_urls
+    ADD COLUMN consecutive_failures INT NOT NULL DEFAULT 0,
+    ADD COLUMN disabled_at TIMESTAMPTZ,
+    ADD COLUMN disabled_reason TEXT;
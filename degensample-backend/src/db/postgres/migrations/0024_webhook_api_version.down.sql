@@ -0,0 +1,2 @@
+ALTER TABLE webhook_urls
+    DROP COLUMN IF EXISTS api_version;
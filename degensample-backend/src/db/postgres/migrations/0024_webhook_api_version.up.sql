@@ -0,0 +1,6 @@
+-- existing urls keep the body they were built against, new ones get the typed envelope
+ALTER TABLE webhook_urls
+    ADD COLUMN api_version TEXT NOT NULL DEFAULT 'v0';
+
+ALTER TABLE webhook_urls
+    ALTER COLUMN api_version SET DEFAULT 'v1';
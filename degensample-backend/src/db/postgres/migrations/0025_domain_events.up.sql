@@ -0,0 +1,20 @@
+CREATE TABLE domain_events (
+    id SERIAL PRIMARY KEY,
+
+    event_type TEXT NOT NULL,
+
+    -- wallets whose webhook urls hear about the event
+    owner_wallet_addresses TEXT[] NOT NULL,
+
+    payload JSONB NOT NULL,
+
+    -- webhook triggers are deduplicated on "{dedupe_key}:{webhook_id}"
+    dedupe_key TEXT,
+
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    -- set once the event has been fanned out to webhook triggers
+    published_at TIMESTAMPTZ
+);
+
+CREATE INDEX domain_events_unpublished_idx ON domain_events (id) WHERE published_at IS NULL;
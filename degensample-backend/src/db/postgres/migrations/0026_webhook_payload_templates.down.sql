@@ -0,0 +1,5 @@
+ALTER TABLE webhook_urls
+    DROP COLUMN IF EXISTS custom_headers;
+
+ALTER TABLE webhook_urls
+    DROP COLUMN IF EXISTS payload_template;
@@ -0,0 +1,7 @@
+-- reshapes the body for receivers such as Slack or Discord, see types/webhook_templates.rs
+ALTER TABLE webhook_urls
+    ADD COLUMN payload_template JSONB;
+
+-- extra request headers as {"name": "value"}
+ALTER TABLE webhook_urls
+    ADD COLUMN custom_headers JSONB;
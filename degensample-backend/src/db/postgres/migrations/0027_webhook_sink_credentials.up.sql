@@ -0,0 +1,3 @@
+-- login for nats, redis and amqp webhook urls as {"username": ..., "password": ...}, see util/delivery_sink.rs
+ALTER TABLE webhook_urls
+    ADD COLUMN sink_credentials JSONB;
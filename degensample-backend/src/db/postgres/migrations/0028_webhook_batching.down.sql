@@ -0,0 +1,3 @@
+ALTER TABLE webhook_urls
+    DROP COLUMN IF EXISTS batch_max_wait_secs,
+    DROP COLUMN IF EXISTS batch_max_size;
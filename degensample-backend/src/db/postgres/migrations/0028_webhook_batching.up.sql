@@ -0,0 +1,4 @@
+-- opt-in batched deliveries, see types/webhook_batches.rs. NULL sends events one by one
+ALTER TABLE webhook_urls
+    ADD COLUMN batch_max_size INT,
+    ADD COLUMN batch_max_wait_secs INT;
@@ -0,0 +1 @@
+ALTER TABLE domain_events DROP COLUMN IF EXISTS xact_id;
@@ -0,0 +1,3 @@
+-- the transaction that wrote the event, taken before its id. Lets the account stream tell
+-- a gap in the ids that may still be committed from one that never will be
+ALTER TABLE domain_events ADD COLUMN xact_id XID8;
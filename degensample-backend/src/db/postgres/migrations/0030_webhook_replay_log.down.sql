@@ -0,0 +1,5 @@
+ALTER TABLE webhook_deliveries
+    DROP COLUMN IF EXISTS replay_scope;
+
+ALTER TABLE webhook_deliveries
+    DROP COLUMN IF EXISTS replay_requested_by;
@@ -0,0 +1,6 @@
+-- set on the attempt 0 row logged when an owner asks for a trigger to be delivered again
+ALTER TABLE webhook_deliveries
+    ADD COLUMN replay_requested_by VARCHAR(255);
+
+ALTER TABLE webhook_deliveries
+    ADD COLUMN replay_scope VARCHAR(255);
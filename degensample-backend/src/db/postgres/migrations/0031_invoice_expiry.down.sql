@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_invoices_unpaid_expires_at;
+
+ALTER TABLE invoices
+    DROP COLUMN IF EXISTS expires_at;
@@ -0,0 +1,6 @@
+-- unpaid invoices past this are expired by the invoice lifecycle bot
+ALTER TABLE invoices
+    ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX idx_invoices_unpaid_expires_at ON invoices (expires_at)
+    WHERE status IN ('draft', 'created') AND expires_at IS NOT NULL;
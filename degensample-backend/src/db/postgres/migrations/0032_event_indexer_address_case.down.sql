@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS event_indexers_contract_address_chain_id_key;
+
+ALTER TABLE event_indexers
+    ADD CONSTRAINT event_indexers_contract_address_chain_id_key UNIQUE (contract_address, chain_id);
@@ -0,0 +1,25 @@
+-- indexers were registered both checksummed and lowercase, so the same contract could be
+-- indexed twice. Keep the one furthest along, store addresses lowercase and compare them
+-- case-insensitively.
+DELETE FROM event_indexers duplicate
+USING event_indexers kept
+WHERE LOWER(duplicate.contract_address) = LOWER(kept.contract_address)
+AND duplicate.chain_id = kept.chain_id
+AND duplicate.id <> kept.id
+AND (
+    COALESCE(duplicate.current_indexing_block, duplicate.start_block),
+    -duplicate.id
+) < (
+    COALESCE(kept.current_indexing_block, kept.start_block),
+    -kept.id
+);
+
+UPDATE event_indexers
+SET contract_address = LOWER(contract_address)
+WHERE contract_address <> LOWER(contract_address);
+
+ALTER TABLE event_indexers
+    DROP CONSTRAINT IF EXISTS event_indexers_contract_address_chain_id_key;
+
+CREATE UNIQUE INDEX event_indexers_contract_address_chain_id_key
+    ON event_indexers (LOWER(contract_address), chain_id);
@@ -0,0 +1,9 @@
+-- fails if the same log has since been indexed on two chains
+ALTER TABLE invoice_payments
+    DROP COLUMN IF EXISTS block_timestamp;
+
+ALTER TABLE invoice_payments
+    DROP CONSTRAINT IF EXISTS invoice_payments_chain_id_transaction_hash_log_index_key;
+
+ALTER TABLE invoice_payments
+    ADD CONSTRAINT invoice_payments_transaction_hash_log_index_key UNIQUE (transaction_hash, log_index);
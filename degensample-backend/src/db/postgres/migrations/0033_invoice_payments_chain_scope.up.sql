@@ -0,0 +1,12 @@
+-- the same transaction hash and log index can exist on two chains, so a ledger row is only
+-- unique within its chain
+ALTER TABLE invoice_payments
+    DROP CONSTRAINT IF EXISTS invoice_payments_transaction_hash_log_index_key;
+
+ALTER TABLE invoice_payments
+    ADD CONSTRAINT invoice_payments_chain_id_transaction_hash_log_index_key
+    UNIQUE (chain_id, transaction_hash, log_index);
+
+-- when the block the transfer was mined in was produced
+ALTER TABLE invoice_payments
+    ADD COLUMN block_timestamp TIMESTAMPTZ;
@@ -4,9 +4,11 @@ use chrono::{DateTime, Utc};
 use degen_sql::db::postgres::{models::model::PostgresModelError, postgres_db::Database};
 use ethers::types::Address;
 use rand::Rng;
-use serde::Serialize;
-use tokio_postgres::Row;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::{Row, Transaction};
 
+use crate::db::postgres::models::domain_events_model::{DomainEvent, DomainEventsModel};
+use crate::db::postgres::models::webhook_triggers_model::IntoWebhookEventData;
 use crate::types::domains::eth_address::DomainEthAddress;
 use crate::types::selected_record::SelectedRecord;
 use crate::util::built_from_row::BuiltFromDbRow;
@@ -87,18 +89,77 @@ impl ApiKey {
     }
 }
 
+/// What webhooks are told about an api key being created or deleted.
+/// Carries only the last characters of the key, never the key itself.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKeyEvent {
+    #[serde(skip)]
+    pub deleted: bool,
+    pub owner_wallet_address: DomainEthAddress,
+    pub key_hint: String,
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiKeyEvent {
+    pub fn new(api_key: &ApiKey, deleted: bool) -> Self {
+        let hint_start = api_key.apikey.len().saturating_sub(4);
+
+        Self {
+            deleted,
+            owner_wallet_address: api_key.owner_wallet_address.clone(),
+            key_hint: format!("...{}", api_key.apikey.get(hint_start..).unwrap_or_default()),
+            name: api_key.name.clone(),
+            created_at: api_key.created_at,
+        }
+    }
+}
+
+impl IntoWebhookEventData for ApiKeyEvent {
+    fn get_event_type(&self) -> String {
+        if self.deleted {
+            "api_key_deleted".into()
+        } else {
+            "api_key_created".into()
+        }
+    }
+
+    fn get_event_data(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+async fn publish_api_key_event(
+    api_key: &ApiKey,
+    deleted: bool,
+    psql_db: &Transaction<'_>,
+) -> Result<(), PostgresModelError> {
+    let event = ApiKeyEvent::new(api_key, deleted);
+
+    DomainEventsModel::publish(
+        DomainEvent::new(&event, vec![api_key.owner_wallet_address.clone()]),
+        psql_db,
+    )
+    .await?;
+
+    Ok(())
+}
+
 pub struct ApiKeysModel {}
 
 impl ApiKeysModel {
-    /// Inserts a new `ProviderApiKey` into the database.
+    /// Inserts a new `ProviderApiKey` into the database and publishes `api_key_created`.
     pub async fn insert_one(
         api_key: ApiKey,
         psql_db: &Database,
     ) -> Result<i32, PostgresModelError> {
+        let mut client = psql_db.connect().await?;
+        let transaction = client.transaction().await?;
+
         let insert_query = "INSERT INTO api_keys (owner_wallet_address, apikey, name, scopes)
                             VALUES ($1, $2, $3, $4)
                             RETURNING id;";
-        let result = psql_db
+        let row = transaction
             .query_one(
                 insert_query,
                 &[
@@ -108,12 +169,13 @@ impl ApiKeysModel {
                     &api_key.scopes,
                 ],
             )
-            .await;
+            .await?;
 
-        match result {
-            Ok(row) => Ok(row.get::<_, i32>("id")),
-            Err(e) => Err(e.into()),
-        }
+        publish_api_key_event(&api_key, false, &transaction).await?;
+
+        transaction.commit().await?;
+
+        Ok(row.get::<_, i32>("id"))
     }
 
     /// Retrieves a `ProviderApiKey` by API key.
@@ -186,14 +248,22 @@ impl ApiKeysModel {
             return Ok(false);
         }
 
+        let mut client = psql_db.connect().await?;
+        let transaction = client.transaction().await?;
+
         // Now delete the API key
-        let delete_query = "DELETE FROM api_keys WHERE apikey = $1 ;";
-        let result = psql_db.execute(delete_query, &[&apikey]).await;
+        let delete_query = "DELETE FROM api_keys WHERE apikey = $1 RETURNING *;";
+        let rows = transaction.query(delete_query, &[&apikey]).await?;
 
-        match result {
-            Ok(rows_affected) => Ok(rows_affected > 0),
-            Err(e) => Err(e.into()),
+        let deleted: Vec<ApiKey> = rows.iter().filter_map(ApiKey::from_row).collect();
+
+        for api_key in &deleted {
+            publish_api_key_event(api_key, true, &transaction).await?;
         }
+
+        transaction.commit().await?;
+
+        Ok(!deleted.is_empty())
     }
 }
 
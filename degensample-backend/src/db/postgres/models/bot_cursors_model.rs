@@ -0,0 +1,84 @@
+use crate::db::postgres::executor::DbExecutor;
+use degen_sql::db::postgres::models::model::PostgresModelError;
+
+/// Durable progress of a bot through an id-ordered table such as `events`.
+///
+/// A bot reads its cursor, does its work, then advances the cursor in the same
+/// transaction as the work's writes. The advance is a compare-and-swap on the value
+/// that was read, so a crashed or concurrent run can never apply an event twice.
+pub struct BotCursorsModel {}
+
+impl BotCursorsModel {
+    /// Returns the last processed id, creating the cursor if this bot has never run
+    pub async fn find_or_create(
+        bot_name: &str,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Option<i32>, PostgresModelError> {
+        psql_db
+            .execute(
+                "
+                INSERT INTO bot_cursors (bot_name)
+                VALUES ($1)
+                ON CONFLICT (bot_name) DO NOTHING;
+                ",
+                &[&bot_name],
+            )
+            .await?;
+
+        let row = psql_db
+            .query_one(
+                "SELECT last_event_id FROM bot_cursors WHERE bot_name = $1;",
+                &[&bot_name],
+            )
+            .await?;
+
+        Ok(row.get("last_event_id"))
+    }
+
+    /// Moves the cursor from `expected` to `next_event_id`. Returns false when the cursor
+    /// is no longer at `expected`, in which case the caller should roll back its work.
+    /// Inside a transaction this also locks the cursor row until commit.
+    pub async fn advance(
+        bot_name: &str,
+        expected: Option<i32>,
+        next_event_id: i32,
+        psql_db: &impl DbExecutor,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE bot_cursors
+                SET last_event_id = $2,
+                    updated_at = NOW()
+                WHERE bot_name = $1
+                AND last_event_id IS NOT DISTINCT FROM $3;
+                ",
+                &[&bot_name, &next_event_id, &expected],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Moves the cursor back to the start, for bots that cycle over a table
+    pub async fn rewind(
+        bot_name: &str,
+        expected: Option<i32>,
+        psql_db: &impl DbExecutor,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE bot_cursors
+                SET last_event_id = NULL,
+                    updated_at = NOW()
+                WHERE bot_name = $1
+                AND last_event_id IS NOT DISTINCT FROM $2;
+                ",
+                &[&bot_name, &expected],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+}
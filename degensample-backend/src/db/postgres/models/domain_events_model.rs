@@ -0,0 +1,298 @@
+use crate::db::postgres::executor::DbExecutor;
+use crate::db::postgres::models::webhook_triggers_model::IntoWebhookEventData;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::types::domains::json::DomainJson;
+use crate::types::selected_record::SelectedRecord;
+use crate::util::built_from_row::BuiltFromDbRow;
+use chrono::{DateTime, Utc};
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use serde::Serialize;
+use tokio_postgres::Row;
+
+/*
+
+An outbox of things that happened, for the webhook fan-out to turn into webhook triggers.
+
+A model publishes an event with the same executor as the write it describes, so inside a
+transaction the event exists exactly when the write commits. The webhook trigger bot claims
+unpublished events, creates a trigger for every matching webhook url of the event's owners,
+and marks them published in one transaction. The webserver reads the same events to stream
+them to their owners' dashboards.
+
+CREATE TABLE domain_events (
+    id SERIAL PRIMARY KEY,
+    event_type TEXT NOT NULL,
+    owner_wallet_addresses TEXT[] NOT NULL,
+    payload JSONB NOT NULL,
+    dedupe_key TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    published_at TIMESTAMPTZ,
+    xact_id XID8
+);
+
+*/
+
+#[derive(Serialize, Clone, Debug)]
+pub struct DomainEvent {
+    pub event_type: String,
+    /// Wallets whose webhook urls are sent the event
+    pub owner_wallet_addresses: Vec<DomainEthAddress>,
+    pub payload: DomainJson,
+    /// Identifies the underlying fact, so publishing it twice never notifies a url twice.
+    /// Without one every published event is delivered.
+    pub dedupe_key: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+impl BuiltFromDbRow for DomainEvent {
+    fn from_row(row: &Row) -> Option<Self> {
+        Some(Self {
+            event_type: row.get("event_type"),
+            owner_wallet_addresses: row.get("owner_wallet_addresses"),
+            payload: row.get("payload"),
+            dedupe_key: row.get("dedupe_key"),
+            created_at: row.get("created_at"),
+            published_at: row.get("published_at"),
+        })
+    }
+}
+
+impl DomainEvent {
+    pub fn new<T: IntoWebhookEventData>(
+        event_data: &T,
+        owner_wallet_addresses: Vec<DomainEthAddress>,
+    ) -> Self {
+        Self {
+            event_type: event_data.get_event_type(),
+            owner_wallet_addresses,
+            payload: DomainJson(event_data.get_event_data()),
+            dedupe_key: None,
+            created_at: Utc::now(),
+            published_at: None,
+        }
+    }
+
+    pub fn with_dedupe_key(mut self, dedupe_key: String) -> Self {
+        self.dedupe_key = Some(dedupe_key);
+        self
+    }
+
+    /// Dedupe key of the webhook trigger this event creates for `webhook_id`
+    pub fn trigger_dedupe_key(&self, event_id: i32, webhook_id: i32) -> String {
+        match &self.dedupe_key {
+            Some(dedupe_key) => format!("{}:{}", dedupe_key, webhook_id),
+            None => format!("domain_event:{}:{}", event_id, webhook_id),
+        }
+    }
+}
+
+impl IntoWebhookEventData for DomainEvent {
+    fn get_event_type(&self) -> String {
+        self.event_type.clone()
+    }
+
+    fn get_event_data(&self) -> serde_json::Value {
+        self.payload.0.clone()
+    }
+}
+
+/// The transactions a read could see, from the snapshot of its statement
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XactHorizon {
+    /// Every transaction below it had ended
+    pub xmin: i64,
+    /// Every transaction that had started is below it
+    pub xmax: i64,
+}
+
+impl XactHorizon {
+    fn from_row(row: &Row) -> Self {
+        Self {
+            xmin: row.get("snapshot_xmin"),
+            xmax: row.get("snapshot_xmax"),
+        }
+    }
+}
+
+const XACT_HORIZON_COLUMNS: &str = "
+    pg_snapshot_xmin(pg_current_snapshot())::text::bigint AS snapshot_xmin,
+    pg_snapshot_xmax(pg_current_snapshot())::text::bigint AS snapshot_xmax";
+
+pub struct DomainEventsModel {}
+
+impl DomainEventsModel {
+    /// Adds an event to the outbox. Pass the transaction of the write the event describes.
+    pub async fn publish(
+        event: DomainEvent,
+        psql_db: &impl DbExecutor,
+    ) -> Result<i32, PostgresModelError> {
+        // The transaction id is taken before the id default runs, so a transaction holding
+        // an id always has a transaction id. The stream relies on it to tell when a missing
+        // id can no longer be committed.
+        let row = psql_db
+            .query_one(
+                "
+                WITH xact AS (SELECT pg_current_xact_id() AS xact_id)
+                INSERT INTO domain_events
+                (event_type, owner_wallet_addresses, payload, dedupe_key, created_at, xact_id)
+                SELECT $1, $2, $3, $4, $5, xact.xact_id
+                FROM xact
+                RETURNING id;
+                ",
+                &[
+                    &event.event_type,
+                    &event.owner_wallet_addresses,
+                    &event.payload,
+                    &event.dedupe_key,
+                    &event.created_at,
+                ],
+            )
+            .await?;
+
+        Ok(row.get("id"))
+    }
+
+    /// Oldest unpublished events, locked until the transaction ends.
+    /// Events locked by another fan-out are skipped.
+    pub async fn claim_unpublished(
+        limit: i64,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Vec<SelectedRecord<DomainEvent>>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "
+                SELECT *
+                FROM domain_events
+                WHERE published_at IS NULL
+                ORDER BY id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED;
+                ",
+                &[&limit],
+            )
+            .await?;
+
+        Ok(rows.iter().filter_map(SelectedRecord::from_row).collect())
+    }
+
+    /// Id of the newest event, or 0 before the first one, and the horizon it was read at
+    pub async fn latest_id(
+        psql_db: &impl DbExecutor,
+    ) -> Result<(i32, XactHorizon), PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                &format!(
+                    "SELECT COALESCE(MAX(id), 0) AS id, {} FROM domain_events;",
+                    XACT_HORIZON_COLUMNS
+                ),
+                &[],
+            )
+            .await?;
+
+        Ok((row.get("id"), XactHorizon::from_row(&row)))
+    }
+
+    /// Events of every type after `after_id`, oldest first, and the horizon they were read
+    /// at. None when there are no such events.
+    pub async fn find_after(
+        after_id: i32,
+        limit: i64,
+        psql_db: &impl DbExecutor,
+    ) -> Result<(Vec<SelectedRecord<DomainEvent>>, Option<XactHorizon>), PostgresModelError> {
+        let rows = psql_db
+            .query(
+                &format!(
+                    "
+                    SELECT *, {}
+                    FROM domain_events
+                    WHERE id > $1
+                    ORDER BY id
+                    LIMIT $2;
+                    ",
+                    XACT_HORIZON_COLUMNS
+                ),
+                &[&after_id, &limit],
+            )
+            .await?;
+
+        let horizon = rows.first().map(XactHorizon::from_row);
+        let events = rows.iter().filter_map(SelectedRecord::from_row).collect();
+
+        Ok((events, horizon))
+    }
+
+    /// An owner's events of `event_types` with ids in `after_id..=up_to_id`, oldest first
+    pub async fn find_for_owner_between(
+        owner_wallet_address: &DomainEthAddress,
+        after_id: i32,
+        up_to_id: i32,
+        event_types: &[&str],
+        limit: i64,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Vec<SelectedRecord<DomainEvent>>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "
+                SELECT *
+                FROM domain_events
+                WHERE id > $1
+                AND id <= $2
+                AND $3 = ANY(owner_wallet_addresses)
+                AND event_type = ANY($4)
+                ORDER BY id
+                LIMIT $5;
+                ",
+                &[
+                    &after_id,
+                    &up_to_id,
+                    owner_wallet_address,
+                    &event_types,
+                    &limit,
+                ],
+            )
+            .await?;
+
+        Ok(rows.iter().filter_map(SelectedRecord::from_row).collect())
+    }
+
+    /// Marks the payment_summary events of a transaction in an orphaned block as published
+    /// without fanning them out, so no triggers are created for the orphaned payment. Hashes
+    /// are in the 0x-prefixed hex form of the payload.
+    pub async fn skip_unpublished_for_reorged_payment(
+        transaction_hash: &str,
+        chain_id: i64,
+        orphaned_block_hash: &str,
+        psql_db: &impl DbExecutor,
+    ) -> Result<u64, PostgresModelError> {
+        psql_db
+            .execute(
+                "
+                UPDATE domain_events
+                SET published_at = NOW()
+                WHERE published_at IS NULL
+                AND event_type = 'payment_summary'
+                AND LOWER(payload->>'transaction_hash') = LOWER($1)
+                AND (payload->>'chain_id')::BIGINT = $2
+                AND (payload->>'payment_at_block_hash' IS NULL
+                     OR LOWER(payload->>'payment_at_block_hash') = LOWER($3));
+                ",
+                &[&transaction_hash, &chain_id, &orphaned_block_hash],
+            )
+            .await
+    }
+
+    pub async fn mark_published(
+        id: i32,
+        psql_db: &impl DbExecutor,
+    ) -> Result<(), PostgresModelError> {
+        psql_db
+            .execute(
+                "UPDATE domain_events SET published_at = NOW() WHERE id = $1;",
+                &[&id],
+            )
+            .await?;
+
+        Ok(())
+    }
+}
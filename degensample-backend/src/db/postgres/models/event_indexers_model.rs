@@ -0,0 +1,48 @@
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+use ethers::types::Address;
+
+/// Rows of the `event_indexers` table that vibegraph walks to collect contract events
+pub struct EventIndexersModel {}
+
+impl EventIndexersModel {
+    /// Adds an indexer for a contract unless one already exists for it on that chain.
+    /// An existing indexer keeps its progress and its original start block.
+    pub async fn register(
+        name: &str,
+        contract_name: &str,
+        contract_address: Address,
+        chain_id: u64,
+        start_block: u64,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        // lowercase hex, as vibegraph writes it. Rows from before are matched case-insensitively.
+        let contract_address = format!("{:?}", contract_address);
+        let chain_id = chain_id as i64;
+        let start_block = start_block as i64;
+
+        let rows_affected = psql_db
+            .execute(
+                "
+                INSERT INTO event_indexers (
+                    name,
+                    contract_name,
+                    contract_address,
+                    chain_id,
+                    start_block
+                ) VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (LOWER(contract_address), chain_id) DO NOTHING;
+                ",
+                &[
+                    &name,
+                    &contract_name,
+                    &contract_address,
+                    &chain_id,
+                    &start_block,
+                ],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+}
@@ -357,4 +357,57 @@ impl EventsModel {
             }
         }
     }
+
+    /// Events of one type emitted by a contract within a block range, in id order.
+    /// Paged by `after_id` so long ranges can be walked in batches.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn find_events_of_type_in_block_range(
+        event_type: &str,
+        chain_id: i64,
+        contract_address: Address,
+        from_block: u64,
+        to_block: Option<u64>,
+        after_id: Option<i32>,
+        limit: i64,
+        psql_db: &Database,
+    ) -> Result<Vec<(i32, ContractEvent)>, PostgresModelError> {
+        let contract_address = format!("{:?}", contract_address);
+        let from_block = Decimal::from(from_block);
+        let to_block = to_block.map(Decimal::from);
+        let after_id = after_id.unwrap_or(0);
+
+        let rows = psql_db
+            .query(
+                "
+                SELECT * FROM events
+                WHERE name = $1
+                AND chain_id = $2
+                AND LOWER(contract_address) = $3
+                AND block_number >= $4
+                AND ($5::NUMERIC IS NULL OR block_number <= $5)
+                AND id > $6
+                ORDER BY id ASC
+                LIMIT $7;
+                ",
+                &[
+                    &event_type,
+                    &chain_id,
+                    &contract_address,
+                    &from_block,
+                    &to_block,
+                    &after_id,
+                    &limit,
+                ],
+            )
+            .await?;
+
+        let mut events = Vec::with_capacity(rows.len());
+
+        for row in rows {
+            let id: i32 = row.get("id");
+            events.push((id, ContractEvent::from_row(&row)?));
+        }
+
+        Ok(events)
+    }
 }
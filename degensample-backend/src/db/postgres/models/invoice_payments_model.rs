@@ -0,0 +1,375 @@
+use crate::types::domains::bytes32::DomainBytes32;
+use crate::types::domains::datetime::DomainDatetime;
+use crate::types::domains::decimal::DomainDecimal;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::types::domains::h256::DomainH256;
+use crate::types::domains::uint256::DomainUint256;
+use degen_sql::pagination::PaginationData;
+
+use crate::types::selected_record::SelectedRecord;
+use crate::util::built_from_row::BuiltFromDbRow;
+use crate::util::token_amount::usd_value;
+use rust_decimal::Decimal;
+use log::info;
+use tokio_postgres::Row;
+
+use crate::db::postgres::executor::DbExecutor;
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+
+use super::payments_model::{PaymentStatus, PaymentsModel};
+use super::token_metadata_model::{TokenMetadata, TokenMetadataModel};
+use ethers::types::{Address, H256};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// A single transfer to one recipient of an invoice, indexed from an `InvoicePayment` event.
+/// Split invoices produce one row per payee, keyed by (chain_id, transaction_hash, log_index).
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct InvoicePayment {
+    pub uuid: DomainBytes32,
+    pub chain_id: i64,
+    pub payspec_contract_address: DomainEthAddress,
+    pub payment_token_address: DomainEthAddress,
+    pub from_address: DomainEthAddress,
+    pub to_address: DomainEthAddress,
+    pub amount: DomainUint256,
+    pub transaction_hash: DomainH256,
+    pub log_index: i64,
+    pub payment_at_block: Option<i64>,
+    pub payment_at_block_hash: Option<DomainH256>,
+    pub payment_at_block_timestamp: Option<DomainDatetime>,
+    pub status: PaymentStatus,
+    /// Registry entry for `payment_token_address`, when the token is known
+    #[serde(default)]
+    pub token: Option<TokenMetadata>,
+    /// `amount` in whole token units, e.g. "100.25"
+    #[serde(default)]
+    pub amount_formatted: Option<String>,
+    /// USD price of one whole token, taken from the snapshot on the parent payment
+    #[serde(default)]
+    pub usd_price: Option<DomainDecimal>,
+    #[serde(default)]
+    pub usd_value: Option<DomainDecimal>,
+}
+
+impl InvoicePayment {
+    pub fn apply_token_metadata(&mut self, token: TokenMetadata) {
+        self.amount_formatted = Some(token.format_amount(self.amount.0));
+        self.token = Some(token);
+    }
+
+    /// Values this transfer at the given per-token price. Needs token metadata to know the decimals.
+    pub fn apply_usd_price(&mut self, usd_price: Decimal) {
+        let Some(token) = &self.token else {
+            return;
+        };
+
+        self.usd_value =
+            usd_value(self.amount.0, token.decimals.max(0) as u32, usd_price).map(DomainDecimal);
+        self.usd_price = Some(DomainDecimal(usd_price));
+    }
+}
+
+impl BuiltFromDbRow for InvoicePayment {
+    fn from_row(row: &Row) -> Option<Self> {
+        Some(Self {
+            uuid: row.get("uuid"),
+            chain_id: row.get("chain_id"),
+            payspec_contract_address: row.get("contract_address"),
+            payment_token_address: row.get("token_address"),
+            from_address: row.get("from_address"),
+            to_address: row.get("to_address"),
+            amount: row.get("amount"),
+            transaction_hash: row.get("transaction_hash"),
+            log_index: row.get("log_index"),
+            payment_at_block: row.get("block_number"),
+            payment_at_block_hash: row.try_get("block_hash").ok(),
+            payment_at_block_timestamp: row.try_get("block_timestamp").ok().flatten(),
+            status: row.get::<_, String>("status").into(),
+            token: None,
+            amount_formatted: None,
+            usd_price: None,
+            usd_value: None,
+        })
+    }
+}
+
+pub struct InvoicePaymentsModel {}
+
+impl InvoicePaymentsModel {
+    /// Fills in the token symbol, decimals and whole-unit amount of each payment,
+    /// resolving every distinct token only once.
+    pub async fn attach_token_metadata(
+        payments: &mut [SelectedRecord<InvoicePayment>],
+        psql_db: &Database,
+    ) -> Result<(), PostgresModelError> {
+        let mut resolved: HashMap<(i64, Address), Option<TokenMetadata>> = HashMap::new();
+
+        for payment in payments.iter_mut() {
+            let key = (payment.entry.chain_id, payment.entry.payment_token_address.0);
+
+            let token = match resolved.entry(key) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => {
+                    let token = TokenMetadataModel::resolve(
+                        payment.entry.chain_id,
+                        &payment.entry.payment_token_address,
+                        psql_db,
+                    )
+                    .await?;
+
+                    entry.insert(token).clone()
+                }
+            };
+
+            if let Some(token) = token {
+                payment.entry.apply_token_metadata(token);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Values each payment with the price snapshotted on its parent row in `payments`.
+    /// Token metadata has to be attached first.
+    pub async fn attach_usd_values(
+        payments: &mut [SelectedRecord<InvoicePayment>],
+        psql_db: &Database,
+    ) -> Result<(), PostgresModelError> {
+        let mut resolved: HashMap<(i64, H256), Option<DomainDecimal>> = HashMap::new();
+
+        for payment in payments.iter_mut() {
+            let key = (payment.entry.chain_id, payment.entry.transaction_hash.0);
+
+            let usd_price = match resolved.entry(key) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => {
+                    let usd_price = PaymentsModel::find_usd_price(
+                        &payment.entry.transaction_hash,
+                        payment.entry.chain_id,
+                        psql_db,
+                    )
+                    .await?;
+
+                    entry.insert(usd_price).clone()
+                }
+            };
+
+            if let Some(usd_price) = usd_price {
+                payment.entry.apply_usd_price(usd_price.0);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Inserts a ledger row with the status of its parent payment when that payment is
+    /// already recorded in the same block, `invoice_payment.status` otherwise. Re-indexing
+    /// the same log is a no-op unless it shows up in a different block, in which case the
+    /// row takes that status again.
+    pub async fn insert_or_update_one(
+        invoice_payment: InvoicePayment,
+        psql_db: &impl DbExecutor,
+    ) -> Result<i32, PostgresModelError> {
+        let status = invoice_payment.status.to_string();
+
+        let upsert_result = psql_db
+            .query_one(
+                "
+                INSERT INTO invoice_payments
+                (
+                uuid,
+                chain_id,
+                contract_address,
+                token_address,
+                from_address,
+                to_address,
+                amount,
+                transaction_hash,
+                log_index,
+                block_number,
+                block_hash,
+                block_timestamp,
+                status
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12,
+                    COALESCE(
+                        (SELECT payments.status
+                         FROM payments
+                         WHERE payments.transaction_hash = $8
+                         AND payments.chain_id = $2
+                         AND payments.block_hash = $11),
+                        $13
+                    )
+                )
+                ON CONFLICT (chain_id, transaction_hash, log_index)
+                DO UPDATE SET
+                    block_number = EXCLUDED.block_number,
+                    block_timestamp = EXCLUDED.block_timestamp,
+                    status = CASE
+                        WHEN invoice_payments.block_hash IS DISTINCT FROM EXCLUDED.block_hash
+                        THEN EXCLUDED.status
+                        ELSE invoice_payments.status
+                    END,
+                    block_hash = EXCLUDED.block_hash
+                RETURNING id;
+                ",
+                &[
+                    &invoice_payment.uuid,
+                    &invoice_payment.chain_id,
+                    &invoice_payment.payspec_contract_address,
+                    &invoice_payment.payment_token_address,
+                    &invoice_payment.from_address,
+                    &invoice_payment.to_address,
+                    &invoice_payment.amount,
+                    &invoice_payment.transaction_hash,
+                    &invoice_payment.log_index,
+                    &invoice_payment.payment_at_block,
+                    &invoice_payment.payment_at_block_hash,
+                    &invoice_payment.payment_at_block_timestamp,
+                    &status,
+                ],
+            )
+            .await;
+
+        match upsert_result {
+            Ok(row) => {
+                let id: i32 = row.get(0);
+                info!("Invoice payment upserted with id: {}", id);
+                Ok(id)
+            }
+            Err(e) => {
+                eprintln!("Database error during upsert: Invoice payment {:?}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Moves every ledger row of a transaction to the status of its parent payment
+    pub async fn update_status_for_transaction(
+        transaction_hash: &DomainH256,
+        chain_id: i64,
+        status: &PaymentStatus,
+        psql_db: &impl DbExecutor,
+    ) -> Result<u64, PostgresModelError> {
+        let status = status.to_string();
+
+        psql_db
+            .execute(
+                "
+                UPDATE invoice_payments
+                SET status = $1
+                WHERE transaction_hash = $2 AND chain_id = $3;
+                ",
+                &[&status, transaction_hash, &chain_id],
+            )
+            .await
+    }
+
+    pub async fn find_by_uuid(
+        uuid: &DomainBytes32,
+        psql_db: &Database,
+    ) -> Result<Vec<SelectedRecord<InvoicePayment>>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "
+                SELECT *
+                FROM invoice_payments
+                WHERE uuid = $1
+                ORDER BY log_index ASC;
+                ",
+                &[uuid],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(SelectedRecord::<InvoicePayment>::from_row)
+            .collect())
+    }
+
+    pub async fn count_by_to_address(
+        to_address: &DomainEthAddress,
+        psql_db: &Database,
+    ) -> Result<i64, PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                "SELECT COUNT(*) FROM invoice_payments WHERE to_address = $1 AND status != 'reorged'",
+                &[to_address],
+            )
+            .await?;
+
+        Ok(row.get(0))
+    }
+
+    pub async fn find_by_to_address(
+        to_address: &DomainEthAddress,
+        chain_id: Option<i64>,
+        psql_db: &Database,
+    ) -> Result<Vec<SelectedRecord<InvoicePayment>>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "
+                SELECT *
+                FROM invoice_payments
+                WHERE to_address = $1
+                AND ($2::BIGINT IS NULL OR chain_id = $2)
+                ORDER BY created_at DESC;
+                ",
+                &[to_address, &chain_id],
+            )
+            .await?;
+
+        Ok(rows
+            .iter()
+            .filter_map(SelectedRecord::<InvoicePayment>::from_row)
+            .collect())
+    }
+
+    pub async fn find_by_to_address_paginated(
+        to_address: &DomainEthAddress,
+        chain_id: Option<i64>,
+        pagination: &PaginationData,
+        psql_db: &Database,
+    ) -> Result<(Vec<SelectedRecord<InvoicePayment>>, i64), PostgresModelError> {
+        // First get total count
+        let count_row = psql_db
+            .query_one(
+                "
+                SELECT COUNT(*) as total
+                FROM invoice_payments
+                WHERE to_address = $1
+                AND ($2::BIGINT IS NULL OR chain_id = $2)
+                ",
+                &[to_address, &chain_id],
+            )
+            .await?;
+
+        let total_count: i64 = count_row.get("total");
+
+        // Then get paginated data
+        let rows = psql_db
+            .query(
+                &format!(
+                    "
+                    SELECT *
+                    FROM invoice_payments
+                    WHERE to_address = $1
+                    AND ($2::BIGINT IS NULL OR chain_id = $2)
+                    {}
+                    ",
+                    pagination.build_query_part()
+                ),
+                &[to_address, &chain_id],
+            )
+            .await?;
+
+        let records = rows
+            .iter()
+            .filter_map(SelectedRecord::<InvoicePayment>::from_row)
+            .collect();
+
+        Ok((records, total_count))
+    }
+}
@@ -0,0 +1,274 @@
+use crate::types::domains::bytes32::DomainBytes32;
+use crate::types::domains::datetime::DomainDatetime;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::types::domains::json::DomainJson;
+use crate::types::domains::pay_to_amounts::DomainPayToAmounts;
+use crate::types::domains::pay_to_array::DomainPayToArray;
+use crate::types::selected_record::SelectedRecord;
+use crate::util::built_from_row::BuiltFromDbRow;
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+use degen_sql::pagination::PaginationData;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+use super::invoices_model::{Invoice, InvoiceError};
+
+/// A reusable token / chain / split configuration that invoices are stamped out from
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct InvoiceTemplate {
+    pub template_uuid: DomainBytes32,
+    pub owner_address: DomainEthAddress,
+    pub chain_id: i64,
+    pub token_address: DomainEthAddress,
+    pub pay_to_array: DomainPayToArray,
+    pub pay_to_amounts: DomainPayToAmounts,
+    pub description: Option<String>,
+    pub metadata: Option<DomainJson>,
+    pub created_at: Option<DomainDatetime>,
+}
+
+impl InvoiceTemplate {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        owner_address: DomainEthAddress,
+        chain_id: i64,
+        token_address: DomainEthAddress,
+        pay_to_array: DomainPayToArray,
+        pay_to_amounts: DomainPayToAmounts,
+        description: Option<String>,
+        metadata: Option<DomainJson>,
+    ) -> Result<Self, InvoiceError> {
+        if pay_to_array.0.is_empty() || pay_to_array.0.len() != pay_to_amounts.0.len() {
+            return Err(InvoiceError::MismatchedPayees);
+        }
+
+        Ok(Self {
+            template_uuid: DomainBytes32::random(),
+            owner_address,
+            chain_id,
+            token_address,
+            pay_to_array,
+            pay_to_amounts,
+            description,
+            metadata,
+            created_at: None,
+        })
+    }
+
+    /// Drafts a new invoice from this template with a fresh nonce
+    pub fn build_invoice(
+        &self,
+        payspec_contract_address: DomainEthAddress,
+        created_for_address: Option<DomainEthAddress>,
+    ) -> Result<Invoice, InvoiceError> {
+        let mut invoice = Invoice::new(
+            self.owner_address.clone(),
+            self.chain_id,
+            payspec_contract_address,
+            self.token_address.clone(),
+            self.pay_to_array.clone(),
+            self.pay_to_amounts.clone(),
+            Invoice::random_nonce(),
+            self.metadata.clone(),
+        )?;
+
+        invoice.template_uuid = Some(self.template_uuid.clone());
+        invoice.created_for_address = created_for_address;
+
+        Ok(invoice)
+    }
+}
+
+impl BuiltFromDbRow for InvoiceTemplate {
+    fn from_row(row: &Row) -> Option<Self> {
+        Some(Self {
+            template_uuid: row.get("template_uuid"),
+            owner_address: row.get("owner_address"),
+            chain_id: row.get("chain_id"),
+            token_address: row.get("token_address"),
+            pay_to_array: row.get("pay_to_array"),
+            pay_to_amounts: row.get("pay_to_amounts"),
+            description: row.get("description"),
+            metadata: row.get("metadata"),
+            created_at: row.try_get("created_at").ok(),
+        })
+    }
+}
+
+pub struct InvoiceTemplatesModel {}
+
+impl InvoiceTemplatesModel {
+    pub async fn insert_one(
+        template: &InvoiceTemplate,
+        psql_db: &Database,
+    ) -> Result<i32, PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                "
+                INSERT INTO invoice_templates
+                (
+                template_uuid,
+                owner_address,
+                chain_id,
+                token_address,
+                pay_to_array,
+                pay_to_amounts,
+                description,
+                metadata
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                RETURNING id;
+                ",
+                &[
+                    &template.template_uuid,
+                    &template.owner_address,
+                    &template.chain_id,
+                    &template.token_address,
+                    &template.pay_to_array,
+                    &template.pay_to_amounts,
+                    &template.description,
+                    &template.metadata,
+                ],
+            )
+            .await?;
+
+        Ok(row.get("id"))
+    }
+
+    pub async fn find_by_uuid(
+        template_uuid: &DomainBytes32,
+        psql_db: &Database,
+    ) -> Result<Option<SelectedRecord<InvoiceTemplate>>, PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                "SELECT * FROM invoice_templates WHERE template_uuid = $1;",
+                &[template_uuid],
+            )
+            .await;
+
+        match row {
+            Ok(row) => Ok(SelectedRecord::<InvoiceTemplate>::from_row(&row)),
+            Err(e) if e.to_string().contains("no rows") => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn find_by_owner_address_paginated(
+        owner_address: &DomainEthAddress,
+        pagination: &PaginationData,
+        psql_db: &Database,
+    ) -> Result<(Vec<SelectedRecord<InvoiceTemplate>>, i64), PostgresModelError> {
+        // First get total count
+        let count_row = psql_db
+            .query_one(
+                "SELECT COUNT(*) as total FROM invoice_templates WHERE owner_address = $1",
+                &[owner_address],
+            )
+            .await?;
+
+        let total_count: i64 = count_row.get("total");
+
+        // Then get paginated data
+        let rows = psql_db
+            .query(
+                &format!(
+                    "
+                    SELECT *
+                    FROM invoice_templates
+                    WHERE owner_address = $1
+                    {}
+                    ",
+                    pagination.build_query_part()
+                ),
+                &[owner_address],
+            )
+            .await?;
+
+        let records = rows
+            .iter()
+            .filter_map(SelectedRecord::<InvoiceTemplate>::from_row)
+            .collect();
+
+        Ok((records, total_count))
+    }
+
+    /// Overwrites the editable fields of a template. Only the owner's templates are touched.
+    pub async fn update_one(
+        template: &InvoiceTemplate,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE invoice_templates
+                SET chain_id = $1,
+                    token_address = $2,
+                    pay_to_array = $3,
+                    pay_to_amounts = $4,
+                    description = $5,
+                    metadata = $6,
+                    updated_at = NOW()
+                WHERE template_uuid = $7 AND owner_address = $8;
+                ",
+                &[
+                    &template.chain_id,
+                    &template.token_address,
+                    &template.pay_to_array,
+                    &template.pay_to_amounts,
+                    &template.description,
+                    &template.metadata,
+                    &template.template_uuid,
+                    &template.owner_address,
+                ],
+            )
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    pub async fn delete_by_uuid(
+        template_uuid: &DomainBytes32,
+        owner_address: &DomainEthAddress,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "DELETE FROM invoice_templates WHERE template_uuid = $1 AND owner_address = $2;",
+                &[template_uuid, owner_address],
+            )
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::{Address, U256};
+
+    #[test]
+    fn test_build_invoice_uses_fresh_nonce() {
+        let template = InvoiceTemplate::new(
+            DomainEthAddress(Address::zero()),
+            8453,
+            DomainEthAddress(Address::zero()),
+            DomainPayToArray(vec![Address::repeat_byte(1)]),
+            DomainPayToAmounts(vec![U256::from(100)]),
+            None,
+            None,
+        )
+        .unwrap();
+
+        let contract = DomainEthAddress(Address::repeat_byte(2));
+
+        let first = template.build_invoice(contract.clone(), None).unwrap();
+        let second = template.build_invoice(contract, None).unwrap();
+
+        assert_ne!(first.uuid, second.uuid);
+        assert_eq!(first.template_uuid, Some(template.template_uuid.clone()));
+        assert_eq!(first.total_amount.0, U256::from(100));
+    }
+}
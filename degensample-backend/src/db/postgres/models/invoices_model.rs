@@ -0,0 +1,533 @@
+use crate::types::domains::bytes32::DomainBytes32;
+use crate::types::domains::datetime::DomainDatetime;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::types::domains::h256::DomainH256;
+use crate::types::domains::json::DomainJson;
+use crate::types::domains::pay_to_amounts::DomainPayToAmounts;
+use crate::types::domains::pay_to_array::DomainPayToArray;
+use crate::types::domains::uint256::DomainUint256;
+use crate::types::evm_types::{RawTx, RawTxError};
+use crate::types::selected_record::SelectedRecord;
+use crate::util::built_from_row::BuiltFromDbRow;
+use crate::util::payspec::{derive_invoice_uuid, total_amount_due};
+use crate::db::postgres::executor::DbExecutor;
+use crate::db::postgres::models::domain_events_model::{DomainEvent, DomainEventsModel};
+use crate::db::postgres::models::webhook_triggers_model::IntoWebhookEventData;
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+use degen_sql::pagination::PaginationData;
+use ethers::types::U256;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+/// Lifecycle of an invoice: drafted off-chain, then created and paid on-chain.
+/// An unpaid invoice past its `expires_at` is expired, and a later payment is recorded
+/// on it without making it paid.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceStatus {
+    #[default]
+    Draft,
+    Created,
+    Paid,
+    Expired,
+}
+
+impl std::fmt::Display for InvoiceStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            InvoiceStatus::Draft => "draft",
+            InvoiceStatus::Created => "created",
+            InvoiceStatus::Paid => "paid",
+            InvoiceStatus::Expired => "expired",
+        };
+
+        write!(f, "{}", status)
+    }
+}
+
+impl From<String> for InvoiceStatus {
+    fn from(s: String) -> Self {
+        match s.to_lowercase().as_str() {
+            "created" => InvoiceStatus::Created,
+            "paid" => InvoiceStatus::Paid,
+            "expired" => InvoiceStatus::Expired,
+            _ => InvoiceStatus::Draft,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvoiceError {
+    #[error("pay_to_array and pay_to_amounts must be non-empty and the same length")]
+    MismatchedPayees,
+
+    #[error("total amount due overflows uint256")]
+    AmountOverflow,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct Invoice {
+    pub uuid: DomainBytes32,
+    pub owner_address: DomainEthAddress,
+    pub chain_id: i64,
+    pub contract_address: DomainEthAddress,
+    pub token_address: DomainEthAddress,
+    pub pay_to_array: DomainPayToArray,
+    pub pay_to_amounts: DomainPayToAmounts,
+    pub nonce: DomainUint256,
+    pub total_amount: DomainUint256,
+    pub metadata: Option<DomainJson>,
+    pub status: InvoiceStatus,
+    pub created_tx_hash: Option<DomainH256>,
+    pub paid_tx_hash: Option<DomainH256>,
+    pub paid_by: Option<DomainEthAddress>,
+    pub template_uuid: Option<DomainBytes32>,
+    pub created_for_address: Option<DomainEthAddress>,
+    /// Unpaid by then, the invoice is expired and `invoice_expired` is published
+    pub expires_at: Option<DomainDatetime>,
+    pub created_at: Option<DomainDatetime>,
+}
+
+impl Invoice {
+    /// Drafts an invoice whose uuid matches what `Payspec.getInvoiceUUID` returns on-chain
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        owner_address: DomainEthAddress,
+        chain_id: i64,
+        contract_address: DomainEthAddress,
+        token_address: DomainEthAddress,
+        pay_to_array: DomainPayToArray,
+        pay_to_amounts: DomainPayToAmounts,
+        nonce: DomainUint256,
+        metadata: Option<DomainJson>,
+    ) -> Result<Self, InvoiceError> {
+        if pay_to_array.0.is_empty() || pay_to_array.0.len() != pay_to_amounts.0.len() {
+            return Err(InvoiceError::MismatchedPayees);
+        }
+
+        let total_amount =
+            total_amount_due(&pay_to_amounts.0).ok_or(InvoiceError::AmountOverflow)?;
+
+        let uuid = derive_invoice_uuid(
+            contract_address.0,
+            nonce.0,
+            token_address.0,
+            total_amount,
+            &pay_to_array.0,
+            &pay_to_amounts.0,
+        );
+
+        Ok(Self {
+            uuid: DomainBytes32(uuid),
+            owner_address,
+            chain_id,
+            contract_address,
+            token_address,
+            pay_to_array,
+            pay_to_amounts,
+            nonce,
+            total_amount: DomainUint256(total_amount),
+            metadata,
+            status: InvoiceStatus::Draft,
+            created_tx_hash: None,
+            paid_tx_hash: None,
+            paid_by: None,
+            template_uuid: None,
+            created_for_address: None,
+            expires_at: None,
+            created_at: None,
+        })
+    }
+
+    /// A fresh random nonce so identical payee configurations still get distinct uuids
+    pub fn random_nonce() -> DomainUint256 {
+        DomainUint256(U256::from_big_endian(&DomainBytes32::random().0))
+    }
+
+    /// The `createAndPayInvoice` transaction that settles this invoice
+    pub fn build_raw_tx(&self) -> Result<RawTx, RawTxError> {
+        RawTx::create_and_pay_invoice(
+            self.chain_id,
+            self.contract_address.0,
+            self.nonce.0,
+            self.token_address.0,
+            self.total_amount.0,
+            &self.pay_to_array.0,
+            &self.pay_to_amounts.0,
+            self.uuid.0,
+        )
+    }
+}
+
+impl BuiltFromDbRow for Invoice {
+    fn from_row(row: &Row) -> Option<Self> {
+        Some(Self {
+            uuid: row.get("uuid"),
+            owner_address: row.get("owner_address"),
+            chain_id: row.get("chain_id"),
+            contract_address: row.get("contract_address"),
+            token_address: row.get("token_address"),
+            pay_to_array: row.get("pay_to_array"),
+            pay_to_amounts: row.get("pay_to_amounts"),
+            nonce: row.get("nonce"),
+            total_amount: row.get("total_amount"),
+            metadata: row.get("metadata"),
+            status: row.get::<_, String>("status").into(),
+            created_tx_hash: row.get("created_tx_hash"),
+            paid_tx_hash: row.get("paid_tx_hash"),
+            paid_by: row.get("paid_by"),
+            template_uuid: row.get("template_uuid"),
+            created_for_address: row.get("created_for_address"),
+            expires_at: row.try_get("expires_at").ok().flatten(),
+            created_at: row.try_get("created_at").ok(),
+        })
+    }
+}
+
+/// Sent to the invoice owner's webhooks when a draft is saved
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct InvoiceCreatedEvent {
+    pub invoice: Invoice,
+}
+
+impl IntoWebhookEventData for InvoiceCreatedEvent {
+    fn get_event_type(&self) -> String {
+        "invoice_created".into()
+    }
+
+    fn get_event_data(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+/// Sent to the invoice owner's webhooks when an invoice is still unpaid at `expires_at`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct InvoiceExpiredEvent {
+    pub invoice: Invoice,
+}
+
+impl IntoWebhookEventData for InvoiceExpiredEvent {
+    fn get_event_type(&self) -> String {
+        "invoice_expired".into()
+    }
+
+    fn get_event_data(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
+pub struct InvoicesModel {}
+
+impl InvoicesModel {
+    /// Saves a draft and publishes `invoice_created` in the same transaction
+    pub async fn insert_one(
+        invoice: &Invoice,
+        psql_db: &Database,
+    ) -> Result<i32, PostgresModelError> {
+        let status = invoice.status.to_string();
+
+        let mut client = psql_db.connect().await?;
+        let transaction = client.transaction().await?;
+
+        let row = transaction
+            .query_one(
+                "
+                INSERT INTO invoices
+                (
+                uuid,
+                owner_address,
+                chain_id,
+                contract_address,
+                token_address,
+                pay_to_array,
+                pay_to_amounts,
+                nonce,
+                total_amount,
+                metadata,
+                status,
+                template_uuid,
+                created_for_address,
+                expires_at
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                RETURNING id;
+                ",
+                &[
+                    &invoice.uuid,
+                    &invoice.owner_address,
+                    &invoice.chain_id,
+                    &invoice.contract_address,
+                    &invoice.token_address,
+                    &invoice.pay_to_array,
+                    &invoice.pay_to_amounts,
+                    &invoice.nonce,
+                    &invoice.total_amount,
+                    &invoice.metadata,
+                    &status,
+                    &invoice.template_uuid,
+                    &invoice.created_for_address,
+                    &invoice.expires_at,
+                ],
+            )
+            .await?;
+
+        let event = InvoiceCreatedEvent {
+            invoice: invoice.clone(),
+        };
+
+        DomainEventsModel::publish(
+            DomainEvent::new(&event, vec![invoice.owner_address.clone()])
+                .with_dedupe_key(format!("invoice_created:{}", invoice.uuid.to_hex())),
+            &transaction,
+        )
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(row.get("id"))
+    }
+
+    pub async fn find_by_uuid(
+        uuid: &DomainBytes32,
+        psql_db: &Database,
+    ) -> Result<Option<SelectedRecord<Invoice>>, PostgresModelError> {
+        let row = psql_db
+            .query_one("SELECT * FROM invoices WHERE uuid = $1;", &[uuid])
+            .await;
+
+        match row {
+            Ok(row) => Ok(SelectedRecord::<Invoice>::from_row(&row)),
+            Err(e) if e.to_string().contains("no rows") => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The template an invoice was stamped out of, if any
+    pub async fn find_template_uuid(
+        uuid: &DomainBytes32,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Option<DomainBytes32>, PostgresModelError> {
+        let rows = psql_db
+            .query("SELECT template_uuid FROM invoices WHERE uuid = $1;", &[uuid])
+            .await?;
+
+        Ok(rows
+            .first()
+            .and_then(|row| row.get::<_, Option<DomainBytes32>>("template_uuid")))
+    }
+
+    /// The most recent unpaid invoice stamped out of a template for a given payer
+    pub async fn find_draft_for_template(
+        template_uuid: &DomainBytes32,
+        created_for_address: &DomainEthAddress,
+        psql_db: &Database,
+    ) -> Result<Option<SelectedRecord<Invoice>>, PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                "
+                SELECT * FROM invoices
+                WHERE template_uuid = $1
+                AND created_for_address = $2
+                AND status = 'draft'
+                ORDER BY created_at DESC
+                LIMIT 1;
+                ",
+                &[template_uuid, created_for_address],
+            )
+            .await;
+
+        match row {
+            Ok(row) => Ok(SelectedRecord::<Invoice>::from_row(&row)),
+            Err(e) if e.to_string().contains("no rows") => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn find_by_owner_address_paginated(
+        owner_address: &DomainEthAddress,
+        pagination: &PaginationData,
+        psql_db: &Database,
+    ) -> Result<(Vec<SelectedRecord<Invoice>>, i64), PostgresModelError> {
+        // First get total count
+        let count_row = psql_db
+            .query_one(
+                "SELECT COUNT(*) as total FROM invoices WHERE owner_address = $1",
+                &[owner_address],
+            )
+            .await?;
+
+        let total_count: i64 = count_row.get("total");
+
+        // Then get paginated data
+        let rows = psql_db
+            .query(
+                &format!(
+                    "
+                    SELECT *
+                    FROM invoices
+                    WHERE owner_address = $1
+                    {}
+                    ",
+                    pagination.build_query_part()
+                ),
+                &[owner_address],
+            )
+            .await?;
+
+        let records = rows
+            .iter()
+            .filter_map(SelectedRecord::<Invoice>::from_row)
+            .collect();
+
+        Ok((records, total_count))
+    }
+
+    /// Expires up to `limit` unpaid invoices past their `expires_at`, and publishes
+    /// `invoice_expired` for each in the same transaction. Returns the expired invoices.
+    ///
+    /// `applied_up_to` is the last event the lifecycle bot applied. An invoice with a
+    /// payment, or on a chain with `PaidInvoice` events after it, is left for the bot, as
+    /// it may have been paid in time.
+    pub async fn expire_due(
+        applied_up_to: Option<i32>,
+        limit: i64,
+        psql_db: &Database,
+    ) -> Result<Vec<Invoice>, PostgresModelError> {
+        let expired_status = InvoiceStatus::Expired.to_string();
+        let unpaid_statuses = vec![
+            InvoiceStatus::Draft.to_string(),
+            InvoiceStatus::Created.to_string(),
+        ];
+
+        let mut client = psql_db.connect().await?;
+        let transaction = client.transaction().await?;
+
+        let rows = transaction
+            .query(
+                "
+                UPDATE invoices
+                SET status = $1,
+                    updated_at = NOW()
+                WHERE id IN (
+                    SELECT id FROM invoices
+                    WHERE status = ANY($2)
+                    AND expires_at <= NOW()
+                    AND NOT EXISTS (
+                        SELECT 1 FROM payments
+                        WHERE payments.uuid = invoices.uuid
+                        AND payments.chain_id = invoices.chain_id
+                    )
+                    AND NOT EXISTS (
+                        SELECT 1 FROM events
+                        WHERE events.name = 'PaidInvoice'
+                        AND events.chain_id = invoices.chain_id
+                        AND events.id > COALESCE($4, 0)
+                    )
+                    ORDER BY expires_at
+                    LIMIT $3
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING *;
+                ",
+                &[&expired_status, &unpaid_statuses, &limit, &applied_up_to],
+            )
+            .await?;
+
+        let invoices: Vec<Invoice> = rows.iter().filter_map(Invoice::from_row).collect();
+
+        for invoice in &invoices {
+            let event = InvoiceExpiredEvent {
+                invoice: invoice.clone(),
+            };
+
+            DomainEventsModel::publish(
+                DomainEvent::new(&event, vec![invoice.owner_address.clone()])
+                    .with_dedupe_key(format!("invoice_expired:{}", invoice.uuid.to_hex())),
+                &transaction,
+            )
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(invoices)
+    }
+
+    /// Matches a `CreatedInvoice` event to its draft. Never moves a paid invoice backwards.
+    pub async fn mark_created(
+        uuid: &DomainBytes32,
+        chain_id: i64,
+        transaction_hash: &DomainH256,
+        psql_db: &impl DbExecutor,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE invoices
+                SET status = CASE WHEN status = 'draft' THEN 'created' ELSE status END,
+                    created_tx_hash = $1,
+                    updated_at = NOW()
+                WHERE uuid = $2 AND chain_id = $3;
+                ",
+                &[transaction_hash, uuid, &chain_id],
+            )
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Matches a `PaidInvoice` event to its draft, and returns the invoice's status after.
+    /// An expired invoice stays expired, `invoice_expired` has already been sent for it.
+    pub async fn mark_paid(
+        uuid: &DomainBytes32,
+        chain_id: i64,
+        paid_by: &DomainEthAddress,
+        transaction_hash: &DomainH256,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Option<InvoiceStatus>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "
+                UPDATE invoices
+                SET status = CASE WHEN status = 'expired' THEN status ELSE 'paid' END,
+                    paid_by = $1,
+                    paid_tx_hash = $2,
+                    updated_at = NOW()
+                WHERE uuid = $3 AND chain_id = $4
+                RETURNING status;
+                ",
+                &[paid_by, transaction_hash, uuid, &chain_id],
+            )
+            .await?;
+
+        Ok(rows
+            .first()
+            .map(|row| InvoiceStatus::from(row.get::<_, String>("status"))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::types::Address;
+
+    #[test]
+    fn test_new_invoice_rejects_mismatched_payees() {
+        let invoice = Invoice::new(
+            DomainEthAddress(Address::zero()),
+            1,
+            DomainEthAddress(Address::zero()),
+            DomainEthAddress(Address::zero()),
+            DomainPayToArray(vec![Address::zero()]),
+            DomainPayToAmounts(vec![]),
+            DomainUint256(U256::one()),
+            None,
+        );
+
+        assert!(matches!(invoice, Err(InvoiceError::MismatchedPayees)));
+    }
+}
@@ -1,5 +1,7 @@
 pub mod auth_challenges_model;
 pub mod auth_sessions_model;
+pub mod bot_cursors_model;
+pub mod domain_events_model;
   
 
 pub mod api_key_model;
@@ -10,8 +12,14 @@ pub mod users_model;
 pub mod premium_subscription_model;
   
 
+pub mod event_indexers_model;
 pub mod events_model;
+pub mod invoice_payments_model;
+pub mod invoice_templates_model;
+pub mod invoices_model;
 pub mod payments_model;
+pub mod token_metadata_model;
+pub mod webhook_deliveries_model;
 pub mod webhook_triggers_model;
 pub mod webhook_urls_model;
 
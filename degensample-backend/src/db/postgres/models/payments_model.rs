@@ -1,24 +1,88 @@
 use crate::types::domains::bytes32::DomainBytes32;
 use crate::types::domains::datetime::DomainDatetime;
+use crate::types::domains::decimal::DomainDecimal;
 use crate::types::domains::eth_address::DomainEthAddress;
 use crate::types::domains::h256::DomainH256;
 use crate::types::domains::pay_to_amounts::DomainPayToAmounts;
 use crate::types::domains::pay_to_array::DomainPayToArray;
 use crate::types::domains::uint256::DomainUint256;
 use degen_sql::pagination::PaginationData;
+use ethers::types::U256;
 
 use crate::types::selected_record::SelectedRecord;
 use crate::util::built_from_row::BuiltFromDbRow;
+use crate::util::token_amount::usd_value;
 use crate::util::unix_day_index::UnixDayIndex;
+use rust_decimal::Decimal;
 use log::info;
 use serde;
 use tokio_postgres::Row;
 
+use crate::db::postgres::executor::DbExecutor;
 use degen_sql::db::postgres::models::model::PostgresModelError;
 use degen_sql::db::postgres::postgres_db::Database;
 
+use super::domain_events_model::{DomainEvent, DomainEventsModel};
+use super::token_metadata_model::TokenMetadata;
 use super::webhook_triggers_model::IntoWebhookEventData;
 
+/// Lifecycle of a payment as blocks are built on top of it:
+/// detected -> confirmed -> finalized, or reorged if its block leaves the canonical chain.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PaymentStatus {
+    #[default]
+    Detected,
+    Confirmed,
+    Finalized,
+    Reorged,
+}
+
+impl std::fmt::Display for PaymentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self {
+            PaymentStatus::Detected => "detected",
+            PaymentStatus::Confirmed => "confirmed",
+            PaymentStatus::Finalized => "finalized",
+            PaymentStatus::Reorged => "reorged",
+        };
+
+        write!(f, "{}", status)
+    }
+}
+
+impl From<String> for PaymentStatus {
+    fn from(s: String) -> Self {
+        match s.to_lowercase().as_str() {
+            "confirmed" => PaymentStatus::Confirmed,
+            "finalized" => PaymentStatus::Finalized,
+            "reorged" => PaymentStatus::Reorged,
+            // legacy rows were written as 'paid' before confirmations were tracked
+            _ => PaymentStatus::Detected,
+        }
+    }
+}
+
+impl PaymentStatus {
+    /// Statuses that still need to be checked against the canonical chain.
+    /// 'paid' is included for rows written before confirmations were tracked.
+    pub const UNFINALIZED: [&'static str; 3] = ["detected", "confirmed", "paid"];
+
+    pub fn from_confirmations(
+        confirmations: u64,
+        confirmation_depth: u64,
+        finality_depth: u64,
+    ) -> Self {
+        if confirmations >= finality_depth {
+            PaymentStatus::Finalized
+        } else if confirmations >= confirmation_depth {
+            PaymentStatus::Confirmed
+        } else {
+            PaymentStatus::Detected
+        }
+    }
+}
+
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct PaymentSummary {
     pub uuid: DomainBytes32,
@@ -27,18 +91,43 @@ pub struct PaymentSummary {
     pub payment_token_address: DomainEthAddress,
     pub from_address: DomainEthAddress,
     pub nonce: DomainUint256,
-    //  #[serde(rename = "total_amount")]
-    //pub totalAmount: DomainUint256,
+    pub total_amount: DomainUint256,
     pub pay_to_array: DomainPayToArray,
     pub pay_to_amounts: DomainPayToAmounts,
     pub transaction_hash: DomainH256,
     pub payment_at_block: Option<i64>,
+    pub payment_at_block_hash: Option<DomainH256>,
     pub payment_at_block_timestamp: Option<DomainDatetime>,
     pub payment_at_unix_days_index: Option<i64>,
+    pub status: PaymentStatus,
+    pub confirmations: i64,
+    /// Registry entry for `payment_token_address`, when the token is known
+    #[serde(default)]
+    pub token: Option<TokenMetadata>,
+    /// `total_amount` in whole token units, e.g. "150.5"
+    #[serde(default)]
+    pub total_amount_formatted: Option<String>,
+    #[serde(default)]
+    pub pay_to_amounts_formatted: Option<Vec<String>>,
+    /// USD price of one whole token at `payment_at_block_timestamp`, snapshotted on insert
+    #[serde(default)]
+    pub usd_price: Option<DomainDecimal>,
+    /// `total_amount` valued at `usd_price`
+    #[serde(default)]
+    pub usd_value: Option<DomainDecimal>,
 }
 
 impl BuiltFromDbRow for PaymentSummary {
     fn from_row(row: &Row) -> Option<Self> {
+        let pay_to_amounts: DomainPayToAmounts = row.get("pay_to_amounts");
+
+        // rows written before total_amount was stored fall back to the sum of the splits
+        let total_amount = row
+            .try_get::<_, Option<DomainUint256>>("total_amount")
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| DomainUint256(pay_to_amounts.0.iter().fold(U256::zero(), |acc, a| acc + a)));
+
         Some(Self {
             uuid: row.get("uuid"),
             chain_id: row.get("chain_id"),
@@ -46,21 +135,113 @@ impl BuiltFromDbRow for PaymentSummary {
             payment_token_address: row.get("token_address"),
             from_address: row.get("from_address"),
             nonce: row.get("nonce"),
-            // These fields may not be directly in the DB schema
-            // totalAmount: DomainUint256::default(),
+            total_amount,
             pay_to_array: row.get("pay_to_array"),
-            pay_to_amounts: row.get("pay_to_amounts"),
+            pay_to_amounts,
             transaction_hash: row.get("transaction_hash"),
             payment_at_block: row.get("block_number"),
+            payment_at_block_hash: row.try_get("block_hash").ok(),
             payment_at_block_timestamp: row.try_get("created_at").ok(),
             payment_at_unix_days_index: None,
+            status: row.get::<_, String>("status").into(),
+            confirmations: row.try_get("confirmations").unwrap_or_default(),
+            token: None,
+            total_amount_formatted: None,
+            pay_to_amounts_formatted: None,
+            usd_price: row.try_get("usd_price").ok().flatten(),
+            usd_value: row.try_get("usd_value").ok().flatten(),
         })
     }
 }
 
 impl PaymentSummary {
+    /// Attaches the token's symbol and decimals along with the amounts in whole units
+    pub fn apply_token_metadata(&mut self, token: TokenMetadata) {
+        self.total_amount_formatted = Some(token.format_amount(self.total_amount.0));
+        self.pay_to_amounts_formatted = Some(
+            self.pay_to_amounts
+                .0
+                .iter()
+                .map(|amount| token.format_amount(*amount))
+                .collect(),
+        );
+        self.token = Some(token);
+    }
+
+    /// Fields that differ from a previously stored version of the same payment,
+    /// as (field, stored, replayed). Status and confirmation progress are not compared.
+    pub fn diff(&self, stored: &PaymentSummary) -> Vec<(&'static str, String, String)> {
+        let mut changes = Vec::new();
+
+        let mut compare = |field: &'static str, stored: String, replayed: String| {
+            if stored != replayed {
+                changes.push((field, stored, replayed));
+            }
+        };
+
+        compare("uuid", stored.uuid.to_hex(), self.uuid.to_hex());
+        compare(
+            "contract_address",
+            format!("{:?}", stored.payspec_contract_address.0),
+            format!("{:?}", self.payspec_contract_address.0),
+        );
+        compare(
+            "token_address",
+            format!("{:?}", stored.payment_token_address.0),
+            format!("{:?}", self.payment_token_address.0),
+        );
+        compare(
+            "from_address",
+            format!("{:?}", stored.from_address.0),
+            format!("{:?}", self.from_address.0),
+        );
+        compare(
+            "nonce",
+            stored.nonce.0.to_string(),
+            self.nonce.0.to_string(),
+        );
+        compare(
+            "total_amount",
+            stored.total_amount.0.to_string(),
+            self.total_amount.0.to_string(),
+        );
+        compare(
+            "pay_to_array",
+            format!("{:?}", stored.pay_to_array.0),
+            format!("{:?}", self.pay_to_array.0),
+        );
+        compare(
+            "pay_to_amounts",
+            format!("{:?}", stored.pay_to_amounts.0),
+            format!("{:?}", self.pay_to_amounts.0),
+        );
+        compare(
+            "block_number",
+            format!("{:?}", stored.payment_at_block),
+            format!("{:?}", self.payment_at_block),
+        );
+        compare(
+            "block_hash",
+            format!("{:?}", stored.payment_at_block_hash.as_ref().map(|h| h.0)),
+            format!("{:?}", self.payment_at_block_hash.as_ref().map(|h| h.0)),
+        );
+
+        changes
+    }
+
+    /// Values the payment at the given per-token price. Needs token metadata to know the decimals.
+    pub fn apply_usd_price(&mut self, usd_price: Decimal) {
+        let Some(token) = &self.token else {
+            return;
+        };
+
+        self.usd_value = usd_value(self.total_amount.0, token.decimals.max(0) as u32, usd_price)
+            .map(DomainDecimal);
+        self.usd_price = Some(DomainDecimal(usd_price));
+    }
+
     pub fn generate_test_payment_summary() -> Self {
-        use ethers::types::{H160, H256, U256};
+        use ethers::types::{H160, H256};
         use std::str::FromStr;
 
         // Helper function to create an Ethereum address from a string
@@ -104,6 +285,7 @@ impl PaymentSummary {
             )), // USDC on mainnet
             from_address: DomainEthAddress(eth_addr("0x8765432109876543210987654321098765432109")),
             nonce: DomainUint256(U256::from(42u64)),
+            total_amount: DomainUint256(U256::from(150000000u64)),
             pay_to_array: DomainPayToArray(vec![
                 eth_addr("0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"),
                 eth_addr("0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"),
@@ -116,8 +298,18 @@ impl PaymentSummary {
                 "0xcccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc",
             )),
             payment_at_block: Some(15000000),
+            payment_at_block_hash: Some(DomainH256(tx_hash(
+                "0xdddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddddd",
+            ))),
             payment_at_block_timestamp: Some(DomainDatetime(chrono::Utc::now())),
             payment_at_unix_days_index: Some(UnixDayIndex::from_timestamp(chrono::Utc::now())),
+            status: PaymentStatus::Confirmed,
+            confirmations: 12,
+            token: None,
+            total_amount_formatted: None,
+            pay_to_amounts_formatted: None,
+            usd_price: None,
+            usd_value: None,
         }
     }
 }
@@ -133,6 +325,24 @@ impl IntoWebhookEventData for PaymentSummary {
     }
 }
 
+/// What `insert_or_update_one` did with a payment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PaymentUpsert {
+    pub id: i32,
+    /// The payment was not recorded before
+    pub inserted: bool,
+    /// The payment was recorded in another block: it was reorged out and mined again
+    pub moved_block: bool,
+}
+
+impl PaymentUpsert {
+    /// Recipients hear about a payment when it is first recorded, and again in its new
+    /// block after a reorg
+    pub fn needs_event(&self) -> bool {
+        self.inserted || self.moved_block
+    }
+}
+
 pub struct PaymentsModel {}
 
 impl PaymentsModel {
@@ -144,7 +354,7 @@ impl PaymentsModel {
         // let nonce_decimal = Decimal::from_str(&loan_summary.nonce.to_string()).unwrap();
         //  let block_number_decimal = Decimal::from_str(&loan_summary. block_number.to_string()).unwrap();
 
-        let status = "paid".to_string();
+        let status = loan_summary.status.to_string();
 
         let insert_result = psql_db
             .query_one(
@@ -158,11 +368,15 @@ impl PaymentsModel {
                 uuid,
                 nonce,
                 block_number,
+                block_hash,
                 status,
                 transaction_hash,
-                chain_id
+                chain_id,
+                total_amount,
+                usd_price,
+                usd_value
                 ) 
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
                 RETURNING id;
                 ",
                 &[
@@ -173,9 +387,13 @@ impl PaymentsModel {
                     &loan_summary.uuid,
                     &loan_summary.nonce,
                     &loan_summary.payment_at_block,
+                    &loan_summary.payment_at_block_hash,
                     &status,
                     &loan_summary.transaction_hash,
                     &loan_summary.chain_id,
+                    &loan_summary.total_amount,
+                    &loan_summary.usd_price,
+                    &loan_summary.usd_value,
                 ],
             )
             .await;
@@ -189,16 +407,23 @@ impl PaymentsModel {
         }
     }
 
+    /// Records a payment, or updates the one already recorded for its transaction
     pub async fn insert_or_update_one(
         loan_summary: PaymentSummary,
-        psql_db: &Database,
-    ) -> Result<i32, PostgresModelError> {
-        let status = "paid".to_string();
+        psql_db: &impl DbExecutor,
+    ) -> Result<PaymentUpsert, PostgresModelError> {
+        let status = loan_summary.status.to_string();
 
-        // Using ON CONFLICT to handle the unique constraint (transaction_hash, chain_id)
+        // Using ON CONFLICT to handle the unique constraint (transaction_hash, chain_id).
+        // A payment keeps its confirmation progress unless it shows up in a different block.
+        // `previous` is read from the snapshot before the upsert.
         let upsert_result = psql_db
             .query_one(
                 "
+                WITH previous AS (
+                    SELECT block_hash FROM payments
+                    WHERE transaction_hash = $11 AND chain_id = $12
+                )
                 INSERT INTO payments 
                 (
                 contract_address,
@@ -209,23 +434,47 @@ impl PaymentsModel {
                 uuid,
                 nonce,
                 block_number,
+                block_hash,
                 status,
                 transaction_hash,
-                chain_id
+                chain_id,
+                total_amount,
+                usd_price,
+                usd_value
                 ) 
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
                 ON CONFLICT (transaction_hash, chain_id) 
                 DO UPDATE SET 
                     contract_address = EXCLUDED.contract_address,
                     token_address = EXCLUDED.token_address,
                     pay_to_array = EXCLUDED.pay_to_array,
                     pay_to_amounts = EXCLUDED.pay_to_amounts,
+                    total_amount = EXCLUDED.total_amount,
+                    usd_price = COALESCE(payments.usd_price, EXCLUDED.usd_price),
+                    usd_value = COALESCE(payments.usd_value, EXCLUDED.usd_value),
                     uuid = EXCLUDED.uuid,
                     nonce = EXCLUDED.nonce,
                     block_number = EXCLUDED.block_number,
                     from_address = EXCLUDED.from_address,
-                    status = EXCLUDED.status
-                RETURNING id;
+                    status = CASE
+                        WHEN payments.block_hash IS DISTINCT FROM EXCLUDED.block_hash
+                        THEN EXCLUDED.status
+                        ELSE payments.status
+                    END,
+                    confirmations = CASE
+                        WHEN payments.block_hash IS DISTINCT FROM EXCLUDED.block_hash
+                        THEN 0
+                        ELSE payments.confirmations
+                    END,
+                    block_hash = EXCLUDED.block_hash
+                RETURNING id,
+                    (xmax = 0) AS inserted,
+                    COALESCE(
+                        (SELECT previous.block_hash IS DISTINCT FROM payments.block_hash
+                         FROM previous
+                         WHERE previous.block_hash IS NOT NULL),
+                        FALSE
+                    ) AS moved_block;
                 ",
                 &[
                     &loan_summary.payspec_contract_address,
@@ -236,22 +485,27 @@ impl PaymentsModel {
                     &loan_summary.uuid,
                     &loan_summary.nonce,
                     &loan_summary.payment_at_block,
+                    &loan_summary.payment_at_block_hash,
                     &status,
                     &loan_summary.transaction_hash,
                     &loan_summary.chain_id,
+                    &loan_summary.total_amount,
+                    &loan_summary.usd_price,
+                    &loan_summary.usd_value,
                 ],
             )
             .await;
 
         match upsert_result {
             Ok(row) => {
-                let id: i32 = row.get(0);
-                info!("Payment upserted with id: {}", id);
-
-                // Check for webhooks and create triggers
-                // Self::create_webhook_triggers_for_payment(&loan_summary, psql_db).await?;
-
-                Ok(id)
+                let upsert = PaymentUpsert {
+                    id: row.get("id"),
+                    inserted: row.get("inserted"),
+                    moved_block: row.get("moved_block"),
+                };
+                info!("Payment upserted with id: {}", upsert.id);
+
+                Ok(upsert)
             }
             Err(e) => {
                 eprintln!("Database error during upsert: Payment {:?}", e);
@@ -260,29 +514,40 @@ impl PaymentsModel {
         }
     }
 
-    /// Creates webhook triggers for all recipients of a payment
-    /*  pub async fn create_webhook_triggers_for_payment(
-            payment: &PaymentSummary,
-            psql_db: &Database,
-        ) -> Result<(), PostgresModelError> {
-            // Get all recipient addresses from the payment
-            let recipients = payment.pay_to_array.0.clone();
-
-            for recipient in recipients {
-                // Check if the recipient has a webhook URL configured
-                let webhooks = WebhookUrlsModel::find_by_owner_address(&recipient.into(), psql_db).await?;
-
-                // Create a webhook trigger for each webhook URL
-                for webhook in webhooks {
-                    let trigger = WebhookTrigger::new(webhook.id.into());
-                    let _ = WebhookTriggersModel::insert_one(trigger, psql_db).await?;
-                    info!("Created webhook trigger for payment to {}", recipient);
-                }
-            }
-
-            Ok(())
-        }
-    */
+    /// Publishes a payment_summary event for the recipients of the payment, which the webhook
+    /// fan-out delivers to their webhook urls. The event is keyed on the payment and its block,
+    /// so publishing the same payment again never notifies a webhook url twice, but a payment
+    /// mined again after a reorg is notified in its new block.
+    pub async fn publish_payment_event(
+        payment: &PaymentSummary,
+        psql_db: &impl DbExecutor,
+    ) -> Result<i32, PostgresModelError> {
+        let mut recipients: Vec<DomainEthAddress> = payment
+            .pay_to_array
+            .0
+            .iter()
+            .map(|recipient| DomainEthAddress(*recipient))
+            .collect();
+        recipients.sort_by_key(|recipient| recipient.0);
+        recipients.dedup();
+
+        let dedupe_key = format!(
+            "payment_summary:{}:{:x}:{}",
+            payment.chain_id,
+            payment.transaction_hash.0,
+            payment
+                .payment_at_block_hash
+                .as_ref()
+                .map(|block_hash| format!("{:x}", block_hash.0))
+                .unwrap_or_default()
+        );
+
+        DomainEventsModel::publish(
+            DomainEvent::new(payment, recipients).with_dedupe_key(dedupe_key),
+            psql_db,
+        )
+        .await
+    }
 
     pub async fn find_by_transaction_hash(
         transaction_hash: &str,
@@ -316,6 +581,30 @@ impl PaymentsModel {
         }
     }
 
+    /// The USD price snapshotted for a payment transaction, if it was valued
+    pub async fn find_usd_price(
+        transaction_hash: &DomainH256,
+        chain_id: i64,
+        psql_db: &Database,
+    ) -> Result<Option<DomainDecimal>, PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                "
+                SELECT usd_price FROM payments
+                WHERE transaction_hash = $1 AND chain_id = $2
+                LIMIT 1;
+                ",
+                &[transaction_hash, &chain_id],
+            )
+            .await;
+
+        match row {
+            Ok(row) => Ok(row.get("usd_price")),
+            Err(e) if e.to_string().contains("no rows") => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
     pub async fn find_by_uuid(
         uuid: &DomainBytes32,
         psql_db: &Database,
@@ -412,7 +701,8 @@ impl PaymentsModel {
             .execute(
                 "
                 UPDATE payments
-                SET status = $1
+                SET status = $1,
+                    status_updated_at = NOW()
                 WHERE id = $2;
                 ",
                 &[&status, &id],
@@ -428,6 +718,112 @@ impl PaymentsModel {
         }
     }
 
+    /// Payments on `chain_ids` that are in a block but have not reached finality yet,
+    /// oldest first
+    pub async fn find_unfinalized_payments(
+        chain_ids: &[i64],
+        limit: i64,
+        psql_db: &Database,
+    ) -> Result<Vec<SelectedRecord<PaymentSummary>>, PostgresModelError> {
+        let unfinalized_statuses: Vec<String> = PaymentStatus::UNFINALIZED
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        let rows = psql_db
+            .query(
+                "
+                SELECT *
+                FROM payments
+                WHERE status = ANY($1)
+                AND chain_id = ANY($2)
+                AND block_number IS NOT NULL
+                ORDER BY id ASC
+                LIMIT $3;
+                ",
+                &[&unfinalized_statuses, &chain_ids, &limit],
+            )
+            .await;
+
+        match rows {
+            Ok(rows) => {
+                let records = rows
+                    .iter()
+                    .filter_map(SelectedRecord::<PaymentSummary>::from_row)
+                    .collect();
+                Ok(records)
+            }
+            Err(e) => {
+                eprintln!("Database error: Unfinalized payments {:?}", e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Marks a payment reorged if it is still recorded in the orphaned block.
+    /// Returns false when the payment has since moved to another block.
+    pub async fn mark_reorged(
+        id: i32,
+        orphaned_block_hash: &DomainH256,
+        psql_db: &impl DbExecutor,
+    ) -> Result<bool, PostgresModelError> {
+        let status = PaymentStatus::Reorged.to_string();
+
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE payments
+                SET status = $1,
+                    status_updated_at = NOW()
+                WHERE id = $2
+                AND block_hash = $3;
+                ",
+                &[&status, &id, orphaned_block_hash],
+            )
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    /// Records the confirmation depth of a payment whose block is still canonical.
+    /// Rows written before block hashes were stored adopt the canonical hash.
+    /// Returns false when the payment has since moved to another block.
+    pub async fn update_confirmations(
+        id: i32,
+        status: &PaymentStatus,
+        confirmations: i64,
+        canonical_block_hash: &DomainH256,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        let status = status.to_string();
+
+        let result = psql_db
+            .execute(
+                "
+                UPDATE payments
+                SET status_updated_at = CASE
+                        WHEN status = $1 THEN status_updated_at
+                        ELSE NOW()
+                    END,
+                    status = $1,
+                    confirmations = $2,
+                    block_hash = COALESCE(block_hash, $3)
+                WHERE id = $4
+                AND (block_hash IS NULL OR block_hash = $3);
+                ",
+                &[&status, &confirmations, canonical_block_hash, &id],
+            )
+            .await;
+
+        match result {
+            Ok(rows_affected) => Ok(rows_affected > 0),
+            Err(e) => {
+                eprintln!("Database error: Payment confirmations update {:?}", e);
+                Err(e)
+            }
+        }
+    }
+
     pub async fn update_one_as_paid(
         uuid: &DomainBytes32,
         transaction_hash: &DomainH256,
@@ -740,3 +1136,49 @@ pub struct PaymentSummary {
 
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payment_status_from_confirmations() {
+        assert_eq!(
+            PaymentStatus::from_confirmations(1, 12, 64),
+            PaymentStatus::Detected
+        );
+        assert_eq!(
+            PaymentStatus::from_confirmations(12, 12, 64),
+            PaymentStatus::Confirmed
+        );
+        assert_eq!(
+            PaymentStatus::from_confirmations(64, 12, 64),
+            PaymentStatus::Finalized
+        );
+    }
+
+    #[test]
+    fn test_payment_status_from_legacy_string() {
+        assert_eq!(PaymentStatus::from("paid".to_string()), PaymentStatus::Detected);
+        assert_eq!(PaymentStatus::from("reorged".to_string()), PaymentStatus::Reorged);
+        assert_eq!(PaymentStatus::Finalized.to_string(), "finalized");
+    }
+
+    #[test]
+    fn test_payment_summary_diff() {
+        let stored = PaymentSummary::generate_test_payment_summary();
+
+        let mut replayed = stored.clone();
+        replayed.status = PaymentStatus::Finalized;
+        replayed.confirmations = 100;
+        assert!(replayed.diff(&stored).is_empty());
+
+        replayed.total_amount = DomainUint256(ethers::types::U256::from(1u64));
+        let changes = replayed.diff(&stored);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].0, "total_amount");
+        assert_eq!(changes[0].1, "150000000");
+        assert_eq!(changes[0].2, "1");
+    }
+}
@@ -18,6 +18,8 @@ use degen_sql::sql_builder::{OrderingDirection, SqlBuilder, SqlStatementBase};
 use serde::{Deserialize, Serialize};
 use tokio_postgres::Row;
 
+use crate::db::postgres::models::domain_events_model::{DomainEvent, DomainEventsModel};
+use crate::db::postgres::models::webhook_triggers_model::IntoWebhookEventData;
 use crate::types::domains::bytes32::DomainBytes32;
 use crate::types::domains::bytes8::DomainBytes8;
 use crate::types::domains::decimal::DomainDecimal;
@@ -95,6 +97,23 @@ impl ApiCreditRefill {
     }
 }
 
+/// Sent to the workspace owner's webhooks once a refill is paid and its credits apply
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct CreditRefillAppliedEvent {
+    pub refill: ApiCreditRefill,
+}
+
+impl IntoWebhookEventData for CreditRefillAppliedEvent {
+    fn get_event_type(&self) -> String {
+        "credit_refill_applied".into()
+    }
+
+    fn get_event_data(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+}
+
 pub struct ApiCreditRefillsModel {}
 
 impl ApiCreditRefillsModel {
@@ -402,16 +421,24 @@ pub async fn update_one_as_paid(
     invoice_uuid: &DomainBytes32,
     psql_db: &Database,
 ) -> Result< Option< SelectedRecord<  ApiCreditRefill > >, PostgresModelError> {
+    let mut client = psql_db.connect().await?;
+    let transaction = client.transaction().await?;
+
     // Only update records with a 'pending' status
     let update_query = "
-        UPDATE api_credit_refill 
-        SET status = $1 
-        WHERE invoice_uuid = $2 
-        AND status = $3
-        RETURNING *;";  // Return ID to check if any row was updated
-    
-    let result = psql_db
-        .query_one(  // Use query_opt to handle cases where no rows match
+        WITH paid AS (
+            UPDATE api_credit_refill
+            SET status = $1
+            WHERE invoice_uuid = $2
+            AND status = $3
+            RETURNING *
+        )
+        SELECT paid.*, w.owner_address AS workspace_owner_address
+        FROM paid
+        LEFT JOIN api_workspaces w ON w.workspace_uuid = paid.workspace_uuid;";
+
+    let rows = transaction
+        .query(
             update_query,
             &[
                 &"paid".to_string(),
@@ -419,20 +446,32 @@ pub async fn update_one_as_paid(
                 &"pending".to_string(),
             ],
         )
-        .await;
-    
-    match result {
-        Ok( row ) => {
-            
-            let selected_record = SelectedRecord::from_row(&row ); 
-            Ok(  selected_record   )
-        },
-    
-        Err(e) => {
-            println!("Update refill error: {:?}", e);
-            Err(e.into())
-        },
+        .await?;
+
+    let Some(row) = rows.first() else {
+        return Ok(None);
+    };
+
+    let selected_record = SelectedRecord::<ApiCreditRefill>::from_row(row);
+
+    let workspace_owner: Option<DomainEthAddress> = row.get("workspace_owner_address");
+
+    if let (Some(record), Some(owner)) = (&selected_record, workspace_owner) {
+        let event = CreditRefillAppliedEvent {
+            refill: record.entry.clone(),
+        };
+
+        DomainEventsModel::publish(
+            DomainEvent::new(&event, vec![owner])
+                .with_dedupe_key(format!("credit_refill_applied:{}", record.entry.invoice_uuid)),
+            &transaction,
+        )
+        .await?;
     }
+
+    transaction.commit().await?;
+
+    Ok(selected_record)
 }
 
 
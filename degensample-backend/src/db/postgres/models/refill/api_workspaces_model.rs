@@ -18,6 +18,7 @@ use degen_sql::sql_builder::{OrderingDirection, SqlBuilder, SqlStatementBase};
 use serde::{Deserialize, Serialize};
 use tokio_postgres::Row;
 
+use crate::types::domains::bytes32::DomainBytes32;
 use crate::types::domains::bytes8::DomainBytes8;
 use crate::types::domains::eth_address::DomainEthAddress;
 use crate::types::selected_record::SelectedRecord;
@@ -30,7 +31,7 @@ pub struct ApiWorkspace {
     pub owner_address: DomainEthAddress,
     pub name: String,
     pub description: Option<String>,
-    //  pub invoice_template_uuid: Option<String>,
+    pub invoice_template_uuid: Option<DomainBytes32>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -41,7 +42,7 @@ impl BuiltFromDbRow for ApiWorkspace {
             owner_address: row.get("owner_address"),
             name: row.get("name"),
             description: row.get("description"),
-            //   invoice_template_uuid: row.get("invoice_template_uuid"),
+            invoice_template_uuid: row.try_get("invoice_template_uuid").ok().flatten(),
             created_at: row.get("created_at"),
         })
     }
@@ -74,14 +75,14 @@ impl ApiWorkspace {
         owner_address: DomainEthAddress,
         name: String,
         description: Option<String>,
-        //   invoice_template_uuid: Option<String>,
+        invoice_template_uuid: Option<DomainBytes32>,
     ) -> Self {
         Self {
             workspace_uuid: DomainBytes8::random(),
             owner_address,
             name,
             description,
-            //   invoice_template_uuid,
+            invoice_template_uuid,
             created_at: Utc::now(),
         }
     }
@@ -96,8 +97,8 @@ impl ApiWorkspacesModel {
         psql_db: &Database,
     ) -> Result<i32, PostgresModelError> {
         let insert_query =
-            "INSERT INTO api_workspaces (workspace_uuid, owner_address, name, description )
-                           VALUES ($1, $2, $3, $4 )
+            "INSERT INTO api_workspaces (workspace_uuid, owner_address, name, description, invoice_template_uuid )
+                           VALUES ($1, $2, $3, $4, $5 )
                            RETURNING id;";
 
         let result = psql_db
@@ -108,7 +109,7 @@ impl ApiWorkspacesModel {
                     &workspace.owner_address,
                     &workspace.name,
                     &workspace.description,
-                    //  &workspace.invoice_template_uuid,
+                    &workspace.invoice_template_uuid,
                 ],
             )
             .await;
@@ -135,9 +136,9 @@ impl ApiWorkspacesModel {
     }
 
     /// Updates the invoice template UUID for a workspace
-    /* pub async fn update_invoice_template_uuid(
-        workspace_uuid: &str,
-        invoice_template_uuid: &str,
+    pub async fn update_invoice_template_uuid(
+        workspace_uuid: &DomainBytes8,
+        invoice_template_uuid: Option<&DomainBytes32>,
         psql_db: &Database,
     ) -> Result<bool, PostgresModelError> {
         let update_query = "UPDATE api_workspaces SET invoice_template_uuid = $1 WHERE workspace_uuid = $2;";
@@ -145,9 +146,9 @@ impl ApiWorkspacesModel {
 
         match result {
             Ok(rows_affected) => Ok(rows_affected > 0),
-            Err(e) => Err(e.into()),
+            Err(e) => Err(e),
         }
-    }*/
+    }
 
     /// Finds all workspaces owned by a specific address with optional pagination
     pub async fn find_all_by_owner(
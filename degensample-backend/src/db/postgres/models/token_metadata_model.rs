@@ -0,0 +1,170 @@
+use crate::config::TokenMetadataConfig;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::util::built_from_row::BuiltFromDbRow;
+use crate::util::erc20::fetch_erc20_metadata;
+use crate::util::rpc_provider::RpcProviderPool;
+use crate::util::token_amount::format_units;
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+use ethers::types::U256;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use utoipa::ToSchema;
+
+/// Symbol and decimals of a token on a given chain
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct TokenMetadata {
+    pub chain_id: i64,
+    pub token_address: DomainEthAddress,
+    pub symbol: String,
+    pub name: Option<String>,
+    pub decimals: i32,
+}
+
+impl TokenMetadata {
+    pub fn format_amount(&self, amount: U256) -> String {
+        format_units(amount, self.decimals.max(0) as u32)
+    }
+}
+
+impl BuiltFromDbRow for TokenMetadata {
+    fn from_row(row: &Row) -> Option<Self> {
+        Some(Self {
+            chain_id: row.get("chain_id"),
+            token_address: row.get("token_address"),
+            symbol: row.get("symbol"),
+            name: row.get("name"),
+            decimals: row.get("decimals"),
+        })
+    }
+}
+
+pub struct TokenMetadataModel {}
+
+impl TokenMetadataModel {
+    pub async fn insert_or_update_one(
+        token: &TokenMetadata,
+        psql_db: &Database,
+    ) -> Result<(), PostgresModelError> {
+        psql_db
+            .execute(
+                "
+                INSERT INTO token_metadata
+                (chain_id, token_address, symbol, name, decimals)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (chain_id, token_address)
+                DO UPDATE SET
+                    symbol = EXCLUDED.symbol,
+                    name = EXCLUDED.name,
+                    decimals = EXCLUDED.decimals;
+                ",
+                &[
+                    &token.chain_id,
+                    &token.token_address,
+                    &token.symbol,
+                    &token.name,
+                    &token.decimals,
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn find_one(
+        chain_id: i64,
+        token_address: &DomainEthAddress,
+        psql_db: &Database,
+    ) -> Result<Option<TokenMetadata>, PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                "SELECT * FROM token_metadata WHERE chain_id = $1 AND token_address = $2;",
+                &[&chain_id, token_address],
+            )
+            .await;
+
+        match row {
+            Ok(row) => Ok(TokenMetadata::from_row(&row)),
+            Err(e) if e.to_string().contains("no rows") => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Every known token on a chain: the seeded config merged with whatever was
+    /// discovered on-chain, with database rows taking precedence.
+    pub async fn find_by_chain_id(
+        chain_id: i64,
+        psql_db: &Database,
+    ) -> Result<Vec<TokenMetadata>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "SELECT * FROM token_metadata WHERE chain_id = $1 ORDER BY symbol ASC;",
+                &[&chain_id],
+            )
+            .await?;
+
+        let mut tokens: Vec<TokenMetadata> =
+            rows.iter().filter_map(TokenMetadata::from_row).collect();
+
+        for seeded in TokenMetadataConfig::load().list_by_chain(chain_id) {
+            if !tokens.iter().any(|t| t.token_address == seeded.token_address) {
+                tokens.push(seeded);
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Looks a token up in the database, then in the seeded config
+    pub async fn resolve(
+        chain_id: i64,
+        token_address: &DomainEthAddress,
+        psql_db: &Database,
+    ) -> Result<Option<TokenMetadata>, PostgresModelError> {
+        if let Some(token) = Self::find_one(chain_id, token_address, psql_db).await? {
+            return Ok(Some(token));
+        }
+
+        Ok(TokenMetadataConfig::load().get_token(chain_id, token_address))
+    }
+
+    /// Like `resolve`, but unknown tokens are read from the ERC-20 contract and
+    /// stored so the next lookup is served from the database.
+    pub async fn resolve_or_fetch(
+        chain_id: i64,
+        token_address: &DomainEthAddress,
+        providers: &RpcProviderPool,
+        psql_db: &Database,
+    ) -> Result<Option<TokenMetadata>, PostgresModelError> {
+        if let Some(token) = Self::resolve(chain_id, token_address, psql_db).await? {
+            return Ok(Some(token));
+        }
+
+        let fetched = match providers
+            .call(|provider| fetch_erc20_metadata(token_address.0, provider))
+            .await
+        {
+            Ok(fetched) => fetched,
+            Err(e) => {
+                warn!(
+                    "could not read erc20 metadata for {:?} on chain {}: {:?}",
+                    token_address, chain_id, e
+                );
+                return Ok(None);
+            }
+        };
+
+        let token = TokenMetadata {
+            chain_id,
+            token_address: token_address.clone(),
+            symbol: fetched.symbol,
+            name: fetched.name,
+            decimals: fetched.decimals as i32,
+        };
+
+        Self::insert_or_update_one(&token, psql_db).await?;
+
+        Ok(Some(token))
+    }
+}
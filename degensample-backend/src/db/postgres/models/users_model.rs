@@ -1,6 +1,7 @@
 use degen_sql::db::postgres::{models::model::PostgresModelError, postgres_db::Database};
 use serde::{Deserialize, Serialize};
 
+use super::invoice_payments_model::InvoicePaymentsModel;
 use crate::types::domains::eth_address::DomainEthAddress;
 
 /// A user entity is derived from their Ethereum wallet address
@@ -47,13 +48,11 @@ impl UsersModel {
             .await?;
         let api_keys_count: i64 = api_keys_count_result.get(0);
 
-        // Get payments count where this user is a recipient (included in pay_to_array)
-        let payments_count_query = "SELECT COUNT(*) FROM payments WHERE $1 = ANY(pay_to_array)";
-        let payments_count_result = match psql_db
-            .query_one(payments_count_query, &[wallet_address])
+        // Get count of transfers this user received, one per recipient ledger entry
+        let payments_count = match InvoicePaymentsModel::count_by_to_address(wallet_address, psql_db)
             .await
         {
-            Ok(row) => row,
+            Ok(count) => count,
             Err(_) => {
                 // If the query fails (e.g., table doesn't exist), set payments count to 0
                 return Ok(UserStats {
@@ -64,7 +63,6 @@ impl UsersModel {
                 });
             }
         };
-        let payments_count: i64 = payments_count_result.get(0);
 
         Ok(UserStats {
             wallet_address: wallet_address.clone(),
@@ -0,0 +1,261 @@
+use crate::db::postgres::executor::DbExecutor;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::types::domains::json::DomainJson;
+use crate::types::selected_record::SelectedRecord;
+use crate::util::built_from_row::BuiltFromDbRow;
+use crate::util::delivery_sink::SinkError;
+use chrono::{DateTime, Utc};
+use degen_sql::db::postgres::{models::model::PostgresModelError, postgres_db::Database};
+use degen_sql::pagination::PaginationData;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+/// Why a delivery attempt did not get a 2xx response
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum DeliveryErrorKind {
+    /// The endpoint answered with a non-2xx status
+    HttpStatus,
+    Timeout,
+    Connect,
+    /// Any other failure while sending the request or reading the response
+    Request,
+    /// Not sent because the url points at a private or reserved address
+    Blocked,
+    /// Sent in a batch whose 2xx response did not list the event as acknowledged
+    NotAcknowledged,
+    /// The message broker refused the message or had nowhere to keep it
+    Rejected,
+}
+
+impl std::fmt::Display for DeliveryErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            DeliveryErrorKind::HttpStatus => "http_status",
+            DeliveryErrorKind::Timeout => "timeout",
+            DeliveryErrorKind::Connect => "connect",
+            DeliveryErrorKind::Request => "request",
+            DeliveryErrorKind::Blocked => "blocked",
+            DeliveryErrorKind::NotAcknowledged => "not_acknowledged",
+            DeliveryErrorKind::Rejected => "rejected",
+        };
+
+        write!(f, "{}", kind)
+    }
+}
+
+impl From<&reqwest::Error> for DeliveryErrorKind {
+    fn from(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            DeliveryErrorKind::Timeout
+        } else if e.is_connect() {
+            DeliveryErrorKind::Connect
+        } else {
+            DeliveryErrorKind::Request
+        }
+    }
+}
+
+impl From<&SinkError> for DeliveryErrorKind {
+    fn from(e: &SinkError) -> Self {
+        match e {
+            SinkError::Blocked(_) => DeliveryErrorKind::Blocked,
+            SinkError::Connect(..) => DeliveryErrorKind::Connect,
+            SinkError::Timeout => DeliveryErrorKind::Timeout,
+            SinkError::Rejected(_) => DeliveryErrorKind::Rejected,
+        }
+    }
+}
+
+/// What an owner asked to have delivered again, logged on the trigger's deliveries
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ReplayScope {
+    /// One trigger, through /redeliver
+    Single,
+    /// Every trigger of a url in a time window, through /replay
+    Range,
+}
+
+impl std::fmt::Display for ReplayScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scope = match self {
+            ReplayScope::Single => "single",
+            ReplayScope::Range => "range",
+        };
+
+        write!(f, "{}", scope)
+    }
+}
+
+/// One attempt at delivering a webhook trigger, kept so failed deliveries can be diagnosed
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WebhookDelivery {
+    pub webhook_trigger_id: i32,
+    pub webhook_url_id: i32,
+    /// 1 for the first attempt of a trigger
+    pub attempt: i32,
+    pub request_url: String,
+    pub request_headers: DomainJson,
+    /// None for message brokers, which answer without a status
+    pub response_status: Option<i32>,
+    /// Cut to `MAX_RESPONSE_BODY_BYTES`. For a message broker, its receipt or error.
+    pub response_body: Option<String>,
+    pub latency_ms: i64,
+    pub error_kind: Option<String>,
+    /// Sent because the owner asked for the trigger to be delivered again
+    pub redelivery: bool,
+    /// Set instead of a response on the row logged when the owner asked for the trigger
+    /// to be delivered again. Such rows have attempt 0.
+    pub replay_requested_by: Option<DomainEthAddress>,
+    pub replay_scope: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// From the response's Retry-After header. Only used to schedule the next attempt, not stored.
+    #[serde(skip)]
+    pub retry_after: Option<std::time::Duration>,
+    /// Event ids a batch response listed, read before the body is cut. Not stored.
+    #[serde(skip)]
+    pub acknowledged: Option<Vec<String>>,
+}
+
+impl BuiltFromDbRow for WebhookDelivery {
+    fn from_row(row: &Row) -> Option<Self> {
+        Some(Self {
+            webhook_trigger_id: row.get("webhook_trigger_id"),
+            webhook_url_id: row.get("webhook_url_id"),
+            attempt: row.get("attempt"),
+            request_url: row.get("request_url"),
+            request_headers: row.get("request_headers"),
+            response_status: row.get("response_status"),
+            response_body: row.get("response_body"),
+            latency_ms: row.get("latency_ms"),
+            error_kind: row.get("error_kind"),
+            redelivery: row.try_get("redelivery").unwrap_or(false),
+            replay_requested_by: row.try_get("replay_requested_by").ok().flatten(),
+            replay_scope: row.try_get("replay_scope").ok().flatten(),
+            created_at: row.get("created_at"),
+            retry_after: None,
+            acknowledged: None,
+        })
+    }
+}
+
+impl WebhookDelivery {
+    /// Longest response body that is stored, in bytes
+    pub const MAX_RESPONSE_BODY_BYTES: usize = 4096;
+
+    /// 410 Gone: the receiver says the endpoint will not come back
+    pub fn endpoint_gone(&self) -> bool {
+        self.response_status == Some(410)
+    }
+
+    /// A 2xx response, or a message a broker accepted, and nothing left unacknowledged
+    pub fn succeeded(&self) -> bool {
+        self.error_kind.is_none()
+            && self
+                .response_status
+                .is_none_or(|status| (200..300).contains(&status))
+    }
+
+    /// Cuts a response body to `MAX_RESPONSE_BODY_BYTES` without splitting a character
+    pub fn truncate_response_body(mut body: String) -> String {
+        if body.len() > Self::MAX_RESPONSE_BODY_BYTES {
+            let mut end = Self::MAX_RESPONSE_BODY_BYTES;
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            body.truncate(end);
+        }
+
+        body
+    }
+}
+
+pub struct WebhookDeliveriesModel {}
+
+impl WebhookDeliveriesModel {
+    pub async fn insert_one(
+        delivery: &WebhookDelivery,
+        psql_db: &impl DbExecutor,
+    ) -> Result<i32, PostgresModelError> {
+        let row = psql_db
+            .query_one(
+                "
+                INSERT INTO webhook_deliveries
+                (webhook_trigger_id, webhook_url_id, attempt, request_url, request_headers,
+                 response_status, response_body, latency_ms, error_kind, redelivery, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                RETURNING id;
+                ",
+                &[
+                    &delivery.webhook_trigger_id,
+                    &delivery.webhook_url_id,
+                    &delivery.attempt,
+                    &delivery.request_url,
+                    &delivery.request_headers,
+                    &delivery.response_status,
+                    &delivery.response_body,
+                    &delivery.latency_ms,
+                    &delivery.error_kind,
+                    &delivery.redelivery,
+                    &delivery.created_at,
+                ],
+            )
+            .await?;
+
+        Ok(row.get("id"))
+    }
+
+    /// Attempts for one trigger, with the total count for pagination
+    pub async fn find_by_trigger_id_paginated(
+        webhook_trigger_id: i32,
+        pagination: &PaginationData,
+        psql_db: &Database,
+    ) -> Result<(Vec<SelectedRecord<WebhookDelivery>>, i64), PostgresModelError> {
+        let count_row = psql_db
+            .query_one(
+                "SELECT COUNT(*) as total FROM webhook_deliveries WHERE webhook_trigger_id = $1",
+                &[&webhook_trigger_id],
+            )
+            .await?;
+
+        let total_count: i64 = count_row.get("total");
+
+        let rows = psql_db
+            .query(
+                &format!(
+                    "
+                    SELECT *
+                    FROM webhook_deliveries
+                    WHERE webhook_trigger_id = $1
+                    {}
+                    ",
+                    pagination.build_query_part()
+                ),
+                &[&webhook_trigger_id],
+            )
+            .await?;
+
+        let records = rows
+            .iter()
+            .filter_map(SelectedRecord::<WebhookDelivery>::from_row)
+            .collect();
+
+        Ok((records, total_count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_response_body_keeps_char_boundary() {
+        let short = "ok".to_string();
+        assert_eq!(WebhookDelivery::truncate_response_body(short), "ok");
+
+        let long = "é".repeat(WebhookDelivery::MAX_RESPONSE_BODY_BYTES);
+        let truncated = WebhookDelivery::truncate_response_body(long);
+
+        assert!(truncated.len() <= WebhookDelivery::MAX_RESPONSE_BODY_BYTES);
+        assert!(truncated.chars().all(|c| c == 'é'));
+    }
+}
@@ -1,11 +1,15 @@
 
+use crate::db::postgres::executor::DbExecutor;
+use crate::types::domains::eth_address::DomainEthAddress;
 use crate::types::domains::json::DomainJson;
 use chrono::{DateTime, Utc};
 use degen_sql::db::postgres::{models::model::PostgresModelError, postgres_db::Database};
+use degen_sql::pagination::PaginationData;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio_postgres::Row;
 
+use super::webhook_deliveries_model::ReplayScope;
 use super::webhook_urls_model::WebhookUrl;
 use crate::types::selected_record::SelectedRecord;
 use crate::util::built_from_row::BuiltFromDbRow;
@@ -17,6 +21,9 @@ pub enum WebhookTriggerStatus {
     Sent,
     Failed,
     Acknowledged,
+    Reorged,
+    /// Gave up after the maximum number of delivery attempts
+    DeadLetter,
 }
 
 impl Default for WebhookTriggerStatus {
@@ -32,6 +39,8 @@ impl ToString for WebhookTriggerStatus {
             WebhookTriggerStatus::Sent => "sent".to_string(),
             WebhookTriggerStatus::Failed => "failed".to_string(),
             WebhookTriggerStatus::Acknowledged => "acknowledged".to_string(),
+            WebhookTriggerStatus::Reorged => "reorged".to_string(),
+            WebhookTriggerStatus::DeadLetter => "dead_letter".to_string(),
         }
     }
 }
@@ -43,6 +52,8 @@ impl From<String> for WebhookTriggerStatus {
             "sent" => WebhookTriggerStatus::Sent,
             "failed" => WebhookTriggerStatus::Failed,
             "acknowledged" => WebhookTriggerStatus::Acknowledged,
+            "reorged" => WebhookTriggerStatus::Reorged,
+            "dead_letter" => WebhookTriggerStatus::DeadLetter,
             _ => WebhookTriggerStatus::Pending,
         }
     }
@@ -57,7 +68,14 @@ pub struct WebhookTrigger {
     pub event_data: Option<DomainJson>,
     pub attempts: i32,
     pub last_triggered_at: Option<DateTime<Utc>>,
-    //pub created_at: DateTime<Utc>,
+    /// Identifies the event a trigger was created for so it is only ever created once
+    pub dedupe_key: Option<String>,
+    /// A failed trigger is not retried before this time
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// How many times the owner asked for this trigger to be sent again
+    pub redelivery_count: i32,
+    /// Only read from joined rows, which alias it as trigger_created_at
+    pub created_at: Option<DateTime<Utc>>,
 }
 
 impl BuiltFromDbRow for WebhookTrigger {
@@ -69,7 +87,10 @@ impl BuiltFromDbRow for WebhookTrigger {
             event_data: row.try_get("event_data").ok(),
             attempts: row.get("attempts"),
             last_triggered_at: row.get("last_triggered_at"),
-            //  created_at: row.get("created_at"),
+            dedupe_key: row.try_get("dedupe_key").ok().flatten(),
+            next_attempt_at: row.try_get("next_attempt_at").ok().flatten(),
+            redelivery_count: row.try_get("redelivery_count").unwrap_or(0),
+            created_at: row.try_get("trigger_created_at").ok().flatten(),
         })
     }
 }
@@ -101,8 +122,17 @@ impl WebhookTrigger {
             event_data: Some(DomainJson(event_data.get_event_data())),
             attempts: 0,
             last_triggered_at: None,
+            dedupe_key: None,
+            next_attempt_at: None,
+            redelivery_count: 0,
+            created_at: None,
         }
     }
+
+    pub fn with_dedupe_key(mut self, dedupe_key: String) -> Self {
+        self.dedupe_key = Some(dedupe_key);
+        self
+    }
 }
 
 /// Struct that combines a webhook trigger with its associated webhook URL
@@ -122,6 +152,45 @@ impl BuiltFromDbRow for WebhookTriggerJoined {
     }
 }
 
+/// Logs a redelivery request on each trigger of a `reset` CTE, as an attempt 0 delivery
+/// made by `$2` with the scope in `$5`
+const LOG_REPLAY_REQUEST: &str = "
+    INSERT INTO webhook_deliveries
+    (webhook_trigger_id, webhook_url_id, attempt, request_url, request_headers,
+     latency_ms, redelivery, replay_requested_by, replay_scope, created_at)
+    SELECT id, webhook_id, 0, webhook_url, '{}'::jsonb, 0, TRUE, $2, $5, NOW()
+    FROM reset;
+";
+
+/// What a claim returns for each trigger: the trigger and its webhook url
+const CLAIMED_TRIGGER_COLUMNS: &str = "
+                    t.id as id,
+                    t.webhook_id,
+                    t.status,
+                    t.event_type,
+                    t.event_data,
+                    t.attempts,
+                    t.last_triggered_at,
+                    t.next_attempt_at,
+                    t.redelivery_count,
+                    t.created_at as trigger_created_at,
+
+                    u.id as url_id,
+                    u.owner_wallet_address,
+                    u.webhook_url,
+                    u.scopes,
+                    u.signing_secret,
+                    u.previous_signing_secret,
+                    u.previous_secret_expires_at,
+                    u.consecutive_failures,
+                    u.api_version,
+                    u.payload_template,
+                    u.custom_headers,
+                    u.sink_credentials,
+                    u.batch_max_size,
+                    u.batch_max_wait_secs,
+                    u.created_at as created_at";
+
 pub struct WebhookTriggersModel {}
 
 impl WebhookTriggersModel {
@@ -153,15 +222,46 @@ impl WebhookTriggersModel {
         }
     }
 
-    /// Updates the status and last_triggered_at of a webhook trigger
+    /// Inserts a trigger unless one with the same dedupe key already exists.
+    /// Returns the new id, or None when the trigger was a duplicate.
+    pub async fn insert_if_new(
+        webhook_trigger: WebhookTrigger,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Option<i32>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "
+                INSERT INTO webhook_triggers
+                (webhook_id, status, event_type, event_data, attempts, last_triggered_at, dedupe_key)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (dedupe_key) DO NOTHING
+                RETURNING id;
+                ",
+                &[
+                    &webhook_trigger.webhook_id,
+                    &webhook_trigger.status,
+                    &webhook_trigger.event_type,
+                    &webhook_trigger.event_data,
+                    &webhook_trigger.attempts,
+                    &webhook_trigger.last_triggered_at,
+                    &webhook_trigger.dedupe_key,
+                ],
+            )
+            .await?;
+
+        Ok(rows.first().map(|row| row.get::<_, i32>("id")))
+    }
+
+    /// Updates the status and last_triggered_at of a webhook trigger, releasing any dispatcher claim
     pub async fn update_status(
         id: i32,
         status: WebhookTriggerStatus,
-        psql_db: &Database,
+        psql_db: &impl DbExecutor,
     ) -> Result<bool, PostgresModelError> {
         let status_str = status.to_string();
         let update_query = "UPDATE webhook_triggers 
-                           SET status = $2, last_triggered_at = NOW(), attempts = attempts + 1
+                           SET status = $2, last_triggered_at = NOW(), attempts = attempts + 1,
+                               claimed_until = NULL
                            WHERE id = $1;";
 
         let result = psql_db.execute(update_query, &[&id, &status_str]).await;
@@ -275,6 +375,44 @@ impl WebhookTriggersModel {
         Ok(triggers)
     }
 
+    /// Triggers for a webhook, one page at a time, with the total count
+    pub async fn find_by_webhook_id_paginated(
+        webhook_id: i32,
+        pagination: &PaginationData,
+        psql_db: &Database,
+    ) -> Result<(Vec<SelectedRecord<WebhookTrigger>>, i64), PostgresModelError> {
+        let count_row = psql_db
+            .query_one(
+                "SELECT COUNT(*) as total FROM webhook_triggers WHERE webhook_id = $1",
+                &[&webhook_id],
+            )
+            .await?;
+
+        let total_count: i64 = count_row.get("total");
+
+        let rows = psql_db
+            .query(
+                &format!(
+                    "
+                    SELECT *
+                    FROM webhook_triggers
+                    WHERE webhook_id = $1
+                    {}
+                    ",
+                    pagination.build_query_part()
+                ),
+                &[&webhook_id],
+            )
+            .await?;
+
+        let triggers = rows
+            .iter()
+            .filter_map(SelectedRecord::<WebhookTrigger>::from_row)
+            .collect();
+
+        Ok((triggers, total_count))
+    }
+
     /// Finds a specific webhook trigger joined with its webhook URL
     pub async fn find_webhook_trigger_joined_by_id(
         trigger_id: i32,
@@ -318,98 +456,348 @@ impl WebhookTriggersModel {
         }
     }
 
-    pub async fn find_next_pending_trigger_with_offset(
-        offset_id: Option<i32>,
-        psql_db: &Database,
-    ) -> Result<Option<SelectedRecord<WebhookTriggerJoined>>, PostgresModelError> {
-        let expected_status = WebhookTriggerStatus::Pending.to_string();
-        let row = match offset_id {
-            Some(id) => {
-                psql_db
-                    .query_one(
-                        "
-
-                        SELECT 
-                            t.id as  id,
-                            t.webhook_id,
-                            t.status,
-                            t.event_type,
-                            t.event_data,
-                            t.attempts,
-                            t.last_triggered_at,
-                           
-                            u.id as url_id, 
-                            u.owner_wallet_address,
-                            u.webhook_url,
-                            u.scopes,
-                            u.created_at as created_at
-                        FROM webhook_triggers t
-                        JOIN webhook_urls u ON t.webhook_id = u.id
-                        WHERE t.status = $1 AND t.id > $2
- 
-                        LIMIT 1 ;
-                        ",
-                        &[&expected_status, &id],
+    /// Claims up to `limit` triggers that are due, for `lease_secs`. Only the oldest pending
+    /// trigger of each webhook url can be claimed, so every url receives its triggers in order
+    /// and one slow url never holds up the others. Urls that have not completed verification,
+    /// or were disabled for failing, are skipped. Rows locked by another dispatcher are skipped too, so several replicas
+    /// can drain the queue at once. Urls that take batches are left to `claim_due_batches`.
+    pub async fn claim_due_triggers(
+        limit: i64,
+        lease_secs: f64,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Vec<SelectedRecord<WebhookTriggerJoined>>, PostgresModelError> {
+        let pending_status = WebhookTriggerStatus::Pending.to_string();
+
+        let query = format!(
+            "
+                WITH heads AS (
+                    SELECT DISTINCT ON (webhook_id) id
+                    FROM webhook_triggers
+                    WHERE status = $1
+                    ORDER BY webhook_id, id
+                ),
+                claimable AS (
+                    SELECT t.id
+                    FROM webhook_triggers t
+                    JOIN heads h ON h.id = t.id
+                    JOIN webhook_urls vu ON vu.id = t.webhook_id
+                    WHERE t.status = $1
+                    AND vu.verified_at IS NOT NULL
+                    AND vu.disabled_at IS NULL
+                    AND vu.batch_max_size IS NULL
+                    AND (t.next_attempt_at IS NULL OR t.next_attempt_at <= NOW())
+                    AND (t.claimed_until IS NULL OR t.claimed_until < NOW())
+                    ORDER BY t.id
+                    LIMIT $2
+                    FOR UPDATE OF t SKIP LOCKED
+                )
+                UPDATE webhook_triggers t
+                SET claimed_until = NOW() + make_interval(secs => $3)
+                FROM webhook_urls u
+                WHERE t.webhook_id = u.id
+                AND t.id IN (SELECT id FROM claimable)
+                RETURNING {};
+                ",
+            CLAIMED_TRIGGER_COLUMNS
+        );
+
+        let rows = psql_db
+            .query(&query, &[&pending_status, &limit, &lease_secs])
+            .await?;
+
+        let mut claimed: Vec<SelectedRecord<WebhookTriggerJoined>> =
+            rows.iter().filter_map(SelectedRecord::from_row).collect();
+
+        claimed.sort_by_key(|record| i32::from(record.id.clone()));
+
+        Ok(claimed)
+    }
+
+    /// Claims the next batch of up to `limit` webhook urls that take batches, for `lease_secs`.
+    /// A url's batch is ready once it has `batch_max_size` pending triggers, its oldest one
+    /// has waited `batch_max_wait_secs`, or its oldest one is being retried. The batch is its
+    /// oldest due triggers, in order. While any of a url's triggers are claimed it gets no
+    /// other batch, and a url being claimed by another dispatcher is skipped.
+    pub async fn claim_due_batches(
+        limit: i64,
+        lease_secs: f64,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Vec<SelectedRecord<WebhookTriggerJoined>>, PostgresModelError> {
+        let pending_status = WebhookTriggerStatus::Pending.to_string();
+
+        let query = format!(
+            "
+                WITH heads AS (
+                    SELECT DISTINCT ON (webhook_id)
+                        id, webhook_id, attempts, next_attempt_at, created_at
+                    FROM webhook_triggers
+                    WHERE status = $1
+                    ORDER BY webhook_id, id
+                ),
+                ready AS (
+                    SELECT h.webhook_id, vu.batch_max_size
+                    FROM heads h
+                    JOIN webhook_urls vu ON vu.id = h.webhook_id
+                    WHERE vu.batch_max_size IS NOT NULL
+                    AND vu.verified_at IS NOT NULL
+                    AND vu.disabled_at IS NULL
+                    AND (h.next_attempt_at IS NULL OR h.next_attempt_at <= NOW())
+                    AND NOT EXISTS (
+                        SELECT 1 FROM webhook_triggers c
+                        WHERE c.webhook_id = h.webhook_id
+                        AND c.status = $1
+                        AND c.claimed_until >= NOW()
                     )
-                    .await
-            }
-            None => {
-                psql_db
-                    .query_one(
-                        "
-
-                        SELECT 
-                            t.id as  id,
-                            t.webhook_id,
-                            t.status,
-                              t.attempts,
-                               t.event_type,
-                            t.event_data,
-                            t.last_triggered_at,
-                           
-                            u.id as url_id, 
-                            u.owner_wallet_address,
-                            u.webhook_url,
-                            u.scopes,
-                            u.created_at as created_at
-                        FROM webhook_triggers t
-                        JOIN webhook_urls u ON t.webhook_id = u.id
-                        WHERE t.status = $1 
- 
-                        LIMIT 1 ;
-                        ",
-                        &[&expected_status],
+                    AND (
+                        h.attempts > 0
+                        OR h.created_at <= NOW() - make_interval(secs => COALESCE(vu.batch_max_wait_secs, 0))
+                        OR (
+                            SELECT COUNT(*) FROM webhook_triggers p
+                            WHERE p.webhook_id = h.webhook_id AND p.status = $1
+                        ) >= vu.batch_max_size
                     )
-                    .await
-            }
-        };
+                    AND pg_try_advisory_xact_lock(hashtext('webhook_batch'), h.webhook_id)
+                    ORDER BY h.id
+                    LIMIT $2
+                ),
+                claimable AS (
+                    SELECT b.id
+                    FROM ready r
+                    CROSS JOIN LATERAL (
+                        SELECT t.id
+                        FROM webhook_triggers t
+                        WHERE t.webhook_id = r.webhook_id
+                        AND t.status = $1
+                        AND (t.next_attempt_at IS NULL OR t.next_attempt_at <= NOW())
+                        ORDER BY t.id
+                        LIMIT r.batch_max_size
+                        FOR UPDATE SKIP LOCKED
+                    ) b
+                )
+                UPDATE webhook_triggers t
+                SET claimed_until = NOW() + make_interval(secs => $3)
+                FROM webhook_urls u
+                WHERE t.webhook_id = u.id
+                AND t.id IN (SELECT id FROM claimable)
+                RETURNING {};
+                ",
+            CLAIMED_TRIGGER_COLUMNS
+        );
+
+        let rows = psql_db
+            .query(&query, &[&pending_status, &limit, &lease_secs])
+            .await?;
+
+        let mut claimed: Vec<SelectedRecord<WebhookTriggerJoined>> =
+            rows.iter().filter_map(SelectedRecord::from_row).collect();
+
+        claimed.sort_by_key(|record| i32::from(record.id.clone()));
+
+        Ok(claimed)
+    }
 
-        match row {
-            Ok(row) => {
-                let trig = SelectedRecord::from_row(&row);
+    /// Counts a failed delivery. The trigger waits until `next_attempt_at` before it is
+    /// tried again, or moves to dead_letter once it has used `max_attempts`.
+    /// Returns the trigger's status after the update.
+    pub async fn record_failed_attempt(
+        id: i32,
+        max_attempts: i32,
+        next_attempt_at: DateTime<Utc>,
+        psql_db: &impl DbExecutor,
+    ) -> Result<WebhookTriggerStatus, PostgresModelError> {
+        let dead_letter_status = WebhookTriggerStatus::DeadLetter.to_string();
+        let update_query = "UPDATE webhook_triggers 
+                           SET attempts = attempts + 1,
+                               last_triggered_at = NOW(),
+                               status = CASE WHEN attempts + 1 >= $2 THEN $3 ELSE status END,
+                               next_attempt_at = CASE WHEN attempts + 1 >= $2 THEN NULL ELSE $4 END,
+                               claimed_until = NULL
+                           WHERE id = $1
+                           RETURNING status;";
+
+        let row = psql_db
+            .query_one(
+                update_query,
+                &[&id, &max_attempts, &dead_letter_status, &next_attempt_at],
+            )
+            .await?;
 
-                Ok(trig)
-            }
-            Err(e) => {
-                eprintln!("Database error: Recent Event {:?}", e);
-                Err(e.into())
-            }
-        }
+        Ok(WebhookTriggerStatus::from(row.get::<_, String>("status")))
     }
 
-    pub async fn increment_trigger_attempts(
+    /// Clears the backoff of every pending trigger of a webhook url, so the dispatcher
+    /// picks its backlog up on the next tick. Returns how many were pending.
+    pub async fn make_pending_due(
+        webhook_id: i32,
+        psql_db: &impl DbExecutor,
+    ) -> Result<u64, PostgresModelError> {
+        let pending_status = WebhookTriggerStatus::Pending.to_string();
+
+        psql_db
+            .execute(
+                "
+                UPDATE webhook_triggers
+                SET next_attempt_at = NULL
+                WHERE webhook_id = $1
+                AND status = $2;
+                ",
+                &[&webhook_id, &pending_status],
+            )
+            .await
+    }
+
+    /// Puts a settled trigger owned by `owner_wallet_address` back in the queue with a fresh
+    /// set of attempts, and logs the request on its deliveries in the same statement.
+    /// Pending and reorged triggers are left alone.
+    /// Returns false when there is no such trigger to redeliver.
+    pub async fn reset_for_redelivery(
         id: i32,
+        owner_wallet_address: &DomainEthAddress,
         psql_db: &Database,
     ) -> Result<bool, PostgresModelError> {
+        let pending_status = WebhookTriggerStatus::Pending.to_string();
+        let redeliverable = Self::redeliverable_statuses();
+        let scope = ReplayScope::Single.to_string();
+
+        let rows_affected = psql_db
+            .execute(
+                &format!(
+                    "
+                    WITH reset AS (
+                        UPDATE webhook_triggers t
+                        SET status = $3,
+                            attempts = 0,
+                            next_attempt_at = NULL,
+                            claimed_until = NULL,
+                            redelivery_count = t.redelivery_count + 1
+                        FROM webhook_urls u
+                        WHERE t.webhook_id = u.id
+                        AND t.id = $1
+                        AND u.owner_wallet_address = $2
+                        AND t.status = ANY($4)
+                        RETURNING t.id, t.webhook_id, u.webhook_url
+                    )
+                    {}
+                    ",
+                    LOG_REPLAY_REQUEST
+                ),
+                &[
+                    &id,
+                    owner_wallet_address,
+                    &pending_status,
+                    &redeliverable,
+                    &scope,
+                ],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Like `reset_for_redelivery`, for every trigger of a webhook url created in
+    /// `[from, to]`, optionally only those in `status`. Returns how many were reset.
+    pub async fn reset_range_for_redelivery(
+        webhook_id: i32,
+        owner_wallet_address: &DomainEthAddress,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        status: Option<WebhookTriggerStatus>,
+        psql_db: &Database,
+    ) -> Result<u64, PostgresModelError> {
+        let pending_status = WebhookTriggerStatus::Pending.to_string();
+        let scope = ReplayScope::Range.to_string();
+
+        let statuses: Vec<String> = match status {
+            Some(status) => Self::redeliverable_statuses()
+                .into_iter()
+                .filter(|s| *s == status.to_string())
+                .collect(),
+            None => Self::redeliverable_statuses(),
+        };
+
+        psql_db
+            .execute(
+                &format!(
+                    "
+                    WITH reset AS (
+                        UPDATE webhook_triggers t
+                        SET status = $3,
+                            attempts = 0,
+                            next_attempt_at = NULL,
+                            claimed_until = NULL,
+                            redelivery_count = t.redelivery_count + 1
+                        FROM webhook_urls u
+                        WHERE t.webhook_id = u.id
+                        AND t.webhook_id = $1
+                        AND u.owner_wallet_address = $2
+                        AND t.status = ANY($4)
+                        AND t.created_at >= $6
+                        AND t.created_at <= $7
+                        RETURNING t.id, t.webhook_id, u.webhook_url
+                    )
+                    {}
+                    ",
+                    LOG_REPLAY_REQUEST
+                ),
+                &[
+                    &webhook_id,
+                    owner_wallet_address,
+                    &pending_status,
+                    &statuses,
+                    &scope,
+                    &from,
+                    &to,
+                ],
+            )
+            .await
+    }
+
+    fn redeliverable_statuses() -> Vec<String> {
+        [
+            WebhookTriggerStatus::Sent,
+            WebhookTriggerStatus::Acknowledged,
+            WebhookTriggerStatus::Failed,
+            WebhookTriggerStatus::DeadLetter,
+        ]
+        .iter()
+        .map(|status| status.to_string())
+        .collect()
+    }
+
+    /// Marks every payment_summary trigger for a transaction in the orphaned block as reorged
+    /// so it is not delivered, and so merchants can see which already-delivered notifications
+    /// were orphaned. Triggers for the payment in a later block are left alone.
+    /// Hashes are the 0x-prefixed hex form used in the serialized event data.
+    pub async fn mark_reorged_for_payment(
+        transaction_hash: &str,
+        chain_id: i64,
+        orphaned_block_hash: &str,
+        psql_db: &impl DbExecutor,
+    ) -> Result<u64, PostgresModelError> {
+        let status_str = WebhookTriggerStatus::Reorged.to_string();
         let update_query = "UPDATE webhook_triggers 
-                           SET attempts = attempts + 1, last_triggered_at = NOW()
-                           WHERE id = $1;";
+                           SET status = $1
+                           WHERE event_type = 'payment_summary'
+                           AND LOWER(event_data->>'transaction_hash') = LOWER($2)
+                           AND (event_data->>'chain_id')::BIGINT = $3
+                           AND (event_data->>'payment_at_block_hash' IS NULL
+                                OR LOWER(event_data->>'payment_at_block_hash') = LOWER($4));";
 
-        let result = psql_db.execute(update_query, &[&id]).await;
+        let result = psql_db
+            .execute(
+                update_query,
+                &[
+                    &status_str,
+                    &transaction_hash,
+                    &chain_id,
+                    &orphaned_block_hash,
+                ],
+            )
+            .await;
 
         match result {
-            Ok(rows_affected) => Ok(rows_affected > 0),
-            Err(e) => Err(e.into()),
+            Ok(rows_affected) => Ok(rows_affected),
+            Err(e) => Err(e),
         }
     }
 }
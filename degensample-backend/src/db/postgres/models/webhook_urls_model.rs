@@ -13,10 +13,19 @@ use degen_sql::db::postgres::{models::model::PostgresModelError, postgres_db::Da
 use serde::{Deserialize, Serialize};
 use tokio_postgres::Row;
 
+use crate::db::postgres::executor::DbExecutor;
+use crate::db::postgres::models::domain_events_model::{DomainEvent, DomainEventsModel};
 use crate::types::domains::eth_address::DomainEthAddress;
 use crate::types::domains::json::DomainJson;
+use crate::types::webhook_batches::WebhookBatching;
+use crate::types::webhook_events::WebhookApiVersion;
 use crate::types::selected_record::SelectedRecord;
 use crate::util::built_from_row::BuiltFromDbRow;
+use crate::db::postgres::models::webhook_triggers_model::{
+    IntoWebhookEventData, WebhookTriggersModel,
+};
+use crate::util::delivery_sink::{QueueTarget, SinkCredentials};
+use crate::util::webhook_signature::{generate_signing_secret, generate_verification_challenge};
 
 /*
 CREATE TABLE webhook_urls (
@@ -28,6 +37,27 @@ CREATE TABLE webhook_urls (
 
     scopes JSONB,
 
+    signing_secret TEXT,
+    previous_signing_secret TEXT,
+    previous_secret_expires_at TIMESTAMPTZ,
+
+    verification_challenge TEXT,
+    verified_at TIMESTAMPTZ,
+
+    consecutive_failures INT NOT NULL DEFAULT 0,
+    disabled_at TIMESTAMPTZ,
+    disabled_reason TEXT,
+
+    api_version TEXT NOT NULL DEFAULT 'v1',
+
+    payload_template JSONB,
+    custom_headers JSONB,
+
+    sink_credentials JSONB,
+
+    batch_max_size INT,
+    batch_max_wait_secs INT,
+
     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
 );
 */
@@ -39,6 +69,42 @@ pub struct WebhookUrl {
     pub webhook_url: String,
     pub scopes: Option<DomainJson>,
     pub created_at: DateTime<Utc>,
+
+    /// Only ever shown to the owner when it is created or rotated
+    #[serde(skip)]
+    pub signing_secret: Option<String>,
+    #[serde(skip)]
+    pub previous_signing_secret: Option<String>,
+    pub previous_secret_expires_at: Option<DateTime<Utc>>,
+
+    /// Sent to the url when it is registered; it must be echoed back before events are delivered
+    #[serde(skip)]
+    pub verification_challenge: Option<String>,
+    /// None until the url has answered the verification challenge
+    pub verified_at: Option<DateTime<Utc>>,
+
+    /// Failed deliveries since the last successful one
+    pub consecutive_failures: i32,
+    /// Set when the url was switched off for failing; it receives nothing until re-enabled
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub disabled_reason: Option<String>,
+
+    /// Shape of the bodies this url is sent
+    pub api_version: WebhookApiVersion,
+
+    /// Replaces the body, see `WebhookPayloadTemplate`
+    pub payload_template: Option<DomainJson>,
+    /// Extra request headers. They may hold the receiver's credentials, so they are never listed.
+    #[serde(skip)]
+    pub custom_headers: Option<DomainJson>,
+
+    /// Broker login when the url is a nats, redis or amqp target. Never listed.
+    #[serde(skip)]
+    pub sink_credentials: Option<DomainJson>,
+
+    /// Set when the url takes its events in batches, see `WebhookBatching`
+    pub batch_max_size: Option<i32>,
+    pub batch_max_wait_secs: Option<i32>,
 }
 
 impl BuiltFromDbRow for WebhookUrl {
@@ -48,6 +114,24 @@ impl BuiltFromDbRow for WebhookUrl {
             webhook_url: row.get("webhook_url"),
             scopes: row.get("scopes"),
             created_at: row.get("created_at"),
+            signing_secret: row.try_get("signing_secret").ok().flatten(),
+            previous_signing_secret: row.try_get("previous_signing_secret").ok().flatten(),
+            previous_secret_expires_at: row.try_get("previous_secret_expires_at").ok().flatten(),
+            verification_challenge: row.try_get("verification_challenge").ok().flatten(),
+            verified_at: row.try_get("verified_at").ok().flatten(),
+            consecutive_failures: row.try_get("consecutive_failures").unwrap_or(0),
+            disabled_at: row.try_get("disabled_at").ok().flatten(),
+            disabled_reason: row.try_get("disabled_reason").ok().flatten(),
+            api_version: row
+                .try_get::<_, String>("api_version")
+                .ok()
+                .and_then(|version| version.parse().ok())
+                .unwrap_or_default(),
+            payload_template: row.try_get("payload_template").ok().flatten(),
+            custom_headers: row.try_get("custom_headers").ok().flatten(),
+            sink_credentials: row.try_get("sink_credentials").ok().flatten(),
+            batch_max_size: row.try_get("batch_max_size").ok().flatten(),
+            batch_max_wait_secs: row.try_get("batch_max_wait_secs").ok().flatten(),
         })
     }
 }
@@ -91,7 +175,122 @@ impl WebhookUrl {
             webhook_url,
             scopes,
             created_at: chrono::Utc::now(),
+            signing_secret: Some(generate_signing_secret()),
+            previous_signing_secret: None,
+            previous_secret_expires_at: None,
+            verification_challenge: Some(generate_verification_challenge()),
+            verified_at: None,
+            consecutive_failures: 0,
+            disabled_at: None,
+            disabled_reason: None,
+            api_version: WebhookApiVersion::LATEST,
+            payload_template: None,
+            custom_headers: None,
+            sink_credentials: None,
+            batch_max_size: None,
+            batch_max_wait_secs: None,
+        }
+    }
+
+    pub fn with_api_version(mut self, api_version: WebhookApiVersion) -> Self {
+        self.api_version = api_version;
+        self
+    }
+
+    pub fn with_payload_template(
+        mut self,
+        payload_template: Option<DomainJson>,
+        custom_headers: Option<DomainJson>,
+    ) -> Self {
+        self.payload_template = payload_template;
+        self.custom_headers = custom_headers;
+        self
+    }
+
+    pub fn with_sink_credentials(mut self, sink_credentials: Option<DomainJson>) -> Self {
+        self.sink_credentials = sink_credentials;
+        self
+    }
+
+    pub fn with_batching(mut self, batching: Option<WebhookBatching>) -> Self {
+        self.batch_max_size = batching.map(|batching| batching.max_size);
+        self.batch_max_wait_secs = batching.map(|batching| batching.max_wait_secs);
+        self
+    }
+
+    pub fn batching(&self) -> Option<WebhookBatching> {
+        Some(WebhookBatching {
+            max_size: self.batch_max_size?,
+            max_wait_secs: self.batch_max_wait_secs.unwrap_or(0),
+        })
+    }
+
+    /// The broker this url publishes to, or None for an HTTP receiver
+    pub fn queue_target(&self) -> Option<QueueTarget> {
+        let credentials = self
+            .sink_credentials
+            .as_ref()
+            .and_then(|credentials| serde_json::from_value::<SinkCredentials>(credentials.0.clone()).ok());
+
+        QueueTarget::from_url(&self.webhook_url, credentials)
+            .ok()
+            .flatten()
+    }
+
+    /// Secrets deliveries are signed with: the current one, plus the previous one
+    /// until its grace period runs out
+    pub fn active_signing_secrets(&self, now: DateTime<Utc>) -> Vec<&str> {
+        let mut secrets: Vec<&str> = self.signing_secret.iter().map(|s| s.as_str()).collect();
+
+        if let (Some(previous), Some(expires_at)) =
+            (&self.previous_signing_secret, self.previous_secret_expires_at)
+        {
+            if expires_at > now {
+                secrets.push(previous);
+            }
         }
+
+        secrets
+    }
+}
+
+/// Why a webhook url was disabled
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum WebhookUrlDisabledReason {
+    /// Reached the consecutive failure limit
+    ConsecutiveFailures,
+    /// Answered 410 Gone, which says the endpoint has been removed for good
+    Gone,
+}
+
+impl std::fmt::Display for WebhookUrlDisabledReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            WebhookUrlDisabledReason::ConsecutiveFailures => "consecutive_failures",
+            WebhookUrlDisabledReason::Gone => "gone",
+        };
+
+        write!(f, "{}", reason)
+    }
+}
+
+/// Sent to the owner's other webhook urls when one of their urls is disabled
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WebhookUrlDisabledEvent {
+    pub webhook_id: i32,
+    pub webhook_url: String,
+    pub reason: String,
+    pub consecutive_failures: i32,
+    pub disabled_at: DateTime<Utc>,
+}
+
+impl IntoWebhookEventData for WebhookUrlDisabledEvent {
+    fn get_event_type(&self) -> String {
+        "webhook_url_disabled".into()
+    }
+
+    fn get_event_data(&self) -> serde_json::Value {
+        serde_json::to_value(self).unwrap_or_default()
     }
 }
 
@@ -123,8 +322,8 @@ impl WebhookUrlsModel {
         webhook_url: WebhookUrl,
         psql_db: &Database,
     ) -> Result<i32, PostgresModelError> {
-        let insert_query = "INSERT INTO webhook_urls (owner_wallet_address, webhook_url, scopes)
-                            VALUES ($1, $2, $3)
+        let insert_query = "INSERT INTO webhook_urls (owner_wallet_address, webhook_url, scopes, signing_secret, verification_challenge, api_version, payload_template, custom_headers, sink_credentials, batch_max_size, batch_max_wait_secs)
+                            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
                             RETURNING id;";
         let result = psql_db
             .query_one(
@@ -133,6 +332,14 @@ impl WebhookUrlsModel {
                     &webhook_url.owner_wallet_address,
                     &webhook_url.webhook_url,
                     &webhook_url.scopes,
+                    &webhook_url.signing_secret,
+                    &webhook_url.verification_challenge,
+                    &webhook_url.api_version.to_string(),
+                    &webhook_url.payload_template,
+                    &webhook_url.custom_headers,
+                    &webhook_url.sink_credentials,
+                    &webhook_url.batch_max_size,
+                    &webhook_url.batch_max_wait_secs,
                 ],
             )
             .await;
@@ -146,35 +353,18 @@ impl WebhookUrlsModel {
     /// Retrieves a `WebhookUrl` by owner's wallet address
     pub async fn find_by_owner_address(
         owner_wallet_address: &DomainEthAddress,
-        psql_db: &Database,
+        psql_db: &impl DbExecutor,
     ) -> Result<Vec<SelectedRecord<WebhookUrl>>, PostgresModelError> {
-        use degen_sql::sql_builder::{OrderingDirection, SqlBuilder, SqlStatementBase};
-        use std::collections::BTreeMap;
-        use std::sync::Arc;
-        use tokio_postgres::types::ToSql;
-
-        // Create SQL builder with where condition
-        let mut where_params: BTreeMap<String, Arc<dyn ToSql + Sync>> = BTreeMap::new();
-        where_params.insert(
-            "owner_wallet_address".to_string(),
-            Arc::new(owner_wallet_address.clone()),
-        );
-
-        let sql_builder = SqlBuilder {
-            statement_base: SqlStatementBase::SelectAll,
-            table_name: "webhook_urls".to_string(),
-            where_params,
-            order: Some(("created_at".to_string(), OrderingDirection::DESC)),
-            limit: None,
-            pagination: None,
-        };
-
-        // Build the SQL query and parameters
-        let (query, params) = sql_builder.build();
-        let built_params = &params.iter().map(|x| &**x).collect::<Vec<_>>();
-
-        // Execute the query
-        let rows = psql_db.query(&query, &built_params).await?;
+        let rows = psql_db
+            .query(
+                "
+                SELECT * FROM webhook_urls
+                WHERE owner_wallet_address = $1
+                ORDER BY created_at DESC;
+                ",
+                &[owner_wallet_address],
+            )
+            .await?;
 
         let webhook_urls = rows
             .iter()
@@ -184,6 +374,277 @@ impl WebhookUrlsModel {
         Ok(webhook_urls)
     }
 
+    /// Only urls that completed the verification handshake receive events
+    pub async fn find_verified_by_owner_address(
+        owner_wallet_address: &DomainEthAddress,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Vec<SelectedRecord<WebhookUrl>>, PostgresModelError> {
+        let rows = psql_db
+            .query(
+                "
+                SELECT * FROM webhook_urls
+                WHERE owner_wallet_address = $1
+                AND verified_at IS NOT NULL
+                ORDER BY created_at DESC;
+                ",
+                &[owner_wallet_address],
+            )
+            .await?;
+
+        Ok(rows.iter().filter_map(SelectedRecord::from_row).collect())
+    }
+
+    pub async fn mark_verified(id: i32, psql_db: &Database) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE webhook_urls
+                SET verified_at = NOW(),
+                    verification_challenge = NULL
+                WHERE id = $1;
+                ",
+                &[&id],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Resets the failure count after a successful delivery
+    pub async fn record_delivery_success(
+        id: i32,
+        psql_db: &impl DbExecutor,
+    ) -> Result<(), PostgresModelError> {
+        psql_db
+            .execute(
+                "
+                UPDATE webhook_urls
+                SET consecutive_failures = 0
+                WHERE id = $1
+                AND consecutive_failures <> 0;
+                ",
+                &[&id],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Counts a failed delivery. The url is disabled once it reaches `disable_after`
+    /// consecutive failures, or straight away when `gone`. Returns the url as updated
+    /// when this failure is the one that disabled it.
+    pub async fn record_delivery_failure(
+        id: i32,
+        disable_after: i32,
+        gone: bool,
+        psql_db: &impl DbExecutor,
+    ) -> Result<Option<WebhookUrl>, PostgresModelError> {
+        let gone_reason = WebhookUrlDisabledReason::Gone.to_string();
+        let failures_reason = WebhookUrlDisabledReason::ConsecutiveFailures.to_string();
+
+        let rows = psql_db
+            .query(
+                "
+                WITH previous AS (
+                    SELECT id, disabled_at
+                    FROM webhook_urls
+                    WHERE id = $1
+                    FOR UPDATE
+                )
+                UPDATE webhook_urls u
+                SET consecutive_failures = u.consecutive_failures + 1,
+                    disabled_at = CASE
+                        WHEN u.disabled_at IS NULL AND ($3 OR u.consecutive_failures + 1 >= $2)
+                        THEN NOW() ELSE u.disabled_at END,
+                    disabled_reason = CASE
+                        WHEN u.disabled_at IS NOT NULL THEN u.disabled_reason
+                        WHEN $3 THEN $4
+                        WHEN u.consecutive_failures + 1 >= $2 THEN $5
+                        ELSE NULL END
+                FROM previous
+                WHERE u.id = previous.id
+                RETURNING u.*, previous.disabled_at IS NULL AND u.disabled_at IS NOT NULL AS newly_disabled;
+                ",
+                &[&id, &disable_after, &gone, &gone_reason, &failures_reason],
+            )
+            .await?;
+
+        Ok(rows
+            .first()
+            .filter(|row| row.get::<_, bool>("newly_disabled"))
+            .and_then(WebhookUrl::from_row))
+    }
+
+    /// Publishes `webhook_url_disabled` to the owner's webhooks, so they hear about it without
+    /// watching the dashboard. Pass the transaction that disabled the url.
+    pub async fn publish_disabled_event(
+        id: i32,
+        disabled: &WebhookUrl,
+        psql_db: &impl DbExecutor,
+    ) -> Result<(), PostgresModelError> {
+        let Some(disabled_at) = disabled.disabled_at else {
+            return Ok(());
+        };
+
+        let event = WebhookUrlDisabledEvent {
+            webhook_id: id,
+            webhook_url: disabled.webhook_url.clone(),
+            reason: disabled.disabled_reason.clone().unwrap_or_default(),
+            consecutive_failures: disabled.consecutive_failures,
+            disabled_at,
+        };
+
+        DomainEventsModel::publish(
+            DomainEvent::new(&event, vec![disabled.owner_wallet_address.clone()]).with_dedupe_key(
+                format!("webhook_url_disabled:{}:{}", id, disabled_at.timestamp()),
+            ),
+            psql_db,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Turns a disabled url owned by `owner_wallet_address` back on with a clean failure count.
+    /// Its pending triggers are made due right away, so it resumes from its backlog.
+    /// Returns how many triggers are pending, or None when the owner has no such url.
+    pub async fn enable(
+        id: i32,
+        owner_wallet_address: &DomainEthAddress,
+        psql_db: &Database,
+    ) -> Result<Option<u64>, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE webhook_urls
+                SET disabled_at = NULL,
+                    disabled_reason = NULL,
+                    consecutive_failures = 0
+                WHERE id = $1
+                AND owner_wallet_address = $2;
+                ",
+                &[&id, owner_wallet_address],
+            )
+            .await?;
+
+        if rows_affected != 1 {
+            return Ok(None);
+        }
+
+        let pending = WebhookTriggersModel::make_pending_due(id, psql_db).await?;
+
+        Ok(Some(pending))
+    }
+
+    /// Pins a webhook url owned by `owner_wallet_address` to another api version.
+    /// Returns false when the owner has no such url.
+    pub async fn set_api_version(
+        id: i32,
+        owner_wallet_address: &DomainEthAddress,
+        api_version: WebhookApiVersion,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE webhook_urls
+                SET api_version = $3
+                WHERE id = $1
+                AND owner_wallet_address = $2;
+                ",
+                &[&id, owner_wallet_address, &api_version.to_string()],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Replaces the payload template and custom headers of a webhook url owned by
+    /// `owner_wallet_address`; None clears them. Returns false when the owner has no such url.
+    pub async fn set_payload_template(
+        id: i32,
+        owner_wallet_address: &DomainEthAddress,
+        payload_template: Option<DomainJson>,
+        custom_headers: Option<DomainJson>,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE webhook_urls
+                SET payload_template = $3,
+                    custom_headers = $4
+                WHERE id = $1
+                AND owner_wallet_address = $2;
+                ",
+                &[&id, owner_wallet_address, &payload_template, &custom_headers],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Switches batched deliveries on, or off with None, for a webhook url owned by
+    /// `owner_wallet_address`. Returns false when no such webhook url exists for the owner.
+    pub async fn set_batching(
+        id: i32,
+        owner_wallet_address: &DomainEthAddress,
+        batching: Option<WebhookBatching>,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE webhook_urls
+                SET batch_max_size = $3,
+                    batch_max_wait_secs = $4
+                WHERE id = $1
+                AND owner_wallet_address = $2;
+                ",
+                &[
+                    &id,
+                    owner_wallet_address,
+                    &batching.map(|batching| batching.max_size),
+                    &batching.map(|batching| batching.max_wait_secs),
+                ],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+
+    /// Replaces the signing secret of a webhook url owned by `owner_wallet_address`.
+    /// The old secret keeps signing deliveries until `previous_secret_expires_at`.
+    /// Returns false when no such webhook url exists for the owner.
+    pub async fn rotate_signing_secret(
+        id: i32,
+        owner_wallet_address: &DomainEthAddress,
+        new_signing_secret: &str,
+        previous_secret_expires_at: DateTime<Utc>,
+        psql_db: &Database,
+    ) -> Result<bool, PostgresModelError> {
+        let rows_affected = psql_db
+            .execute(
+                "
+                UPDATE webhook_urls
+                SET previous_signing_secret = signing_secret,
+                    previous_secret_expires_at = $4,
+                    signing_secret = $3
+                WHERE id = $1
+                AND owner_wallet_address = $2;
+                ",
+                &[
+                    &id,
+                    owner_wallet_address,
+                    &new_signing_secret,
+                    &previous_secret_expires_at,
+                ],
+            )
+            .await?;
+
+        Ok(rows_affected == 1)
+    }
+
     /// Delete a webhook URL by ID
     pub async fn delete_by_id(
         id: i32,
@@ -0,0 +1,217 @@
+use defirelay_backend::config::{NetworksConfig, PayspecContractsConfig};
+use defirelay_backend::db::postgres::models::events_model::EventsModel;
+use defirelay_backend::db::postgres::models::payments_model::PaymentsModel;
+use defirelay_backend::util::payment_summary::{
+    fetch_payment_summary, PaidInvoiceData, PaymentSummaryConfig,
+};
+use degen_sql::db::postgres::postgres_db::Database;
+use dotenvy::dotenv;
+use ethers::types::Address;
+use std::str::FromStr;
+
+/*
+
+Rebuilds rows in `payments` from the PaidInvoice events already stored in `events`.
+
+cargo run --bin replay_payments -- --chain-id 8453 --from-block 20000000 --dry-run
+cargo run --bin replay_payments -- --chain-id 8453 --contract 0x... --from-block 20000000 --to-block 21000000 --emit-webhooks
+
+  --chain-id       chain to replay (required)
+  --contract       payspec contract, defaults to the one in config/networks.json
+  --from-block     first block to replay, defaults to 0
+  --to-block       last block to replay, defaults to the latest indexed event
+  --dry-run        only print what would change
+  --emit-webhooks  publish payment events for payments that were added or changed,
+                   which the webhook trigger bot delivers.
+                   By default replays are silent. Webhook urls that were already
+                   notified of a payment are never notified again.
+
+*/
+
+const EVENT_BATCH_SIZE: i64 = 500;
+
+struct ReplayArgs {
+    chain_id: u64,
+    contract_address: Option<Address>,
+    from_block: u64,
+    to_block: Option<u64>,
+    dry_run: bool,
+    emit_webhooks: bool,
+}
+
+impl ReplayArgs {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut chain_id = None;
+        let mut contract_address = None;
+        let mut from_block = 0;
+        let mut to_block = None;
+        let mut dry_run = false;
+        let mut emit_webhooks = false;
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("{} requires a value", name))
+            };
+
+            match arg.as_str() {
+                "--chain-id" => {
+                    chain_id = Some(value(&arg)?.parse().map_err(|_| "invalid --chain-id")?)
+                }
+                "--contract" => {
+                    contract_address =
+                        Some(Address::from_str(&value(&arg)?).map_err(|_| "invalid --contract")?)
+                }
+                "--from-block" => {
+                    from_block = value(&arg)?.parse().map_err(|_| "invalid --from-block")?
+                }
+                "--to-block" => {
+                    to_block = Some(value(&arg)?.parse().map_err(|_| "invalid --to-block")?)
+                }
+                "--dry-run" => dry_run = true,
+                "--emit-webhooks" => emit_webhooks = true,
+                other => return Err(format!("unknown argument {}", other)),
+            }
+        }
+
+        Ok(Self {
+            chain_id: chain_id.ok_or("--chain-id is required")?,
+            contract_address,
+            from_block,
+            to_block,
+            dry_run,
+            emit_webhooks,
+        })
+    }
+}
+
+#[derive(Default)]
+struct ReplayTotals {
+    added: usize,
+    changed: usize,
+    unchanged: usize,
+    failed: usize,
+    webhook_events: usize,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    dotenv().ok();
+
+    let args = ReplayArgs::parse(std::env::args().skip(1))?;
+
+    let networks = NetworksConfig::load();
+
+    let contract_address = match args.contract_address {
+        Some(contract_address) => contract_address,
+        None => {
+            PayspecContractsConfig::from_networks(&networks)
+                .get_contract_address(args.chain_id as i64)
+                .ok_or("no payspec contract configured for this chain, pass --contract")?
+                .0
+        }
+    };
+
+    let db_conn_url = std::env::var("DB_CONN_URL").expect(" DB_CONN_URL must be set in env ");
+
+    let database = Database::new(db_conn_url, None)?;
+
+    let config = PaymentSummaryConfig::from_env(networks);
+
+    println!(
+        "replaying PaidInvoice events for {:?} on chain {} from block {} to {}{}",
+        contract_address,
+        args.chain_id,
+        args.from_block,
+        args.to_block
+            .map(|b| b.to_string())
+            .unwrap_or("latest".to_string()),
+        if args.dry_run { " (dry run)" } else { "" }
+    );
+
+    let mut totals = ReplayTotals::default();
+    let mut after_id = None;
+
+    loop {
+        let events = EventsModel::find_events_of_type_in_block_range(
+            "PaidInvoice",
+            args.chain_id as i64,
+            contract_address,
+            args.from_block,
+            args.to_block,
+            after_id,
+            EVENT_BATCH_SIZE,
+            &database,
+        )
+        .await?;
+
+        let Some((last_id, _)) = events.last() else {
+            break;
+        };
+        after_id = Some(*last_id);
+
+        for (event_id, event) in events {
+            let Some(paid_invoice_data) = PaidInvoiceData::extract_paid_invoice_data(&event) else {
+                println!("! event {}: could not extract PaidInvoice data", event_id);
+                totals.failed += 1;
+                continue;
+            };
+
+            let payment_summary =
+                match fetch_payment_summary(&paid_invoice_data, &config, &database).await {
+                    Ok(payment_summary) => payment_summary,
+                    Err(e) => {
+                        println!("! event {}: {}", event_id, e);
+                        totals.failed += 1;
+                        continue;
+                    }
+                };
+
+            let tx_hash = format!("{:x}", payment_summary.transaction_hash.0);
+
+            let stored =
+                PaymentsModel::find_by_transaction_hash(&tx_hash, args.chain_id as i64, &database)
+                    .await?;
+
+            match &stored {
+                None => {
+                    println!("+ 0x{} new payment", tx_hash);
+                    totals.added += 1;
+                }
+                Some(stored) => {
+                    let changes = payment_summary.diff(&stored.entry);
+
+                    if changes.is_empty() {
+                        totals.unchanged += 1;
+                        continue;
+                    }
+
+                    println!("~ 0x{}", tx_hash);
+                    for (field, stored_value, replayed_value) in changes {
+                        println!("    {}: {} -> {}", field, stored_value, replayed_value);
+                    }
+                    totals.changed += 1;
+                }
+            }
+
+            if args.dry_run {
+                continue;
+            }
+
+            PaymentsModel::insert_or_update_one(payment_summary.clone(), &database).await?;
+
+            if args.emit_webhooks {
+                PaymentsModel::publish_payment_event(&payment_summary, &database).await?;
+                totals.webhook_events += 1;
+            }
+        }
+    }
+
+    println!(
+        "{} added, {} changed, {} unchanged, {} failed, {} webhook events published",
+        totals.added, totals.changed, totals.unchanged, totals.failed, totals.webhook_events
+    );
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use crate::db::postgres::models::domain_events_model::DomainEvent;
+use crate::types::selected_record::SelectedRecord;
+use crate::types::webhook_events::{WebhookEventDataV1, WebhookEventError};
+use ethers::types::Address;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/*
+
+What the `/api/stream` endpoints push to a dashboard. Each event is a domain event of the
+connected wallet, with the `type` and `data` of a v1 webhook body:
+
+  {"id": 481, "type": "payment_summary", "data": {...}, "created_at": 1700000000}
+
+Ids only increase, so a client that reconnects with the last id it saw is sent what it
+missed. Over server-sent events a frame is
+
+  id: 481
+  event: payment_summary
+  data: {"id": 481, "type": "payment_summary", ...}
+
+*/
+
+/// Domain events a dashboard is streamed
+pub const STREAM_EVENT_TYPES: [&str; 4] = [
+    "payment_summary",
+    "invoice_created",
+    "invoice_expired",
+    "webhook_url_disabled",
+];
+
+#[derive(Serialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct AccountStreamEvent {
+    /// Id of the domain event, to resume from
+    pub id: i32,
+    #[serde(skip)]
+    pub event_type: String,
+    /// Wallets the event is streamed to
+    #[serde(skip)]
+    pub owners: Vec<Address>,
+    #[serde(flatten)]
+    pub event: WebhookEventDataV1,
+    /// Unix seconds when the event happened
+    pub created_at: i64,
+}
+
+impl AccountStreamEvent {
+    pub fn from_record(record: &SelectedRecord<DomainEvent>) -> Result<Self, WebhookEventError> {
+        let event = &record.entry;
+
+        Ok(Self {
+            id: record.id.0,
+            event_type: event.event_type.clone(),
+            owners: event
+                .owner_wallet_addresses
+                .iter()
+                .map(|owner| owner.0)
+                .collect(),
+            event: WebhookEventDataV1::from_stored(&event.event_type, &event.payload.0)?,
+            created_at: event.created_at.timestamp(),
+        })
+    }
+
+    pub fn is_for(&self, owner: &Address) -> bool {
+        self.owners.contains(owner)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// A complete server-sent event, including the blank line that ends it
+    pub fn to_sse_frame(&self) -> String {
+        format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            self.id,
+            self.event_type,
+            self.to_json()
+        )
+    }
+}
+
+/// Reads a `Last-Event-ID`. Anything but an id the stream sent starts from new events.
+pub fn parse_last_event_id(value: &str) -> Option<i32> {
+    value.trim().parse::<i32>().ok().filter(|id| *id >= 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::postgres::models::payments_model::PaymentSummary;
+    use crate::types::domains::eth_address::DomainEthAddress;
+
+    #[test]
+    fn test_stream_event_frames() {
+        let owner = Address::random();
+        let payment = PaymentSummary::generate_test_payment_summary();
+        let record = SelectedRecord {
+            id: 481.into(),
+            entry: DomainEvent::new(&payment, vec![DomainEthAddress(owner)]),
+        };
+
+        let event = AccountStreamEvent::from_record(&record).unwrap();
+        assert!(event.is_for(&owner));
+        assert!(!event.is_for(&Address::random()));
+
+        let json: serde_json::Value = serde_json::from_str(&event.to_json()).unwrap();
+        assert_eq!(json["id"], 481);
+        assert_eq!(json["type"], "payment_summary");
+        assert!(json.get("owners").is_none());
+
+        let frame = event.to_sse_frame();
+        assert!(frame.starts_with("id: 481\nevent: payment_summary\ndata: {"));
+        assert!(frame.ends_with("}\n\n"));
+
+        assert_eq!(parse_last_event_id(" 481 "), Some(481));
+        assert_eq!(parse_last_event_id("whevt_481"), None);
+        assert_eq!(parse_last_event_id("-1"), None);
+    }
+}
@@ -3,13 +3,21 @@ use crate::types::domains::json::DomainJson;
 use crate::types::selected_record::SelectedRecord;
 use crate::util::header_map_preset::HeaderMapPreset;
 use crate::util::http_request::EndpointType;
+use crate::util::http_request::EndpointUrlAndData;
 use crate::util::http_request::IntoHttpRequest;
+use crate::types::webhook_events::{WebhookApiVersion, WebhookEventError, WebhookEventV1};
+use crate::types::webhook_templates::{WebhookCustomHeaders, WebhookPayloadTemplate};
+use crate::util::webhook_signature::{
+    signature_header, EVENT_ID_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+};
+use chrono::{DateTime, Utc};
 use reqwest::header::HeaderMap;
 use serde::Deserialize;
 use serde::Serialize;
+use utoipa::ToSchema;
 
-
-#[derive(Serialize, Deserialize)]
+/// Everything a webhook request is built from. Serialized as is, this is the v0 body.
+#[derive(Serialize, Deserialize, ToSchema)]
 pub struct DefiRelayWebhookPayload {
     pub webhook_trigger_id: i32,
     pub webhook_id: i32,
@@ -18,6 +26,22 @@ pub struct DefiRelayWebhookPayload {
 
     pub event_type: Option<String>,
     pub event_data: Option<DomainJson>,
+
+    /// True when the owner asked for this event to be sent again
+    #[serde(default)]
+    pub redelivery: bool,
+
+    /// Version of the webhook url, which decides the body that is sent
+    #[serde(skip)]
+    pub api_version: WebhookApiVersion,
+    /// When the trigger was created
+    #[serde(skip)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Replaces the body when the webhook url has one
+    #[serde(skip)]
+    pub payload_template: Option<WebhookPayloadTemplate>,
+    #[serde(skip)]
+    pub custom_headers: WebhookCustomHeaders,
 }
 
 impl DefiRelayWebhookPayload {
@@ -25,6 +49,10 @@ impl DefiRelayWebhookPayload {
         let id = input.id;
         let trig = input.entry.webhook_trigger;
         let webhook_url = input.entry.webhook_url;
+        let api_version = webhook_url.api_version;
+        let payload_template =
+            WebhookPayloadTemplate::from_stored(webhook_url.payload_template.as_ref());
+        let custom_headers = WebhookCustomHeaders::from_stored(webhook_url.custom_headers.as_ref());
 
         Self {
             webhook_trigger_id: id.into(),
@@ -32,8 +60,121 @@ impl DefiRelayWebhookPayload {
             webhook_url: webhook_url.webhook_url,
             event_type: trig.event_type, // often, 'payment_summary'
             event_data: trig.event_data, // often, PaymentSummary
+            redelivery: trig.redelivery_count > 0,
+            api_version,
+            created_at: trig.created_at,
+            payload_template,
+            custom_headers,
+        }
+    }
+
+    /// Identifies the delivery to the receiver and stays the same across retries
+    pub fn event_id(&self) -> String {
+        format!("whevt_{}", self.webhook_trigger_id)
+    }
+
+    /// The body for the webhook url's api version
+    pub fn versioned_body(&self) -> Result<serde_json::Value, WebhookEventError> {
+        match self.api_version {
+            WebhookApiVersion::V0 => Ok(self.get_data()),
+            WebhookApiVersion::V1 => Ok(serde_json::to_value(WebhookEventV1::from_payload(self)?)
+                .unwrap_or_default()),
         }
     }
+
+    /// The body that is sent: the url's template rendered over the v1 event, otherwise
+    /// the body of its api version
+    pub fn body(&self) -> Result<serde_json::Value, WebhookEventError> {
+        match &self.payload_template {
+            Some(template) => Ok(template.render(&WebhookEventV1::from_payload(self)?)),
+            None => self.versioned_body(),
+        }
+    }
+
+    /// The POST for this payload with the url's custom headers and event id, timestamp
+    /// and signature headers.
+    /// The signature covers the exact JSON body that is sent. Without any secret
+    /// the request goes out unsigned, as it did for urls created before signing.
+    /// Fails when the stored event cannot be put in the url's api version.
+    pub fn to_signed_request(
+        &self,
+        signing_secrets: &[&str],
+        timestamp: i64,
+    ) -> Result<EndpointUrlAndData, WebhookEventError> {
+        Ok(signed_post(
+            self.get_url(),
+            self.body()?,
+            self.event_id(),
+            self.custom_headers.to_pairs(),
+            signing_secrets,
+            timestamp,
+        ))
+    }
+}
+
+pub(crate) fn signed_post(
+    url: String,
+    data: serde_json::Value,
+    event_id: String,
+    mut extra_headers: Vec<(String, String)>,
+    signing_secrets: &[&str],
+    timestamp: i64,
+) -> EndpointUrlAndData {
+    extra_headers.push((EVENT_ID_HEADER.to_string(), event_id));
+    extra_headers.push((TIMESTAMP_HEADER.to_string(), timestamp.to_string()));
+
+    if !signing_secrets.is_empty() {
+        let body = serde_json::to_vec(&data).unwrap_or_default();
+
+        extra_headers.push((
+            SIGNATURE_HEADER.to_string(),
+            signature_header(signing_secrets, timestamp, &body),
+        ));
+    }
+
+    EndpointUrlAndData {
+        url,
+        data,
+        headers: HeaderMapPreset::ApplicationJson
+            .with_extra_headers(extra_headers)
+            .build(),
+        endpoint_type: EndpointType::POST,
+    }
+}
+
+/// Sent once to a newly registered url. The url only receives events after
+/// it answers with the challenge.
+#[derive(Serialize, Deserialize)]
+pub struct WebhookVerificationPayload {
+    pub event_type: String,
+    pub webhook_id: i32,
+    pub challenge: String,
+}
+
+impl WebhookVerificationPayload {
+    pub fn new(webhook_id: i32, challenge: String) -> Self {
+        Self {
+            event_type: "webhook_verification".to_string(),
+            webhook_id,
+            challenge,
+        }
+    }
+
+    pub fn to_signed_request(
+        &self,
+        webhook_url: String,
+        signing_secrets: &[&str],
+        timestamp: i64,
+    ) -> EndpointUrlAndData {
+        signed_post(
+            webhook_url,
+            serde_json::to_value(self).unwrap_or_default(),
+            format!("whverify_{}", self.webhook_id),
+            Vec::new(),
+            signing_secrets,
+            timestamp,
+        )
+    }
 }
 
 impl IntoHttpRequest for DefiRelayWebhookPayload {
@@ -38,6 +38,8 @@ pub struct RawTx {
 
 // Import ethers ABI component
 use ethers::abi::Abi;
+use ethers::abi::Token;
+use ethers::types::{Address, U256};
 use log::warn;
 use thiserror::Error;
 
@@ -54,6 +56,53 @@ pub enum RawTxError {
 }
  
 
+impl RawTx {
+    /// Builds the `createAndPayInvoice` call a payer submits to settle an invoice.
+    /// Invoices in the native token (zero address) carry the total as the tx value.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_and_pay_invoice(
+        chain_id: i64,
+        payspec_contract_address: Address,
+        nonce: U256,
+        token_address: Address,
+        total_amount_due: U256,
+        pay_to_array: &[Address],
+        pay_to_amounts: &[U256],
+        expected_uuid: [u8; 32],
+    ) -> Result<Self, RawTxError> {
+        let payspec_abi: Abi = serde_json::from_str(include_str!("../../abi/payspec.json"))
+            .map_err(|e| RawTxError::AbiLoadError(e.to_string()))?;
+
+        let function = payspec_abi
+            .function("createAndPayInvoice")
+            .map_err(|_| RawTxError::FunctionNotFound)?;
+
+        let calldata = function
+            .encode_input(&[
+                Token::Uint(nonce),
+                Token::Address(token_address),
+                Token::Uint(total_amount_due),
+                Token::Array(pay_to_array.iter().copied().map(Token::Address).collect()),
+                Token::Array(pay_to_amounts.iter().copied().map(Token::Uint).collect()),
+                Token::FixedBytes(expected_uuid.to_vec()),
+            ])
+            .map_err(|e| RawTxError::EncodingError(e.to_string()))?;
+
+        let value = if token_address.is_zero() {
+            Some(DomainUint256(total_amount_due))
+        } else {
+            None
+        };
+
+        Ok(Self {
+            chain_id,
+            to: DomainEthAddress(payspec_contract_address),
+            data: TransactionCalldata::new(calldata),
+            value,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct TransactionCalldata(pub Vec<u8>);
 
@@ -179,6 +228,39 @@ mod tests {
         assert_eq!(reserialized["data"], "0xdeadbeef");
     }
 
+    #[test]
+    fn test_create_and_pay_invoice_raw_tx() {
+        let contract: Address = "0x1082d89364765ED958c830F4E77D001837984E31"
+            .parse()
+            .unwrap();
+        let payee: Address = "0x5AEDA56215b167893e80B4fE645BA6d5Bab767DE"
+            .parse()
+            .unwrap();
+        let amount = U256::from(1000);
+
+        let raw_tx = RawTx::create_and_pay_invoice(
+            8453,
+            contract,
+            U256::one(),
+            Address::zero(),
+            amount,
+            &[payee],
+            &[amount],
+            [1u8; 32],
+        )
+        .unwrap();
+
+        // createAndPayInvoice(uint256,address,uint256,address[],uint256[],bytes32)
+        let selector = &ethers::utils::id(
+            "createAndPayInvoice(uint256,address,uint256,address[],uint256[],bytes32)",
+        );
+        assert_eq!(&raw_tx.data.as_bytes()[..4], selector);
+        assert_eq!(raw_tx.to.0, contract);
+
+        // native token invoices send the total as value
+        assert_eq!(raw_tx.value.map(|v| v.0), Some(amount));
+    }
+
     #[test]
     fn test_transaction_calldata_from_hex() {
         // Test valid hex with 0x prefix
@@ -1,3 +1,4 @@
+pub mod account_stream;
 pub mod alphanumeric;
  
 pub mod domains;
@@ -6,3 +7,7 @@ pub mod pagination;
 pub mod selected_record;
 
 pub mod defi_relay_webhook_payload;
+pub mod webhook_batches;
+pub mod webhook_events;
+pub mod webhook_scopes;
+pub mod webhook_templates;
@@ -0,0 +1,179 @@
+use crate::types::defi_relay_webhook_payload::signed_post;
+use crate::util::http_request::EndpointUrlAndData;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/*
+
+A webhook url can opt in to receiving its events in batches, for receivers that would
+rather take one request than thousands:
+
+  {"max_size": 50, "max_wait_secs": 30}
+
+Events wait until `max_size` of them are pending or the oldest has waited `max_wait_secs`,
+then go out together:
+
+  {"batch_id": "whbat_120", "webhook_id": 12, "events": [
+      {"id": "whevt_120", "body": {...}},
+      {"id": "whevt_121", "body": {...}}
+  ]}
+
+Each `body` is what the event would have been sent on its own. Any 2xx acknowledges every
+event, unless the response lists the ones it took, `{"acknowledged": ["whevt_120"]}`.
+Events left out are retried one by one on their own schedule, in later batches.
+
+*/
+
+/// Largest batch a url can ask for, which keeps an acknowledgement list within the
+/// stored response body
+pub const MAX_BATCH_SIZE: i32 = 100;
+pub const MAX_BATCH_WAIT_SECS: i32 = 3600;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum WebhookBatchingError {
+    #[error("max_size must be between 1 and {0}")]
+    InvalidSize(i32),
+
+    #[error("max_wait_secs must be between 0 and {0}")]
+    InvalidWait(i32),
+
+    #[error("Batching is only available for http urls")]
+    UnsupportedTarget,
+}
+
+/// When a url's pending events are sent together
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, ToSchema)]
+pub struct WebhookBatching {
+    pub max_size: i32,
+    /// How long the oldest pending event may wait for the batch to fill
+    pub max_wait_secs: i32,
+}
+
+impl WebhookBatching {
+    pub fn validate(self) -> Result<Self, WebhookBatchingError> {
+        if !(1..=MAX_BATCH_SIZE).contains(&self.max_size) {
+            return Err(WebhookBatchingError::InvalidSize(MAX_BATCH_SIZE));
+        }
+
+        if !(0..=MAX_BATCH_WAIT_SECS).contains(&self.max_wait_secs) {
+            return Err(WebhookBatchingError::InvalidWait(MAX_BATCH_WAIT_SECS));
+        }
+
+        Ok(self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct WebhookBatchEvent {
+    /// The event's `whevt_` id, as acknowledged in the response
+    pub id: String,
+    pub body: serde_json::Value,
+}
+
+/// The body of a batched request
+#[derive(Serialize, Deserialize, Clone, Debug, ToSchema)]
+pub struct WebhookBatchPayload {
+    /// Also sent as the event id header. A retried event goes out in a new batch.
+    pub batch_id: String,
+    pub webhook_id: i32,
+    pub events: Vec<WebhookBatchEvent>,
+}
+
+impl WebhookBatchPayload {
+    /// Named after its first event
+    pub fn new(webhook_id: i32, events: Vec<WebhookBatchEvent>) -> Self {
+        let batch_id = events
+            .first()
+            .map(|event| event.id.replacen("whevt_", "whbat_", 1))
+            .unwrap_or_default();
+
+        Self {
+            batch_id,
+            webhook_id,
+            events,
+        }
+    }
+
+    pub fn to_signed_request(
+        &self,
+        webhook_url: String,
+        custom_headers: Vec<(String, String)>,
+        signing_secrets: &[&str],
+        timestamp: i64,
+    ) -> EndpointUrlAndData {
+        signed_post(
+            webhook_url,
+            serde_json::to_value(self).unwrap_or_default(),
+            self.batch_id.clone(),
+            custom_headers,
+            signing_secrets,
+            timestamp,
+        )
+    }
+}
+
+#[derive(Deserialize)]
+struct WebhookBatchAck {
+    acknowledged: Vec<String>,
+}
+
+/// Event ids a receiver listed as taken, or None when its response does not list any
+pub fn acknowledged_event_ids(response_body: &str) -> Option<Vec<String>> {
+    serde_json::from_str::<WebhookBatchAck>(response_body)
+        .ok()
+        .map(|ack| ack.acknowledged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_batch_payload_and_acknowledgements() {
+        let batch = WebhookBatchPayload::new(
+            12,
+            vec![
+                WebhookBatchEvent {
+                    id: "whevt_120".to_string(),
+                    body: json!({"id": "whevt_120"}),
+                },
+                WebhookBatchEvent {
+                    id: "whevt_121".to_string(),
+                    body: json!({"id": "whevt_121"}),
+                },
+            ],
+        );
+        assert_eq!(batch.batch_id, "whbat_120");
+
+        let request = batch.to_signed_request(
+            "https://example.com/hook".to_string(),
+            Vec::new(),
+            &["secret"],
+            1_700_000_000,
+        );
+        assert_eq!(request.data["events"][1]["id"], "whevt_121");
+        assert_eq!(request.headers["x-defirelay-event-id"], "whbat_120");
+
+        assert_eq!(
+            acknowledged_event_ids(r#"{"acknowledged": ["whevt_120"]}"#),
+            Some(vec!["whevt_120".to_string()])
+        );
+        assert_eq!(acknowledged_event_ids("ok"), None);
+
+        assert!(WebhookBatching {
+            max_size: 50,
+            max_wait_secs: 30
+        }
+        .validate()
+        .is_ok());
+        assert_eq!(
+            WebhookBatching {
+                max_size: 500,
+                max_wait_secs: 30
+            }
+            .validate(),
+            Err(WebhookBatchingError::InvalidSize(MAX_BATCH_SIZE))
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use crate::db::postgres::models::api_key_model::ApiKeyEvent;
+use crate::db::postgres::models::invoices_model::{
+    Invoice, InvoiceCreatedEvent, InvoiceExpiredEvent,
+};
+use crate::db::postgres::models::payments_model::PaymentSummary;
+use crate::db::postgres::models::refill::api_credit_refills_model::CreditRefillAppliedEvent;
+use crate::db::postgres::models::webhook_urls_model::WebhookUrlDisabledEvent;
+use crate::types::defi_relay_webhook_payload::DefiRelayWebhookPayload;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::openapi::{RefOr, Schema};
+use utoipa::ToSchema;
+
+/*
+
+Webhook bodies are versioned. A webhook url is pinned to an api_version when it is created
+and keeps receiving that shape until its owner moves it to another one.
+
+  v0   the original body: trigger and webhook ids, `event_type` and untyped `event_data`
+  v1   an envelope around a typed `data` object:
+
+  {
+    "id": "whevt_123",
+    "type": "payment_summary",
+    "data": { "invoice_uuid": "0x...", "chain_id": 8453, ... },
+    "api_version": "v1",
+    "created_at": 1700000000,
+    "webhook_id": 12,
+    "redelivery": false
+  }
+
+`webhook_json_schema` describes the body of each version, and `cargo run --bin apigen`
+writes them to webhook_schemas/.
+
+*/
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum WebhookEventError {
+    #[error("Unknown api version {0}, expected one of v0, v1")]
+    UnknownApiVersion(String),
+
+    #[error("Unknown event type {0}")]
+    UnknownEventType(String),
+
+    #[error("Stored {event_type} event data is unreadable: {message}")]
+    InvalidEventData { event_type: String, message: String },
+}
+
+/// Shape of the webhook bodies a url receives
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookApiVersion {
+    /// Urls and bodies from before versioning
+    #[default]
+    V0,
+    V1,
+}
+
+impl WebhookApiVersion {
+    pub const ALL: [WebhookApiVersion; 2] = [WebhookApiVersion::V0, WebhookApiVersion::V1];
+
+    /// What new webhook urls are pinned to
+    pub const LATEST: WebhookApiVersion = WebhookApiVersion::V1;
+}
+
+impl std::fmt::Display for WebhookApiVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let version = match self {
+            WebhookApiVersion::V0 => "v0",
+            WebhookApiVersion::V1 => "v1",
+        };
+
+        write!(f, "{}", version)
+    }
+}
+
+impl FromStr for WebhookApiVersion {
+    type Err = WebhookEventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "v0" => Ok(WebhookApiVersion::V0),
+            "v1" => Ok(WebhookApiVersion::V1),
+            other => Err(WebhookEventError::UnknownApiVersion(other.to_string())),
+        }
+    }
+}
+
+/// Body of every v1 webhook request
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct WebhookEventV1 {
+    /// Same for every retry and redelivery of an event, use it to drop duplicates
+    pub id: String,
+    #[serde(flatten)]
+    pub event: WebhookEventDataV1,
+    pub api_version: WebhookApiVersion,
+    /// Unix seconds when the event happened
+    pub created_at: i64,
+    pub webhook_id: i32,
+    /// True when the owner asked for this event to be sent again
+    pub redelivery: bool,
+}
+
+/// `type` names the event and decides the shape of `data`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum WebhookEventDataV1 {
+    PaymentSummary(Box<PaymentEventV1>),
+    InvoiceCreated(Box<InvoiceEventV1>),
+    InvoiceExpired(Box<InvoiceEventV1>),
+    CreditRefillApplied(CreditRefillEventV1),
+    ApiKeyCreated(ApiKeyEventV1),
+    ApiKeyDeleted(ApiKeyEventV1),
+    WebhookUrlDisabled(WebhookUrlDisabledEventV1),
+}
+
+/// A payment of an invoice. Amounts are decimal strings in the token's smallest unit,
+/// with `_formatted` counterparts in whole tokens when the token is known.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct PaymentEventV1 {
+    pub invoice_uuid: String,
+    pub chain_id: i64,
+    pub contract_address: String,
+    pub payer_address: String,
+    pub nonce: String,
+    pub token: PaymentTokenV1,
+    pub total_amount: String,
+    pub total_amount_formatted: Option<String>,
+    pub recipients: Vec<PaymentRecipientV1>,
+    pub transaction_hash: String,
+    pub block_number: Option<i64>,
+    pub block_hash: Option<String>,
+    /// Unix seconds
+    pub block_timestamp: Option<i64>,
+    /// detected, confirmed, finalized or reorged
+    pub status: String,
+    pub confirmations: i64,
+    /// `total_amount` in USD at the time of the payment
+    pub usd_value: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct PaymentTokenV1 {
+    pub address: String,
+    /// Null for tokens that are not in the registry
+    pub symbol: Option<String>,
+    pub decimals: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct PaymentRecipientV1 {
+    pub address: String,
+    pub amount: String,
+    pub amount_formatted: Option<String>,
+}
+
+/// An invoice of the owner, as it was when the event happened
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct InvoiceEventV1 {
+    pub invoice_uuid: String,
+    pub chain_id: i64,
+    pub owner_address: String,
+    pub contract_address: String,
+    pub token_address: String,
+    pub nonce: String,
+    pub total_amount: String,
+    pub recipients: Vec<PaymentRecipientV1>,
+    /// draft, created, paid or expired
+    pub status: String,
+    pub template_uuid: Option<String>,
+    /// The payer a template invoice was stamped out for
+    pub created_for_address: Option<String>,
+    pub metadata: Option<serde_json::Value>,
+    /// Unix seconds
+    pub expires_at: Option<i64>,
+}
+
+/// Api credits bought for one of the owner's workspaces were paid for
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct CreditRefillEventV1 {
+    pub invoice_uuid: String,
+    pub workspace_uuid: String,
+    pub client_address: String,
+    pub payment_token_address: String,
+    /// In the token's smallest unit
+    pub payment_amount: String,
+    /// Unix seconds when the refill was requested
+    pub created_at: i64,
+}
+
+/// An api key of the owner. Only the last characters of the key are ever sent.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct ApiKeyEventV1 {
+    pub owner_address: String,
+    /// e.g. "...3f2a"
+    pub key_hint: String,
+    pub name: Option<String>,
+    /// Unix seconds
+    pub created_at: i64,
+}
+
+/// One of the owner's webhook urls stopped receiving events, see `/api/webhooks/enable`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+pub struct WebhookUrlDisabledEventV1 {
+    pub webhook_id: i32,
+    pub webhook_url: String,
+    /// consecutive_failures or gone
+    pub reason: String,
+    pub consecutive_failures: i32,
+    /// Unix seconds
+    pub disabled_at: i64,
+}
+
+impl From<&PaymentSummary> for PaymentEventV1 {
+    fn from(payment: &PaymentSummary) -> Self {
+        let amounts_formatted = payment.pay_to_amounts_formatted.as_ref();
+
+        let recipients = payment
+            .pay_to_array
+            .0
+            .iter()
+            .zip(payment.pay_to_amounts.0.iter())
+            .enumerate()
+            .map(|(i, (address, amount))| PaymentRecipientV1 {
+                address: format!("{:?}", address),
+                amount: amount.to_string(),
+                amount_formatted: amounts_formatted.and_then(|amounts| amounts.get(i).cloned()),
+            })
+            .collect();
+
+        Self {
+            invoice_uuid: payment.uuid.to_hex(),
+            chain_id: payment.chain_id,
+            contract_address: payment.payspec_contract_address.to_string_full(),
+            payer_address: payment.from_address.to_string_full(),
+            nonce: payment.nonce.0.to_string(),
+            token: PaymentTokenV1 {
+                address: payment.payment_token_address.to_string_full(),
+                symbol: payment.token.as_ref().map(|token| token.symbol.clone()),
+                decimals: payment.token.as_ref().map(|token| token.decimals),
+            },
+            total_amount: payment.total_amount.0.to_string(),
+            total_amount_formatted: payment.total_amount_formatted.clone(),
+            recipients,
+            transaction_hash: format!("0x{:x}", payment.transaction_hash.0),
+            block_number: payment.payment_at_block,
+            block_hash: payment
+                .payment_at_block_hash
+                .as_ref()
+                .map(|hash| format!("0x{:x}", hash.0)),
+            block_timestamp: payment
+                .payment_at_block_timestamp
+                .as_ref()
+                .map(|timestamp| timestamp.0.timestamp()),
+            status: payment.status.to_string(),
+            confirmations: payment.confirmations,
+            usd_value: payment.usd_value.as_ref().map(|value| value.0.to_string()),
+        }
+    }
+}
+
+impl From<&Invoice> for InvoiceEventV1 {
+    fn from(invoice: &Invoice) -> Self {
+        let recipients = invoice
+            .pay_to_array
+            .0
+            .iter()
+            .zip(invoice.pay_to_amounts.0.iter())
+            .map(|(address, amount)| PaymentRecipientV1 {
+                address: format!("{:?}", address),
+                amount: amount.to_string(),
+                amount_formatted: None,
+            })
+            .collect();
+
+        Self {
+            invoice_uuid: invoice.uuid.to_hex(),
+            chain_id: invoice.chain_id,
+            owner_address: invoice.owner_address.to_string_full(),
+            contract_address: invoice.contract_address.to_string_full(),
+            token_address: invoice.token_address.to_string_full(),
+            nonce: invoice.nonce.0.to_string(),
+            total_amount: invoice.total_amount.0.to_string(),
+            recipients,
+            status: invoice.status.to_string(),
+            template_uuid: invoice.template_uuid.as_ref().map(|uuid| uuid.to_hex()),
+            created_for_address: invoice
+                .created_for_address
+                .as_ref()
+                .map(|address| address.to_string_full()),
+            metadata: invoice.metadata.as_ref().map(|metadata| metadata.0.clone()),
+            expires_at: invoice
+                .expires_at
+                .as_ref()
+                .map(|expires_at| expires_at.0.timestamp()),
+        }
+    }
+}
+
+impl From<&CreditRefillAppliedEvent> for CreditRefillEventV1 {
+    fn from(event: &CreditRefillAppliedEvent) -> Self {
+        let refill = &event.refill;
+
+        Self {
+            invoice_uuid: refill.invoice_uuid.clone(),
+            workspace_uuid: refill.workspace_uuid.to_hex(),
+            client_address: refill.client_address.to_string_full(),
+            payment_token_address: refill.payment_token_address.to_string_full(),
+            payment_amount: refill.payment_amount_raw.0.to_string(),
+            created_at: refill.created_at.timestamp(),
+        }
+    }
+}
+
+impl From<&ApiKeyEvent> for ApiKeyEventV1 {
+    fn from(event: &ApiKeyEvent) -> Self {
+        Self {
+            owner_address: event.owner_wallet_address.to_string_full(),
+            key_hint: event.key_hint.clone(),
+            name: event.name.clone(),
+            created_at: event.created_at.timestamp(),
+        }
+    }
+}
+
+impl From<&WebhookUrlDisabledEvent> for WebhookUrlDisabledEventV1 {
+    fn from(event: &WebhookUrlDisabledEvent) -> Self {
+        Self {
+            webhook_id: event.webhook_id,
+            webhook_url: event.webhook_url.clone(),
+            reason: event.reason.clone(),
+            consecutive_failures: event.consecutive_failures,
+            disabled_at: event.disabled_at.timestamp(),
+        }
+    }
+}
+
+fn read_event_data<T: serde::de::DeserializeOwned>(
+    event_type: &str,
+    event_data: &serde_json::Value,
+) -> Result<T, WebhookEventError> {
+    serde_json::from_value(event_data.clone()).map_err(|e| WebhookEventError::InvalidEventData {
+        event_type: event_type.to_string(),
+        message: e.to_string(),
+    })
+}
+
+impl WebhookEventDataV1 {
+    /// Types the event data stored on a webhook trigger
+    pub fn from_stored(
+        event_type: &str,
+        event_data: &serde_json::Value,
+    ) -> Result<Self, WebhookEventError> {
+        match event_type {
+            "payment_summary" => {
+                let payment: PaymentSummary = read_event_data(event_type, event_data)?;
+                Ok(Self::PaymentSummary(Box::new((&payment).into())))
+            }
+            "invoice_created" => {
+                let event: InvoiceCreatedEvent = read_event_data(event_type, event_data)?;
+                Ok(Self::InvoiceCreated(Box::new((&event.invoice).into())))
+            }
+            "invoice_expired" => {
+                let event: InvoiceExpiredEvent = read_event_data(event_type, event_data)?;
+                Ok(Self::InvoiceExpired(Box::new((&event.invoice).into())))
+            }
+            "credit_refill_applied" => {
+                let event: CreditRefillAppliedEvent = read_event_data(event_type, event_data)?;
+                Ok(Self::CreditRefillApplied((&event).into()))
+            }
+            "api_key_created" => {
+                let event: ApiKeyEvent = read_event_data(event_type, event_data)?;
+                Ok(Self::ApiKeyCreated((&event).into()))
+            }
+            "api_key_deleted" => {
+                let event: ApiKeyEvent = read_event_data(event_type, event_data)?;
+                Ok(Self::ApiKeyDeleted((&event).into()))
+            }
+            "webhook_url_disabled" => {
+                let event: WebhookUrlDisabledEvent = read_event_data(event_type, event_data)?;
+                Ok(Self::WebhookUrlDisabled((&event).into()))
+            }
+            other => Err(WebhookEventError::UnknownEventType(other.to_string())),
+        }
+    }
+}
+
+impl WebhookEventV1 {
+    pub fn from_payload(payload: &DefiRelayWebhookPayload) -> Result<Self, WebhookEventError> {
+        let event_type = payload.event_type.as_deref().unwrap_or_default();
+        let event_data = payload
+            .event_data
+            .as_ref()
+            .map(|data| data.0.clone())
+            .unwrap_or_default();
+
+        Ok(Self {
+            id: payload.event_id(),
+            event: WebhookEventDataV1::from_stored(event_type, &event_data)?,
+            api_version: WebhookApiVersion::V1,
+            created_at: payload
+                .created_at
+                .unwrap_or_else(chrono::Utc::now)
+                .timestamp(),
+            webhook_id: payload.webhook_id,
+            redelivery: payload.redelivery,
+        })
+    }
+}
+
+fn root_schema<T: ToSchema>() -> (RefOr<Schema>, Vec<(String, RefOr<Schema>)>) {
+    let mut definitions = Vec::new();
+    T::schemas(&mut definitions);
+
+    (T::schema(), definitions)
+}
+
+/// JSON Schema (draft 2020-12) for the body of a webhook request in `version`
+pub fn webhook_json_schema(version: WebhookApiVersion) -> serde_json::Value {
+    let (root, definitions) = match version {
+        WebhookApiVersion::V0 => root_schema::<DefiRelayWebhookPayload>(),
+        WebhookApiVersion::V1 => root_schema::<WebhookEventV1>(),
+    };
+
+    let mut schema = serde_json::to_value(root).unwrap_or_default();
+
+    if let Some(object) = schema.as_object_mut() {
+        object.insert(
+            "$schema".to_string(),
+            "https://json-schema.org/draft/2020-12/schema".into(),
+        );
+        object.insert(
+            "title".to_string(),
+            format!("DefiRelay webhook {}", version).into(),
+        );
+
+        let defs: serde_json::Map<String, serde_json::Value> = definitions
+            .into_iter()
+            .map(|(name, schema)| (name, serde_json::to_value(schema).unwrap_or_default()))
+            .collect();
+
+        if !defs.is_empty() {
+            object.insert("$defs".to_string(), defs.into());
+        }
+    }
+
+    // utoipa writes OpenAPI component references
+    let schema = schema
+        .to_string()
+        .replace("#/components/schemas/", "#/$defs/");
+
+    serde_json::from_str(&schema).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::postgres::models::api_key_model::ApiKey;
+    use crate::db::postgres::models::domain_events_model::DomainEvent;
+    use crate::types::domains::eth_address::DomainEthAddress;
+    use crate::types::domains::json::DomainJson;
+
+    #[test]
+    fn test_payment_envelope_v1() {
+        let payment = PaymentSummary::generate_test_payment_summary();
+
+        let payload = DefiRelayWebhookPayload {
+            webhook_trigger_id: 7,
+            webhook_id: 3,
+            webhook_url: "https://example.com/hook".to_string(),
+            event_type: Some("payment_summary".to_string()),
+            event_data: Some(DomainJson(serde_json::to_value(&payment).unwrap())),
+            redelivery: false,
+            api_version: WebhookApiVersion::V1,
+            created_at: None,
+            payload_template: None,
+            custom_headers: Default::default(),
+        };
+
+        let body = serde_json::to_value(WebhookEventV1::from_payload(&payload).unwrap()).unwrap();
+
+        assert_eq!(body["id"], "whevt_7");
+        assert_eq!(body["type"], "payment_summary");
+        assert_eq!(body["api_version"], "v1");
+        assert_eq!(body["data"]["total_amount"], "150000000");
+        assert_eq!(body["data"]["recipients"][1]["amount"], "50000000");
+        assert_eq!(
+            body["data"]["recipients"][0]["address"],
+            "0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa"
+        );
+
+        let unknown = DefiRelayWebhookPayload {
+            event_type: Some("refund".to_string()),
+            ..payload
+        };
+        assert_eq!(
+            WebhookEventV1::from_payload(&unknown),
+            Err(WebhookEventError::UnknownEventType("refund".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_api_key_event_v1_hides_key() {
+        let api_key = ApiKey::new(
+            DomainEthAddress(ethers::types::Address::zero()),
+            Some("ci".to_string()),
+            None,
+        );
+        let event = ApiKeyEvent::new(&api_key, true);
+
+        let stored = DomainEvent::new(&event, vec![api_key.owner_wallet_address.clone()]);
+        assert_eq!(stored.event_type, "api_key_deleted");
+        assert!(!stored.payload.0.to_string().contains(&api_key.apikey));
+
+        let data = WebhookEventDataV1::from_stored(&stored.event_type, &stored.payload.0).unwrap();
+        let WebhookEventDataV1::ApiKeyDeleted(data) = data else {
+            panic!("expected api_key_deleted, got {:?}", data);
+        };
+
+        assert_eq!(data.key_hint, format!("...{}", &api_key.apikey[28..]));
+        assert_eq!(data.name.as_deref(), Some("ci"));
+    }
+
+    #[test]
+    fn test_invoice_expired_event_v1() {
+        use crate::db::postgres::models::invoices_model::InvoiceStatus;
+        use crate::types::domains::datetime::DomainDatetime;
+        use crate::types::domains::pay_to_amounts::DomainPayToAmounts;
+        use crate::types::domains::pay_to_array::DomainPayToArray;
+        use crate::types::domains::uint256::DomainUint256;
+        use ethers::types::{Address, U256};
+
+        let mut invoice = Invoice::new(
+            DomainEthAddress(Address::random()),
+            8453,
+            DomainEthAddress(Address::random()),
+            DomainEthAddress(Address::random()),
+            DomainPayToArray(vec![Address::random()]),
+            DomainPayToAmounts(vec![U256::from(1000)]),
+            DomainUint256(U256::one()),
+            None,
+        )
+        .unwrap();
+        invoice.status = InvoiceStatus::Expired;
+        invoice.expires_at = chrono::DateTime::from_timestamp(1700000000, 0).map(DomainDatetime);
+
+        let event = InvoiceExpiredEvent {
+            invoice: invoice.clone(),
+        };
+        let stored = DomainEvent::new(&event, vec![invoice.owner_address.clone()]);
+        assert_eq!(stored.event_type, "invoice_expired");
+
+        let data = WebhookEventDataV1::from_stored(&stored.event_type, &stored.payload.0).unwrap();
+        let WebhookEventDataV1::InvoiceExpired(data) = data else {
+            panic!("expected invoice_expired, got {:?}", data);
+        };
+        assert_eq!(data.status, "expired");
+        assert_eq!(data.expires_at, Some(1700000000));
+
+        // Invoices stored before expiry existed have no expires_at
+        let mut created = serde_json::to_value(&invoice).unwrap();
+        created.as_object_mut().unwrap().remove("expires_at");
+        let data = WebhookEventDataV1::from_stored("invoice_created", &created).unwrap();
+        let WebhookEventDataV1::InvoiceCreated(data) = data else {
+            panic!("expected invoice_created, got {:?}", data);
+        };
+        assert_eq!(data.expires_at, None);
+    }
+
+    #[test]
+    fn test_json_schema_per_version() {
+        for version in WebhookApiVersion::ALL {
+            let schema = webhook_json_schema(version);
+
+            assert_eq!(
+                schema["$schema"],
+                "https://json-schema.org/draft/2020-12/schema"
+            );
+            assert!(!schema.to_string().contains("#/components/schemas/"));
+        }
+
+        let v1 = webhook_json_schema(WebhookApiVersion::V1).to_string();
+        assert!(v1.contains("PaymentEventV1"));
+        assert!(v1.contains("\"webhook_url_disabled\""));
+        assert!(v1.contains("\"invoice_created\""));
+        assert!(v1.contains("\"invoice_expired\""));
+    }
+}
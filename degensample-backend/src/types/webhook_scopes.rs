@@ -0,0 +1,304 @@
+use crate::db::postgres::models::invoices_model::Invoice;
+use crate::db::postgres::models::payments_model::PaymentSummary;
+use crate::types::domains::bytes32::DomainBytes32;
+use crate::types::domains::decimal::DomainDecimal;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::types::domains::json::DomainJson;
+use log::warn;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use utoipa::ToSchema;
+
+/*
+
+Scopes decide which events a webhook url is sent. Every field that is set must match,
+and a field matches when the event has any of the listed values. Empty scopes match everything.
+
+  {"chain_ids": [8453], "token_addresses": ["0x8335...2913"]}                 Base USDC only
+  {"exclude": {"chain_ids": [8453], "token_addresses": ["0x8335...2913"]}}    everything else
+  {"event_types": ["payment_summary"], "min_amount": "100"}                    payments of 100 tokens or more
+  {"event_types": ["invoice_created", "api_key_deleted"]}                     only those two events
+
+*/
+
+/// Event types a webhook url can be scoped to
+pub const KNOWN_EVENT_TYPES: &[&str] = &[
+    "payment_summary",
+    "invoice_created",
+    "invoice_expired",
+    "credit_refill_applied",
+    "api_key_created",
+    "api_key_deleted",
+    "webhook_url_disabled",
+];
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum WebhookScopesError {
+    #[error("Invalid scopes: {0}")]
+    Malformed(String),
+
+    #[error("Unknown event type {0}")]
+    UnknownEventType(String),
+
+    #[error("min_amount cannot be negative")]
+    NegativeMinAmount,
+
+    #[error("exclude cannot be empty or nested")]
+    InvalidExclude,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookScopes {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub event_types: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chain_ids: Vec<i64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_addresses: Vec<DomainEthAddress>,
+
+    /// In whole token units, e.g. "100.5". Events whose token is not in the registry never match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_amount: Option<DomainDecimal>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invoice_uuids: Vec<DomainBytes32>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub template_uuids: Vec<DomainBytes32>,
+
+    /// Events matching these scopes are not sent, even if they match the rest
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(no_recursion)]
+    pub exclude: Option<Box<WebhookScopes>>,
+}
+
+/// What scopes are evaluated against. Fields an event does not have are None,
+/// and a scope on such a field never matches.
+#[derive(Debug, Default)]
+pub struct ScopedEvent<'a> {
+    pub event_type: &'a str,
+    pub chain_id: Option<i64>,
+    pub token_address: Option<&'a DomainEthAddress>,
+    pub amount: Option<Decimal>,
+    pub invoice_uuid: Option<&'a DomainBytes32>,
+    pub template_uuid: Option<&'a DomainBytes32>,
+}
+
+impl<'a> ScopedEvent<'a> {
+    pub fn from_payment(
+        payment: &'a PaymentSummary,
+        template_uuid: Option<&'a DomainBytes32>,
+    ) -> Self {
+        Self {
+            event_type: "payment_summary",
+            chain_id: Some(payment.chain_id),
+            token_address: Some(&payment.payment_token_address),
+            amount: payment
+                .total_amount_formatted
+                .as_deref()
+                .and_then(|amount| Decimal::from_str(amount).ok()),
+            invoice_uuid: Some(&payment.uuid),
+            template_uuid,
+        }
+    }
+
+    /// Invoices only hold raw token units, so a `min_amount` scope never matches them
+    pub fn from_invoice(event_type: &'a str, invoice: &'a Invoice) -> Self {
+        Self {
+            event_type,
+            chain_id: Some(invoice.chain_id),
+            token_address: Some(&invoice.token_address),
+            amount: None,
+            invoice_uuid: Some(&invoice.uuid),
+            template_uuid: invoice.template_uuid.as_ref(),
+        }
+    }
+
+    /// Events that only carry their type, so any other scope filters them out
+    pub fn untyped(event_type: &'a str) -> Self {
+        Self {
+            event_type,
+            ..Default::default()
+        }
+    }
+}
+
+fn matches_any<T: PartialEq>(allowed: &[T], value: Option<&T>) -> bool {
+    allowed.is_empty() || value.is_some_and(|value| allowed.contains(value))
+}
+
+impl WebhookScopes {
+    /// Parses and validates scopes sent by a client
+    pub fn from_json(value: serde_json::Value) -> Result<Self, WebhookScopesError> {
+        let scopes: Self = serde_json::from_value(value)
+            .map_err(|e| WebhookScopesError::Malformed(e.to_string()))?;
+
+        scopes.validate()?;
+
+        if let Some(exclude) = &scopes.exclude {
+            if exclude.exclude.is_some() || **exclude == Self::default() {
+                return Err(WebhookScopesError::InvalidExclude);
+            }
+            exclude.validate()?;
+        }
+
+        Ok(scopes)
+    }
+
+    /// Scopes as stored on a webhook url. Urls created before scopes were typed may hold
+    /// arbitrary JSON; those keep receiving every event, as they always have.
+    pub fn from_stored(scopes: Option<&DomainJson>) -> Self {
+        let Some(scopes) = scopes else {
+            return Self::default();
+        };
+
+        Self::from_json(scopes.0.clone()).unwrap_or_else(|e| {
+            warn!("ignoring unreadable webhook scopes: {}", e);
+            Self::default()
+        })
+    }
+
+    fn validate(&self) -> Result<(), WebhookScopesError> {
+        if let Some(unknown) = self
+            .event_types
+            .iter()
+            .find(|event_type| !KNOWN_EVENT_TYPES.contains(&event_type.as_str()))
+        {
+            return Err(WebhookScopesError::UnknownEventType(unknown.clone()));
+        }
+
+        if self
+            .min_amount
+            .as_ref()
+            .is_some_and(|min_amount| min_amount.0.is_sign_negative())
+        {
+            return Err(WebhookScopesError::NegativeMinAmount);
+        }
+
+        Ok(())
+    }
+
+    /// Whether a webhook url with these scopes should be sent `event`
+    pub fn matches(&self, event: &ScopedEvent) -> bool {
+        if self
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.matches_fields(event))
+        {
+            return false;
+        }
+
+        self.matches_fields(event)
+    }
+
+    fn matches_fields(&self, event: &ScopedEvent) -> bool {
+        let event_type = event.event_type.to_string();
+
+        matches_any(&self.event_types, Some(&event_type))
+            && matches_any(&self.chain_ids, event.chain_id.as_ref())
+            && matches_any(&self.token_addresses, event.token_address)
+            && matches_any(&self.invoice_uuids, event.invoice_uuid)
+            && matches_any(&self.template_uuids, event.template_uuid)
+            && self
+                .min_amount
+                .as_ref()
+                .is_none_or(|min_amount| event.amount.is_some_and(|amount| amount >= min_amount.0))
+    }
+
+    /// Whether evaluating these scopes needs the template an invoice was created from
+    pub fn uses_templates(&self) -> bool {
+        !self.template_uuids.is_empty()
+            || self
+                .exclude
+                .as_ref()
+                .is_some_and(|exclude| exclude.uses_templates())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const BASE_USDC: &str = "0x833589fcd6edb6e08f4c7c32d4f71b54bda02913";
+
+    fn event<'a>(chain_id: i64, token: &'a DomainEthAddress, amount: &str) -> ScopedEvent<'a> {
+        ScopedEvent {
+            event_type: "payment_summary",
+            chain_id: Some(chain_id),
+            token_address: Some(token),
+            amount: Decimal::from_str(amount).ok(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_base_usdc_and_everything_else() {
+        let base_usdc =
+            WebhookScopes::from_json(json!({"chain_ids": [8453], "token_addresses": [BASE_USDC]}))
+                .unwrap();
+        let everything_else = WebhookScopes::from_json(
+            json!({"exclude": {"chain_ids": [8453], "token_addresses": [BASE_USDC]}}),
+        )
+        .unwrap();
+
+        let usdc = DomainEthAddress(BASE_USDC.parse().unwrap());
+        let other_token = DomainEthAddress(ethers::types::Address::zero());
+
+        let usdc_on_base = event(8453, &usdc, "10");
+        let other_on_base = event(8453, &other_token, "10");
+        let usdc_elsewhere = event(1, &usdc, "10");
+
+        assert!(base_usdc.matches(&usdc_on_base));
+        assert!(!everything_else.matches(&usdc_on_base));
+
+        for other in [&other_on_base, &usdc_elsewhere] {
+            assert!(!base_usdc.matches(other));
+            assert!(everything_else.matches(other));
+        }
+    }
+
+    #[test]
+    fn test_min_amount_and_validation() {
+        let scopes = WebhookScopes::from_json(json!({"min_amount": "100"})).unwrap();
+        let token = DomainEthAddress(ethers::types::Address::zero());
+
+        assert!(scopes.matches(&event(1, &token, "100")));
+        assert!(!scopes.matches(&event(1, &token, "99.99")));
+        assert!(!scopes.matches(&ScopedEvent {
+            event_type: "payment_summary",
+            ..Default::default()
+        }));
+
+        assert_eq!(
+            WebhookScopes::from_json(json!({"event_types": ["refund"]})),
+            Err(WebhookScopesError::UnknownEventType("refund".to_string()))
+        );
+        assert_eq!(
+            WebhookScopes::from_json(json!({"exclude": {}})),
+            Err(WebhookScopesError::InvalidExclude)
+        );
+        assert!(matches!(
+            WebhookScopes::from_json(json!({"chains": [1]})),
+            Err(WebhookScopesError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_events_without_payment_fields() {
+        let created_only =
+            WebhookScopes::from_json(json!({"event_types": ["api_key_created"]})).unwrap();
+        let base_only = WebhookScopes::from_json(json!({"chain_ids": [8453]})).unwrap();
+
+        let api_key_created = ScopedEvent::untyped("api_key_created");
+
+        assert!(created_only.matches(&api_key_created));
+        assert!(!created_only.matches(&ScopedEvent::untyped("api_key_deleted")));
+        assert!(!base_only.matches(&api_key_created));
+        assert!(WebhookScopes::default().matches(&api_key_created));
+    }
+}
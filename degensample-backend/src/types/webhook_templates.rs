@@ -0,0 +1,490 @@
+use crate::types::domains::json::DomainJson;
+use crate::types::webhook_events::{WebhookEventDataV1, WebhookEventV1};
+use log::warn;
+use reqwest::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+/*
+
+A webhook url can reshape the body it is sent, for receivers such as chat tools that
+expect their own JSON instead of a DefiRelay payload.
+
+  {"preset": "slack"}       a Slack incoming-webhook message with blocks
+  {"preset": "discord"}     a Discord webhook message with one embed
+  {"custom": {"text": "{{type}}: {{data.total_amount_formatted}} {{data.token.symbol}}",
+              "amount": "{{data.total_amount}}"}}
+
+Templates are rendered over the v1 event (see webhook_events.rs), whatever the url's api
+version. A placeholder is a dotted path into it, with numbers indexing arrays, e.g.
+`data.recipients.0.address`. A string that is a single placeholder is replaced by the value
+itself, keeping its JSON type; elsewhere values are written into the string, and paths the
+event does not have render as null or as nothing.
+
+Custom headers are sent with every request, for receivers that want a token of their own.
+Headers the relay sets itself cannot be overridden.
+
+*/
+
+/// Largest accepted custom template, as JSON
+const MAX_TEMPLATE_BYTES: usize = 16 * 1024;
+
+const MAX_CUSTOM_HEADERS: usize = 10;
+const MAX_HEADER_VALUE_LEN: usize = 1024;
+
+const RESERVED_HEADERS: &[&str] = &[
+    "host",
+    "content-type",
+    "content-length",
+    "transfer-encoding",
+    "connection",
+    "accept",
+];
+const RESERVED_HEADER_PREFIX: &str = "x-defirelay-";
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum WebhookTemplateError {
+    #[error("Invalid payload template: {0}")]
+    Malformed(String),
+
+    #[error("Payload template is larger than {0} bytes")]
+    TooLarge(usize),
+
+    #[error("Unclosed placeholder in \"{0}\"")]
+    UnclosedPlaceholder(String),
+
+    #[error("At most {0} custom headers are allowed")]
+    TooManyHeaders(usize),
+
+    #[error("Invalid custom header {0}")]
+    InvalidHeader(String),
+
+    #[error("Header {0} is set by the relay and cannot be overridden")]
+    ReservedHeader(String),
+}
+
+/// Built-in bodies for chat tools
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookTemplatePreset {
+    Slack,
+    Discord,
+}
+
+/// Replaces the body a webhook url is sent
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookPayloadTemplate {
+    Preset(WebhookTemplatePreset),
+    /// Any JSON, whose strings may hold `{{path}}` placeholders into the v1 event
+    Custom(Value),
+}
+
+impl WebhookPayloadTemplate {
+    /// Parses and validates a template sent by a client
+    pub fn from_json(value: Value) -> Result<Self, WebhookTemplateError> {
+        if value.to_string().len() > MAX_TEMPLATE_BYTES {
+            return Err(WebhookTemplateError::TooLarge(MAX_TEMPLATE_BYTES));
+        }
+
+        let template: Self = serde_json::from_value(value)
+            .map_err(|e| WebhookTemplateError::Malformed(e.to_string()))?;
+
+        if let Self::Custom(body) = &template {
+            check_placeholders(body)?;
+        }
+
+        Ok(template)
+    }
+
+    /// The template stored on a webhook url. An unreadable one is ignored, so the url
+    /// falls back to the body of its api version rather than receiving nothing.
+    pub fn from_stored(template: Option<&DomainJson>) -> Option<Self> {
+        let template = template?;
+
+        Self::from_json(template.0.clone())
+            .inspect_err(|e| warn!("ignoring unreadable payload template: {}", e))
+            .ok()
+    }
+
+    pub fn render(&self, event: &WebhookEventV1) -> Value {
+        match self {
+            Self::Preset(WebhookTemplatePreset::Slack) => slack_body(event),
+            Self::Preset(WebhookTemplatePreset::Discord) => discord_body(event),
+            Self::Custom(body) => {
+                render_value(body, &serde_json::to_value(event).unwrap_or_default())
+            }
+        }
+    }
+}
+
+/// Extra request headers of a webhook url, by name
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, ToSchema)]
+#[serde(transparent)]
+pub struct WebhookCustomHeaders(pub BTreeMap<String, String>);
+
+impl WebhookCustomHeaders {
+    /// Checks headers sent by a client. Names are stored lowercase.
+    pub fn new(headers: BTreeMap<String, String>) -> Result<Self, WebhookTemplateError> {
+        if headers.len() > MAX_CUSTOM_HEADERS {
+            return Err(WebhookTemplateError::TooManyHeaders(MAX_CUSTOM_HEADERS));
+        }
+
+        let mut checked = BTreeMap::new();
+
+        for (name, value) in headers {
+            let name = name.trim().to_lowercase();
+
+            if RESERVED_HEADERS.contains(&name.as_str()) || name.starts_with(RESERVED_HEADER_PREFIX)
+            {
+                return Err(WebhookTemplateError::ReservedHeader(name));
+            }
+
+            if HeaderName::from_bytes(name.as_bytes()).is_err()
+                || HeaderValue::from_str(&value).is_err()
+                || value.len() > MAX_HEADER_VALUE_LEN
+            {
+                return Err(WebhookTemplateError::InvalidHeader(name));
+            }
+
+            checked.insert(name, value);
+        }
+
+        Ok(Self(checked))
+    }
+
+    /// Headers as stored on a webhook url. Unreadable ones are dropped.
+    pub fn from_stored(headers: Option<&DomainJson>) -> Self {
+        let Some(headers) = headers else {
+            return Self::default();
+        };
+
+        serde_json::from_value(headers.0.clone())
+            .map_err(|e| e.to_string())
+            .and_then(|headers| Self::new(headers).map_err(|e| e.to_string()))
+            .unwrap_or_else(|e| {
+                warn!("ignoring unreadable custom headers: {}", e);
+                Self::default()
+            })
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.0.keys().cloned().collect()
+    }
+
+    pub fn to_pairs(&self) -> Vec<(String, String)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+}
+
+fn check_placeholders(template: &Value) -> Result<(), WebhookTemplateError> {
+    match template {
+        Value::String(text) => {
+            let mut rest = text.as_str();
+
+            while let Some(start) = rest.find("{{") {
+                let after = &rest[start + 2..];
+
+                let Some(end) = after.find("}}") else {
+                    return Err(WebhookTemplateError::UnclosedPlaceholder(text.clone()));
+                };
+
+                rest = &after[end + 2..];
+            }
+
+            Ok(())
+        }
+        Value::Array(items) => items.iter().try_for_each(check_placeholders),
+        Value::Object(fields) => fields.values().try_for_each(check_placeholders),
+        _ => Ok(()),
+    }
+}
+
+fn lookup<'a>(event: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(event, |current, key| match current {
+            Value::Object(fields) => fields.get(key),
+            Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+fn render_value(template: &Value, event: &Value) -> Value {
+    match template {
+        Value::String(text) => render_string(text, event),
+        Value::Array(items) => items.iter().map(|item| render_value(item, event)).collect(),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), render_value(value, event)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn render_string(text: &str, event: &Value) -> Value {
+    let single_placeholder = text
+        .trim()
+        .strip_prefix("{{")
+        .and_then(|rest| rest.strip_suffix("}}"))
+        .filter(|path| !path.contains("{{") && !path.contains("}}"));
+
+    if let Some(path) = single_placeholder {
+        return lookup(event, path.trim()).cloned().unwrap_or(Value::Null);
+    }
+
+    let mut rendered = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+
+        match lookup(event, after[..end].trim()) {
+            Some(Value::String(value)) => rendered.push_str(value),
+            Some(Value::Null) | None => {}
+            Some(value) => rendered.push_str(&value.to_string()),
+        }
+
+        rest = &after[end + 2..];
+    }
+
+    rendered.push_str(rest);
+    Value::String(rendered)
+}
+
+/// A headline and labelled fields, which the chat presets lay out
+fn summarize(event: &WebhookEventDataV1) -> (String, Vec<(&'static str, String)>) {
+    match event {
+        WebhookEventDataV1::PaymentSummary(payment) => {
+            let amount = payment
+                .total_amount_formatted
+                .clone()
+                .unwrap_or_else(|| payment.total_amount.clone());
+            let token = payment
+                .token
+                .symbol
+                .clone()
+                .unwrap_or_else(|| payment.token.address.clone());
+
+            let mut fields = vec![
+                ("Invoice", payment.invoice_uuid.clone()),
+                ("Chain", payment.chain_id.to_string()),
+                ("Payer", payment.payer_address.clone()),
+                ("Transaction", payment.transaction_hash.clone()),
+                ("Status", payment.status.clone()),
+            ];
+
+            if let Some(usd_value) = &payment.usd_value {
+                fields.push(("USD value", usd_value.clone()));
+            }
+
+            (format!("Payment of {} {} received", amount, token), fields)
+        }
+        WebhookEventDataV1::InvoiceCreated(invoice) => (
+            format!("Invoice created on chain {}", invoice.chain_id),
+            vec![
+                ("Invoice", invoice.invoice_uuid.clone()),
+                ("Amount", invoice.total_amount.clone()),
+                ("Token", invoice.token_address.clone()),
+                ("Status", invoice.status.clone()),
+            ],
+        ),
+        WebhookEventDataV1::InvoiceExpired(invoice) => (
+            format!("Invoice expired on chain {}", invoice.chain_id),
+            vec![
+                ("Invoice", invoice.invoice_uuid.clone()),
+                ("Amount", invoice.total_amount.clone()),
+                ("Token", invoice.token_address.clone()),
+                ("Status", invoice.status.clone()),
+            ],
+        ),
+        WebhookEventDataV1::CreditRefillApplied(refill) => (
+            "Credit refill applied".to_string(),
+            vec![
+                ("Workspace", refill.workspace_uuid.clone()),
+                ("Amount", refill.payment_amount.clone()),
+                ("Token", refill.payment_token_address.clone()),
+                ("Client", refill.client_address.clone()),
+            ],
+        ),
+        WebhookEventDataV1::ApiKeyCreated(api_key) => (
+            format!("API key {} created", api_key.key_hint),
+            vec![("Name", api_key.name.clone().unwrap_or_default())],
+        ),
+        WebhookEventDataV1::ApiKeyDeleted(api_key) => (
+            format!("API key {} deleted", api_key.key_hint),
+            vec![("Name", api_key.name.clone().unwrap_or_default())],
+        ),
+        WebhookEventDataV1::WebhookUrlDisabled(disabled) => (
+            format!("Webhook url {} was disabled", disabled.webhook_url),
+            vec![
+                ("Reason", disabled.reason.clone()),
+                (
+                    "Consecutive failures",
+                    disabled.consecutive_failures.to_string(),
+                ),
+            ],
+        ),
+    }
+}
+
+fn slack_body(event: &WebhookEventV1) -> Value {
+    let (headline, fields) = summarize(&event.event);
+
+    let mut blocks = vec![json!({
+        "type": "section",
+        "text": { "type": "mrkdwn", "text": format!("*{}*", headline) }
+    })];
+
+    // slack rejects a section with no fields
+    if !fields.is_empty() {
+        let fields: Vec<Value> = fields
+            .iter()
+            .map(|(name, value)| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", name, value) }))
+            .collect();
+
+        blocks.push(json!({ "type": "section", "fields": fields }));
+    }
+
+    blocks.push(json!({
+        "type": "context",
+        "elements": [{ "type": "mrkdwn", "text": event.id }]
+    }));
+
+    json!({ "text": headline, "blocks": blocks })
+}
+
+fn discord_body(event: &WebhookEventV1) -> Value {
+    let (headline, fields) = summarize(&event.event);
+
+    // discord rejects empty field values
+    let fields: Vec<Value> = fields
+        .iter()
+        .map(|(name, value)| {
+            let value = if value.is_empty() {
+                "-"
+            } else {
+                value.as_str()
+            };
+            json!({ "name": name, "value": value, "inline": true })
+        })
+        .collect();
+
+    json!({
+        "embeds": [{
+            "title": headline,
+            "fields": fields,
+            "timestamp": chrono::DateTime::from_timestamp(event.created_at, 0)
+                .map(|created_at| created_at.to_rfc3339()),
+            "footer": { "text": event.id }
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::postgres::models::payments_model::PaymentSummary;
+    use crate::types::webhook_events::WebhookApiVersion;
+
+    fn payment_event() -> WebhookEventV1 {
+        let payment = PaymentSummary::generate_test_payment_summary();
+
+        WebhookEventV1 {
+            id: "whevt_1".to_string(),
+            event: WebhookEventDataV1::PaymentSummary(Box::new((&payment).into())),
+            api_version: WebhookApiVersion::V1,
+            created_at: 1_700_000_000,
+            webhook_id: 1,
+            redelivery: false,
+        }
+    }
+
+    #[test]
+    fn test_custom_template_placeholders() {
+        let template = WebhookPayloadTemplate::from_json(json!({"custom": {
+            "text": "{{type}} of {{ data.total_amount }} to {{data.recipients.1.address}}",
+            "amount": "{{data.total_amount}}",
+            "chain": "{{data.chain_id}}",
+            "missing": "{{data.nothing}}",
+            "static": [1, true]
+        }}))
+        .unwrap();
+
+        let body = template.render(&payment_event());
+
+        assert_eq!(
+            body["text"],
+            "payment_summary of 150000000 to 0xbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb"
+        );
+        assert_eq!(body["amount"], "150000000");
+        assert_eq!(body["chain"], json!(1));
+        assert_eq!(body["missing"], Value::Null);
+        assert_eq!(body["static"], json!([1, true]));
+
+        assert_eq!(
+            WebhookPayloadTemplate::from_json(json!({"custom": {"text": "{{type"}})),
+            Err(WebhookTemplateError::UnclosedPlaceholder(
+                "{{type".to_string()
+            ))
+        );
+        assert!(matches!(
+            WebhookPayloadTemplate::from_json(json!({"preset": "teams"})),
+            Err(WebhookTemplateError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_presets() {
+        let event = payment_event();
+
+        let slack = WebhookPayloadTemplate::Preset(WebhookTemplatePreset::Slack).render(&event);
+        assert!(slack["text"].as_str().unwrap().starts_with("Payment of "));
+        assert_eq!(slack["blocks"][2]["elements"][0]["text"], "whevt_1");
+
+        let discord = WebhookPayloadTemplate::Preset(WebhookTemplatePreset::Discord).render(&event);
+        assert_eq!(discord["embeds"][0]["title"], slack["text"]);
+        assert_eq!(discord["embeds"][0]["fields"][1]["value"], "1");
+    }
+
+    #[test]
+    fn test_custom_headers() {
+        let headers = WebhookCustomHeaders::new(BTreeMap::from([(
+            "Authorization".to_string(),
+            "Bearer abc".to_string(),
+        )]))
+        .unwrap();
+        assert_eq!(headers.names(), vec!["authorization".to_string()]);
+
+        for reserved in ["Content-Type", "X-DefiRelay-Signature"] {
+            assert_eq!(
+                WebhookCustomHeaders::new(BTreeMap::from([(
+                    reserved.to_string(),
+                    "x".to_string()
+                )])),
+                Err(WebhookTemplateError::ReservedHeader(
+                    reserved.to_lowercase()
+                ))
+            );
+        }
+
+        assert_eq!(
+            WebhookCustomHeaders::new(BTreeMap::from([(
+                "x-token".to_string(),
+                "a\nb".to_string()
+            )])),
+            Err(WebhookTemplateError::InvalidHeader("x-token".to_string()))
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use crate::db::postgres::models::domain_events_model::{DomainEventsModel, XactHorizon};
+use crate::types::account_stream::{AccountStreamEvent, STREAM_EVENT_TYPES};
+use crate::types::domains::eth_address::DomainEthAddress;
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+use ethers::types::Address;
+use log::warn;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/*
+
+Feeds the `/api/stream` connections of one webserver.
+
+A single task reads new domain events and broadcasts them, and each connection keeps the
+ones of its wallet, so the database is polled once however many dashboards are open. A
+connection that resumes from an id, or falls too far behind the broadcast, reads what it
+missed from the database first.
+
+Ids are taken when an event is written but become visible when its transaction commits,
+so a lower id can show up after a higher one. The hub stops at a missing id until it
+appears, or until every transaction that had started when the id was first seen missing
+has ended, after which it never will (see `StreamCursor`).
+
+*/
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const PAGE_SIZE: i64 = 500;
+
+/// Events a connection may fall behind the broadcast before it reads from the database
+const BROADCAST_CAPACITY: usize = 1024;
+
+pub struct AccountEventHub {
+    database: Arc<Database>,
+    sender: broadcast::Sender<Arc<AccountStreamEvent>>,
+    /// Id up to which every event has been broadcast, or will never exist
+    cursor: AtomicI32,
+}
+
+/// Where the hub has read up to in the id sequence
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct StreamCursor {
+    id: i32,
+    /// `xmax` of the read that first found the id after `id` missing. The transaction
+    /// holding that id, if any, is below it: transaction ids are taken before event ids.
+    gap_xmax: Option<i64>,
+}
+
+impl StreamCursor {
+    /// Moves past the ids, read in order with `horizon`, that can be streamed without
+    /// skipping an event still to be committed. Returns how many of them it moved past.
+    fn advance(&mut self, ids: &[i32], horizon: XactHorizon) -> usize {
+        let mut settled = 0;
+
+        for &id in ids {
+            if id > self.id + 1 {
+                let gap_xmax = *self.gap_xmax.get_or_insert(horizon.xmax);
+
+                // A transaction that might still commit the missing ids is running
+                if horizon.xmin < gap_xmax {
+                    break;
+                }
+            }
+
+            self.id = id;
+            self.gap_xmax = None;
+            settled += 1;
+        }
+
+        settled
+    }
+}
+
+impl AccountEventHub {
+    /// Starts broadcasting events newer than the newest one stored. Waits for the
+    /// transactions running at that point, which may still commit lower ids.
+    pub async fn start(database: Arc<Database>) -> Arc<Self> {
+        let cursor = Self::settled_latest_id(database.as_ref()).await;
+
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+
+        let hub = Arc::new(Self {
+            database,
+            sender,
+            cursor: AtomicI32::new(cursor),
+        });
+
+        tokio::spawn(Arc::clone(&hub).poll(StreamCursor {
+            id: cursor,
+            gap_xmax: None,
+        }));
+
+        hub
+    }
+
+    async fn settled_latest_id(database: &Database) -> i32 {
+        let (latest_id, started) = match DomainEventsModel::latest_id(database).await {
+            Ok(latest) => latest,
+            Err(e) => {
+                warn!(
+                    "Could not read the latest domain event, streaming from the start: {}",
+                    e
+                );
+                return 0;
+            }
+        };
+
+        loop {
+            match DomainEventsModel::latest_id(database).await {
+                Ok((_, horizon)) if horizon.xmin >= started.xmax => return latest_id,
+                Ok(_) => {}
+                Err(e) => warn!("Could not read the domain event horizon: {}", e),
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn poll(self: Arc<Self>, mut cursor: StreamCursor) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match self.broadcast_next_page(&mut cursor).await {
+                    Ok(true) => continue,
+                    Ok(false) => break,
+                    Err(e) => {
+                        warn!("Could not read domain events to stream: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns whether there may be more events waiting
+    async fn broadcast_next_page(
+        &self,
+        cursor: &mut StreamCursor,
+    ) -> Result<bool, PostgresModelError> {
+        let (records, horizon) =
+            DomainEventsModel::find_after(cursor.id, PAGE_SIZE, self.database.as_ref()).await?;
+
+        let Some(horizon) = horizon else {
+            return Ok(false);
+        };
+
+        let ids: Vec<i32> = records.iter().map(|record| record.id.0).collect();
+        let settled = cursor.advance(&ids, horizon);
+
+        // Moved before sending, so a connection subscribing in between reads the page from
+        // the database and drops the broadcast copies it already has
+        self.cursor.store(cursor.id, Ordering::SeqCst);
+
+        for record in &records[..settled] {
+            if !STREAM_EVENT_TYPES.contains(&record.entry.event_type.as_str()) {
+                continue;
+            }
+
+            match AccountStreamEvent::from_record(record) {
+                // No receivers is not an error, the event is simply not streamed
+                Ok(event) => {
+                    let _ = self.sender.send(Arc::new(event));
+                }
+                Err(e) => warn!("Domain event {} is not streamed: {}", record.id, e),
+            }
+        }
+
+        Ok(settled as i64 == PAGE_SIZE)
+    }
+
+    /// Events of `owner` after `last_event_id`, or from now on without one
+    pub fn subscribe(
+        self: &Arc<Self>,
+        owner: Address,
+        last_event_id: Option<i32>,
+    ) -> AccountEventSubscription {
+        // Subscribed before the cursor is read, so nothing falls between the two
+        let receiver = self.sender.subscribe();
+        let cursor = self.cursor.load(Ordering::SeqCst);
+
+        AccountEventSubscription {
+            hub: Arc::clone(self),
+            owner,
+            receiver,
+            last_sent_id: last_event_id.unwrap_or(cursor),
+            backlog: VecDeque::new(),
+            needs_replay: last_event_id.is_some_and(|id| id < cursor),
+        }
+    }
+}
+
+pub struct AccountEventSubscription {
+    hub: Arc<AccountEventHub>,
+    owner: Address,
+    receiver: broadcast::Receiver<Arc<AccountStreamEvent>>,
+    /// Id of the newest event sent, or read into the backlog
+    last_sent_id: i32,
+    backlog: VecDeque<AccountStreamEvent>,
+    needs_replay: bool,
+}
+
+impl AccountEventSubscription {
+    /// The next event of the wallet, in id order. None once the stream cannot go on, and
+    /// the client should reconnect with the last id it received.
+    pub async fn next(&mut self) -> Option<AccountStreamEvent> {
+        loop {
+            if let Some(event) = self.backlog.pop_front() {
+                return Some(event);
+            }
+
+            if self.needs_replay {
+                if let Err(e) = self.replay_page().await {
+                    warn!("Could not replay domain events to a stream: {}", e);
+                    return None;
+                }
+                continue;
+            }
+
+            match self.receiver.recv().await {
+                Ok(event) if event.id > self.last_sent_id && event.is_for(&self.owner) => {
+                    self.last_sent_id = event.id;
+                    return Some(event.as_ref().clone());
+                }
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => self.needs_replay = true,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Reads the next page of missed events into the backlog, up to the hub's cursor
+    async fn replay_page(&mut self) -> Result<(), PostgresModelError> {
+        let up_to_id = self.hub.cursor.load(Ordering::SeqCst);
+
+        let records = DomainEventsModel::find_for_owner_between(
+            &DomainEthAddress(self.owner),
+            self.last_sent_id,
+            up_to_id,
+            &STREAM_EVENT_TYPES,
+            PAGE_SIZE,
+            self.hub.database.as_ref(),
+        )
+        .await?;
+
+        match records.last() {
+            Some(last) if records.len() as i64 == PAGE_SIZE => self.last_sent_id = last.id.0,
+            // Everything of this wallet's up to the cursor has been read
+            _ => {
+                self.last_sent_id = self.last_sent_id.max(up_to_id);
+                self.needs_replay = false;
+            }
+        }
+
+        self.backlog.extend(records.iter().filter_map(|record| {
+            AccountStreamEvent::from_record(record)
+                .map_err(|e| warn!("Domain event {} is not streamed: {}", record.id, e))
+                .ok()
+        }));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::postgres::models::domain_events_model::DomainEvent;
+    use crate::db::postgres::models::payments_model::PaymentSummary;
+
+    fn horizon(xmin: i64, xmax: i64) -> XactHorizon {
+        XactHorizon { xmin, xmax }
+    }
+
+    #[test]
+    fn test_cursor_waits_for_missing_ids() {
+        let mut cursor = StreamCursor {
+            id: 10,
+            gap_xmax: None,
+        };
+
+        // 12 committed while the transaction holding 11 (xid 500) is still running
+        assert_eq!(cursor.advance(&[12], horizon(500, 510)), 0);
+        assert_eq!(cursor.id, 10);
+
+        // Newer transactions do not count, only the ones started when 11 went missing
+        assert_eq!(cursor.advance(&[12, 13], horizon(500, 520)), 0);
+
+        // 11 commits after 12, and is streamed before it
+        assert_eq!(cursor.advance(&[11, 12, 13], horizon(505, 520)), 3);
+        assert_eq!(cursor.id, 13);
+
+        // 14 is rolled back: skipped once every transaction that could hold it has ended
+        assert_eq!(cursor.advance(&[15], horizon(530, 540)), 0);
+        assert_eq!(cursor.advance(&[15], horizon(539, 545)), 0);
+        assert_eq!(cursor.advance(&[15, 16], horizon(540, 545)), 2);
+        assert_eq!(cursor.id, 16);
+        assert_eq!(cursor.gap_xmax, None);
+
+        // With nothing running a missing id is skipped straight away
+        assert_eq!(cursor.advance(&[18], horizon(550, 550)), 1);
+    }
+
+    /// Needs a database with the domain_events migrations:
+    /// DB_CONN_URL=... cargo test -- --ignored test_lower_id_committed_after_higher_one
+    #[tokio::test]
+    #[ignore]
+    async fn test_lower_id_committed_after_higher_one() {
+        let database =
+            Database::new(std::env::var("DB_CONN_URL").unwrap(), None).expect("database");
+        let event = || {
+            DomainEvent::new(
+                &PaymentSummary::generate_test_payment_summary(),
+                vec![DomainEthAddress(Address::random())],
+            )
+        };
+
+        let (latest_id, _) = DomainEventsModel::latest_id(&database).await.unwrap();
+        let mut cursor = StreamCursor {
+            id: latest_id,
+            gap_xmax: None,
+        };
+
+        let mut slow_client = database.connect().await.unwrap();
+        let slow = slow_client.transaction().await.unwrap();
+        let lower_id = DomainEventsModel::publish(event(), &slow).await.unwrap();
+
+        let mut fast_client = database.connect().await.unwrap();
+        let fast = fast_client.transaction().await.unwrap();
+        let higher_id = DomainEventsModel::publish(event(), &fast).await.unwrap();
+        fast.commit().await.unwrap();
+        assert!(lower_id < higher_id);
+
+        let (records, horizon) = DomainEventsModel::find_after(cursor.id, PAGE_SIZE, &database)
+            .await
+            .unwrap();
+        let ids: Vec<i32> = records.iter().map(|record| record.id.0).collect();
+        assert_eq!(cursor.advance(&ids, horizon.unwrap()), 0);
+
+        slow.commit().await.unwrap();
+
+        let (records, horizon) = DomainEventsModel::find_after(cursor.id, PAGE_SIZE, &database)
+            .await
+            .unwrap();
+        let ids: Vec<i32> = records.iter().map(|record| record.id.0).collect();
+        assert_eq!(ids, vec![lower_id, higher_id]);
+        assert_eq!(cursor.advance(&ids, horizon.unwrap()), 2);
+    }
+}
@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use ethers::contract::abigen;
+use ethers::providers::{Http, Provider};
+use ethers::types::Address;
+
+abigen!(
+    Erc20Metadata,
+    r#"[
+        function name() external view returns (string)
+        function symbol() external view returns (string)
+        function decimals() external view returns (uint8)
+    ]"#
+);
+
+#[derive(Debug, Clone)]
+pub struct Erc20MetadataResult {
+    pub symbol: String,
+    pub name: Option<String>,
+    pub decimals: u8,
+}
+
+/// Reads symbol, name and decimals straight from an ERC-20 contract.
+/// `name()` is optional in the standard, so a failing call there is tolerated.
+pub async fn fetch_erc20_metadata(
+    token_address: Address,
+    provider: Arc<Provider<Http>>,
+) -> Result<Erc20MetadataResult, ethers::contract::ContractError<Provider<Http>>> {
+    let token = Erc20Metadata::new(token_address, provider);
+
+    let symbol = token.symbol().call().await?;
+    let decimals = token.decimals().call().await?;
+    let name = token.name().call().await.ok();
+
+    Ok(Erc20MetadataResult {
+        symbol,
+        name,
+        decimals,
+    })
+}
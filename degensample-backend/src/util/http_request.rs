@@ -1,5 +1,5 @@
 use reqwest::header::HeaderMap;
-use reqwest::{Error, Response};
+use reqwest::{Client, Error, Response};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
 
@@ -44,11 +44,12 @@ impl IntoHttpRequest for EndpointUrlAndData {
 }
 
 // Helper functions for making HTTP requests
-async fn post_request(url: &str, data: &Value, headers: &HeaderMap) -> Result<Response, Error> {
-    let client = reqwest::Client::new();
-
-    println!("sending post {:?} {:?} {:?}", url, data, headers);
-
+async fn post_request(
+    client: &Client,
+    url: &str,
+    data: &Value,
+    headers: &HeaderMap,
+) -> Result<Response, Error> {
     client
         .post(url)
         .headers(headers.clone())
@@ -57,8 +58,12 @@ async fn post_request(url: &str, data: &Value, headers: &HeaderMap) -> Result<Re
         .await
 }
 
-async fn get_request(url: &str, data: &Value, headers: &HeaderMap) -> Result<Response, Error> {
-    let client = reqwest::Client::new();
+async fn get_request(
+    client: &Client,
+    url: &str,
+    data: &Value,
+    headers: &HeaderMap,
+) -> Result<Response, Error> {
     client
         .get(url)
         .headers(headers.clone())
@@ -69,12 +74,17 @@ async fn get_request(url: &str, data: &Value, headers: &HeaderMap) -> Result<Res
 
 impl EndpointUrlAndData {
     pub async fn perform_req(&self) -> Result<Response, Error> {
+        self.perform_req_with_client(&Client::new()).await
+    }
+
+    /// Sends the request through a shared client, so its connection pool and timeout are reused
+    pub async fn perform_req_with_client(&self, client: &Client) -> Result<Response, Error> {
         let url = &self.url;
         let data = &self.data;
         let header_map = &self.headers;
         match self.endpoint_type {
-            EndpointType::POST => post_request(url, data, header_map).await,
-            EndpointType::GET => get_request(url, data, header_map).await,
+            EndpointType::POST => post_request(client, url, data, header_map).await,
+            EndpointType::GET => get_request(client, url, data, header_map).await,
         }
     }
 
@@ -87,8 +97,8 @@ impl EndpointUrlAndData {
         let header_map = &self.headers;
 
         let response_result = match self.endpoint_type {
-            EndpointType::POST => post_request(url, data, header_map).await,
-            EndpointType::GET => get_request(url, data, header_map).await,
+            EndpointType::POST => post_request(&Client::new(), url, data, header_map).await,
+            EndpointType::GET => get_request(&Client::new(), url, data, header_map).await,
         };
 
         match response_result {
@@ -111,8 +121,8 @@ pub async fn perform_req<T: IntoHttpRequest>(request: &T) -> Result<Response, Er
     let headers = request.get_headers().unwrap_or_else(|| HeaderMap::new());
 
     match request.get_endpoint_type() {
-        EndpointType::POST => post_request(&url, &data, &headers).await,
-        EndpointType::GET => get_request(&url, &data, &headers).await,
+        EndpointType::POST => post_request(&Client::new(), &url, &data, &headers).await,
+        EndpointType::GET => get_request(&Client::new(), &url, &data, &headers).await,
     }
 }
 
@@ -126,8 +136,8 @@ where
     let headers = request.get_headers().unwrap_or_else(|| HeaderMap::new());
 
     let response_result = match request.get_endpoint_type() {
-        EndpointType::POST => post_request(&url, &data, &headers).await,
-        EndpointType::GET => get_request(&url, &data, &headers).await,
+        EndpointType::POST => post_request(&Client::new(), &url, &data, &headers).await,
+        EndpointType::GET => get_request(&Client::new(), &url, &data, &headers).await,
     };
 
     match response_result {
@@ -4,7 +4,6 @@ pub mod backend_server_error;
 
 pub mod request;
 
-pub mod rpc_network;
 
 pub mod built_from_row;
 
@@ -13,3 +12,17 @@ pub mod unix_day_index;
 
 pub mod header_map_preset;
 pub mod http_request;
+pub mod payment_summary;
+pub mod payspec;
+pub mod price_source;
+pub mod rpc_provider;
+pub mod rpc_proxy;
+pub mod erc20;
+pub mod delivery_sink;
+pub mod token_amount;
+pub mod account_event_hub;
+pub mod webhook_delivery;
+pub mod webhook_fanout;
+pub mod webhook_retry;
+pub mod webhook_signature;
+pub mod webhook_url_guard;
@@ -0,0 +1,329 @@
+use crate::config::NetworksConfig;
+use crate::db::postgres::models::payments_model::{PaymentStatus, PaymentSummary};
+use crate::db::postgres::models::token_metadata_model::TokenMetadataModel;
+use crate::types::domains::bytes32::DomainBytes32;
+use crate::types::domains::eth_address::DomainEthAddress;
+use crate::types::domains::h256::DomainH256;
+use crate::types::domains::pay_to_amounts::DomainPayToAmounts;
+use crate::types::domains::pay_to_array::DomainPayToArray;
+use crate::types::domains::uint256::DomainUint256;
+use crate::util::price_source::{ConfiguredPriceSource, PriceSource};
+use crate::util::rpc_provider::{RpcPoolError, RpcProviderPool, RpcProviders};
+use crate::util::unix_day_index::UnixDayIndex;
+use chrono::{DateTime, Utc};
+use degen_sql::db::postgres::postgres_db::Database;
+use ethers::abi::{Address, Token};
+use ethers::contract::abigen;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::{H256, U64};
+use log::{info, warn};
+use rust_decimal::Decimal;
+use vibegraph::event::ContractEvent;
+
+/// What is needed to turn a PaidInvoice event into a `PaymentSummary`
+pub struct PaymentSummaryConfig {
+    pub networks: NetworksConfig,
+    pub providers: RpcProviders,
+    pub price_source: Option<ConfiguredPriceSource>,
+}
+
+impl PaymentSummaryConfig {
+    pub fn from_env(networks: NetworksConfig) -> Self {
+        // payments are still indexed without a price source, they just go unvalued
+        let price_source = match ConfiguredPriceSource::from_env() {
+            Ok(price_source) => Some(price_source),
+            Err(e) => {
+                warn!(
+                    "no price source configured, payments will not be valued: {}",
+                    e
+                );
+                None
+            }
+        };
+
+        Self {
+            providers: RpcProviders::from_networks(&networks),
+            networks,
+            price_source,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PaidInvoiceData {
+    pub contract_address: Address,
+
+    pub uuid: Vec<u8>,
+    pub chain_id: u64,
+
+    pub tx_hash: H256,
+
+    pub from_address: Address,
+
+    pub block_number: Option<U64>,
+
+    pub block_hash: Option<H256>,
+}
+
+impl PaidInvoiceData {
+    pub fn extract_paid_invoice_data(contract_event: &ContractEvent) -> Option<Self> {
+        let event_name = &contract_event.name;
+
+        if event_name != "PaidInvoice" {
+            return None;
+        }
+
+        let chain_id = contract_event.chain_id;
+        let contract_address = contract_event.address;
+
+        let tx_hash = contract_event.transaction_hash?;
+
+        let block_number = contract_event.block_number;
+        let block_hash = contract_event.block_hash;
+
+        let mut uuid_opt = None;
+
+        let mut from_address_opt = None;
+
+        for arg in &contract_event.args {
+            if arg.name == "uuid" {
+                if let Token::FixedBytes(uuid_bytes) = &arg.value {
+                    uuid_opt = Some(uuid_bytes);
+                }
+            }
+
+            if arg.name == "from" {
+                if let Token::Address(from_addr) = &arg.value {
+                    from_address_opt = Some(from_addr);
+                }
+            }
+        }
+
+        // let bid_id = U256::zero(); // FIX
+
+        if let Some(uuid) = uuid_opt {
+            if let Some(from_address) = from_address_opt {
+                return Some(Self {
+                    contract_address,
+                    chain_id,
+                    uuid: uuid.to_vec(),
+                    tx_hash,
+                    block_number,
+                    block_hash,
+                    from_address: *from_address,
+                });
+            }
+        }
+
+        None
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PaymentSummaryError {
+    #[error("Failed to fetch summary from contract: {0}")]
+    ContractCallError(#[from] ethers::contract::ContractError<Provider<Http>>),
+
+    #[error("Rpc error: {0}")]
+    RpcError(#[from] RpcPoolError),
+
+    #[error("Block {0} not found")]
+    BlockNotFound(u64),
+
+    #[error("Failed to parse RPC URL for chain {0}")]
+    RpcUrlParseError(String),
+
+    #[error("Network provider initialization failed: {0}")]
+    ProviderError(#[from] ethers::providers::ProviderError),
+
+    #[error("abi parse failed: {0}")]
+    ParseError(#[from] url::ParseError),
+
+    #[error("Reqwest error: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+}
+
+/// Reads the full payment from the Payspec contract, then attaches token metadata and a USD price
+pub async fn fetch_payment_summary(
+    payment_data: &PaidInvoiceData,
+    app_config: &PaymentSummaryConfig,
+    psql_db: &Database,
+) -> Result<PaymentSummary, PaymentSummaryError> {
+    abigen!(
+        Payspec,
+        "./abi/payspec.json",
+        event_derives(serde::Deserialize, serde::Serialize)
+    );
+
+    info!("fetch_payment_summary {:?}", payment_data);
+
+    let payspec_contract_address = &payment_data.contract_address;
+
+    let chain_id = &payment_data.chain_id;
+
+    let block_number = &payment_data.block_number;
+
+    let transaction_hash = &payment_data.tx_hash;
+
+    let providers = app_config
+        .providers
+        .get(*chain_id)
+        .ok_or_else(|| PaymentSummaryError::RpcUrlParseError(chain_id.to_string()))?;
+
+    let from_address = &payment_data.from_address;
+    let uuid: &Vec<u8> = &payment_data.uuid;
+
+    // ----
+
+    let payment_at_block = *block_number;
+
+    let mut payment_at_block_timestamp = None;
+    let mut payment_at_unix_days_index = None;
+
+    if let Some(payment_at_block) = payment_at_block {
+        payment_at_block_timestamp = fetch_block_timestamp(payment_at_block, providers)
+            .await
+            .ok();
+
+        payment_at_unix_days_index = payment_at_block_timestamp.map(UnixDayIndex::from_timestamp);
+    }
+
+    //  info!("accepted_at_block {:?}" , payment_at_block );
+
+    //  info!("accepted_at_unix_days_index {:?}" , payment_at_unix_days_index );
+
+    // ----
+
+    // Ensure we have exactly 32 bytes for the UUID
+    let mut uuid_bytes = [0u8; 32];
+    let copy_len = std::cmp::min(uuid.len(), 32);
+
+    // If the UUID is less than 32 bytes, it will be padded with zeros
+    // If it's longer, it will be truncated
+    uuid_bytes[..copy_len].copy_from_slice(&uuid[..copy_len]);
+
+    let domain_uuid_bytes = DomainBytes32(uuid_bytes);
+
+    // this read decides what gets recorded as paid, so it is cross-checked when quorum is on
+    let payment_details = providers
+        .call_critical(|provider| async move {
+            Payspec::new(*payspec_contract_address, provider)
+                .get_invoice_payment_details(uuid_bytes)
+                .call()
+                .await
+        })
+        .await?;
+    info!("payment_details {:?}", payment_details);
+
+    //  access the tuple elements:
+    let _paid_by = payment_details.0;
+    let payment_token_address = payment_details.1;
+    let total_amount = payment_details.2;
+
+    let nonce = payment_details.3;
+
+    let pay_to_array = payment_details.4;
+    let pay_to_amounts = payment_details.5;
+
+    let mut payment_summary = PaymentSummary {
+        uuid: domain_uuid_bytes.clone(),
+
+        chain_id: *chain_id as i64,
+
+        //  paid_by  , ...
+        from_address: (*from_address).into(),
+
+        payment_token_address: DomainEthAddress(payment_token_address),
+        total_amount: DomainUint256(total_amount),
+        pay_to_array: DomainPayToArray(pay_to_array),
+        pay_to_amounts: DomainPayToAmounts(pay_to_amounts),
+
+        payment_at_block: payment_at_block.map(|x| x.as_u64() as i64),
+        payment_at_block_hash: payment_data.block_hash.map(DomainH256),
+        payment_at_block_timestamp: payment_at_block_timestamp.map(|x| x.into()),
+        payment_at_unix_days_index,
+
+        status: PaymentStatus::Detected,
+        confirmations: 0,
+
+        payspec_contract_address: DomainEthAddress(*payspec_contract_address),
+        nonce: DomainUint256(nonce),
+
+        transaction_hash: DomainH256(*transaction_hash),
+
+        token: None,
+        total_amount_formatted: None,
+        pay_to_amounts_formatted: None,
+        usd_price: None,
+        usd_value: None,
+    };
+
+    // unknown tokens are read from the contract once and cached in the registry
+    let token_metadata = TokenMetadataModel::resolve_or_fetch(
+        *chain_id as i64,
+        &payment_summary.payment_token_address,
+        providers,
+        psql_db,
+    )
+    .await
+    .map_err(|e| PaymentSummaryError::DatabaseError(e.to_string()))?;
+
+    if let Some(token_metadata) = token_metadata {
+        payment_summary.apply_token_metadata(token_metadata);
+    }
+
+    if let Some(usd_price) = fetch_usd_price(&payment_summary, app_config).await {
+        payment_summary.apply_usd_price(usd_price);
+    }
+
+    Ok(payment_summary)
+}
+
+/// Price of the payment token at block time. Valuation is best-effort and never blocks indexing.
+async fn fetch_usd_price(
+    payment_summary: &PaymentSummary,
+    app_config: &PaymentSummaryConfig,
+) -> Option<Decimal> {
+    let price_source = app_config.price_source.as_ref()?;
+    let token = payment_summary.token.as_ref()?;
+    let at = payment_summary.payment_at_block_timestamp.as_ref()?;
+
+    match price_source
+        .get_usd_price(
+            payment_summary.chain_id,
+            payment_summary.payment_token_address.0,
+            &token.symbol,
+            at.0,
+        )
+        .await
+    {
+        Ok(usd_price) => usd_price,
+        Err(e) => {
+            warn!("could not price {} at {:?}: {}", token.symbol, at.0, e);
+            None
+        }
+    }
+}
+
+/// Timestamp of a block, cross-checked across endpoints when quorum is on
+pub async fn fetch_block_timestamp(
+    block_number: U64,
+    providers: &RpcProviderPool,
+) -> Result<DateTime<Utc>, PaymentSummaryError> {
+    let timestamp = providers
+        .call_critical(|provider| async move {
+            provider
+                .get_block(block_number)
+                .await
+                .map(|block| block.map(|block| block.timestamp))
+        })
+        .await?;
+
+    timestamp
+        .and_then(|timestamp| i64::try_from(timestamp.as_u64()).ok())
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+        .ok_or(PaymentSummaryError::BlockNotFound(block_number.as_u64()))
+}
@@ -0,0 +1,148 @@
+use ethers::abi::{encode_packed, Token};
+use ethers::types::{Address, U256};
+use ethers::utils::keccak256;
+
+/// Mirrors `Payspec.getInvoiceUUID`:
+/// `keccak256(abi.encodePacked(address(this), nonce, token, totalAmountDue, payTo, amountsDue))`
+///
+/// Array members are padded to 32 bytes by `encodePacked`, which `encode_packed` reproduces.
+/// Standalone `Token::Uint`s are packed minimally by ethers though, so those are passed as
+/// full 32-byte words to match Solidity's `uint256`.
+pub fn derive_invoice_uuid(
+    payspec_contract_address: Address,
+    nonce: U256,
+    token_address: Address,
+    total_amount_due: U256,
+    pay_to_array: &[Address],
+    pay_to_amounts: &[U256],
+) -> [u8; 32] {
+    let packed = encode_packed(&[
+        Token::Address(payspec_contract_address),
+        uint256_word(nonce),
+        Token::Address(token_address),
+        uint256_word(total_amount_due),
+        Token::Array(pay_to_array.iter().copied().map(Token::Address).collect()),
+        Token::Array(pay_to_amounts.iter().copied().map(Token::Uint).collect()),
+    ])
+    .expect("payspec uuid tokens are always packable");
+
+    keccak256(packed)
+}
+
+fn uint256_word(value: U256) -> Token {
+    let mut word = [0u8; 32];
+    value.to_big_endian(&mut word);
+    Token::FixedBytes(word.to_vec())
+}
+
+/// Sum of all amounts owed to the payees, or None on overflow
+pub fn total_amount_due(pay_to_amounts: &[U256]) -> Option<U256> {
+    pay_to_amounts
+        .iter()
+        .try_fold(U256::zero(), |total, amount| total.checked_add(*amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derive_invoice_uuid_matches_packed_layout() {
+        let contract: Address = "0x1082d89364765ED958c830F4E77D001837984E31"
+            .parse()
+            .unwrap();
+        let token: Address = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        let payee: Address = "0x5AEDA56215b167893e80B4fE645BA6d5Bab767DE"
+            .parse()
+            .unwrap();
+
+        let nonce = U256::from(7);
+        let amount = U256::from(1_000_000);
+
+        let word = |value: U256| {
+            let mut bytes = [0u8; 32];
+            value.to_big_endian(&mut bytes);
+            bytes
+        };
+
+        // address (20) | nonce (32) | token (20) | total (32) | payTo[] (32 each) | amounts[] (32 each)
+        let mut expected_packed = Vec::new();
+        expected_packed.extend_from_slice(contract.as_bytes());
+        expected_packed.extend_from_slice(&word(nonce));
+        expected_packed.extend_from_slice(token.as_bytes());
+        expected_packed.extend_from_slice(&word(amount));
+        expected_packed.extend_from_slice(&[0u8; 12]);
+        expected_packed.extend_from_slice(payee.as_bytes());
+        expected_packed.extend_from_slice(&word(amount));
+
+        let uuid = derive_invoice_uuid(contract, nonce, token, amount, &[payee], &[amount]);
+
+        assert_eq!(uuid, keccak256(expected_packed));
+    }
+
+    /// Checks against `getInvoiceUUID` of the Payspec contract deployed on mainnet:
+    /// MAINNET_RPC_URL=... cargo test -- --ignored test_derive_invoice_uuid_matches_deployed_contract
+    #[tokio::test]
+    #[ignore]
+    async fn test_derive_invoice_uuid_matches_deployed_contract() {
+        use ethers::contract::abigen;
+        use ethers::providers::{Http, Provider};
+        use std::sync::Arc;
+
+        abigen!(Payspec, "./abi/payspec.json");
+
+        let provider = Provider::<Http>::try_from(std::env::var("MAINNET_RPC_URL").unwrap())
+            .expect("provider");
+        let contract: Address = "0x1082d89364765ED958c830F4E77D001837984E31"
+            .parse()
+            .unwrap();
+        let token: Address = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"
+            .parse()
+            .unwrap();
+        let pay_to_array: Vec<Address> = vec![
+            "0x5AEDA56215b167893e80B4fE645BA6d5Bab767DE"
+                .parse()
+                .unwrap(),
+            "0x00000000000000000000000000000000000000a1"
+                .parse()
+                .unwrap(),
+        ];
+        let pay_to_amounts = vec![U256::from(1_000_000), U256::from(250_000)];
+        let total = total_amount_due(&pay_to_amounts).unwrap();
+        let nonce = U256::from(7);
+
+        let on_chain = Payspec::new(contract, Arc::new(provider))
+            .get_invoice_uuid(
+                nonce,
+                token,
+                total,
+                pay_to_array.clone(),
+                pay_to_amounts.clone(),
+            )
+            .call()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            derive_invoice_uuid(
+                contract,
+                nonce,
+                token,
+                total,
+                &pay_to_array,
+                &pay_to_amounts
+            ),
+            on_chain
+        );
+    }
+
+    #[test]
+    fn test_total_amount_due() {
+        let amounts = vec![U256::from(5), U256::from(10)];
+        assert_eq!(total_amount_due(&amounts), Some(U256::from(15)));
+
+        assert_eq!(total_amount_due(&[U256::MAX, U256::one()]), None);
+    }
+}
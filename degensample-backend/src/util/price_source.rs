@@ -0,0 +1,226 @@
+use chrono::{DateTime, Utc};
+use ethers::types::Address;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+
+/*
+
+PRICE_SOURCE=file PRICE_HISTORY_PATH=./config/price_history.json
+PRICE_SOURCE=http PRICE_API_URL=http://localhost:8090
+
+*/
+
+#[derive(Debug, thiserror::Error)]
+pub enum PriceSourceError {
+    #[error("Could not read price history: {0}")]
+    HistoryReadError(String),
+
+    #[error("Price request failed: {0}")]
+    RequestError(#[from] reqwest::Error),
+
+    #[error("Invalid price: {0}")]
+    InvalidPrice(String),
+}
+
+/// Something that can tell what one whole token was worth in USD at a point in time
+pub trait PriceSource {
+    fn get_usd_price(
+        &self,
+        chain_id: i64,
+        token_address: Address,
+        token_symbol: &str,
+        at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<Decimal>, PriceSourceError>> + Send;
+}
+
+/// Reads prices from a JSON file keyed by token symbol:
+/// `{ "ETH": [[1700000000, "2050.12"], ...] }` with points sorted by unix timestamp.
+/// The latest point at or before the requested time wins.
+pub struct FilePriceSource {
+    history: HashMap<String, Vec<(i64, Decimal)>>,
+}
+
+impl FilePriceSource {
+    pub fn load(path: &str) -> Result<Self, PriceSourceError> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|e| PriceSourceError::HistoryReadError(format!("{}: {}", path, e)))?;
+
+        Self::from_json_str(&raw)
+    }
+
+    pub fn from_json_str(raw: &str) -> Result<Self, PriceSourceError> {
+        let parsed: HashMap<String, Vec<(i64, String)>> = serde_json::from_str(raw)
+            .map_err(|e| PriceSourceError::HistoryReadError(e.to_string()))?;
+
+        let mut history = HashMap::new();
+
+        for (symbol, points) in parsed {
+            let mut prices = points
+                .into_iter()
+                .map(|(timestamp, price)| {
+                    Decimal::from_str(&price)
+                        .map(|price| (timestamp, price))
+                        .map_err(|_| PriceSourceError::InvalidPrice(price))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            prices.sort_by_key(|(timestamp, _)| *timestamp);
+
+            history.insert(symbol.to_uppercase(), prices);
+        }
+
+        Ok(Self { history })
+    }
+
+    fn price_at(&self, token_symbol: &str, at: DateTime<Utc>) -> Option<Decimal> {
+        let points = self.history.get(&token_symbol.to_uppercase())?;
+
+        points
+            .iter()
+            .rev()
+            .find(|(timestamp, _)| *timestamp <= at.timestamp())
+            .map(|(_, price)| *price)
+    }
+}
+
+impl PriceSource for FilePriceSource {
+    async fn get_usd_price(
+        &self,
+        _chain_id: i64,
+        _token_address: Address,
+        token_symbol: &str,
+        at: DateTime<Utc>,
+    ) -> Result<Option<Decimal>, PriceSourceError> {
+        Ok(self.price_at(token_symbol, at))
+    }
+}
+
+#[derive(Deserialize)]
+struct HttpPriceResponse {
+    usd_price: Option<String>,
+}
+
+/// Asks a price API for historical prices:
+/// `GET {base_url}/price?chain_id=..&token_address=..&symbol=..&timestamp=..`
+/// answering `{ "usd_price": "1.0001" }`, or a null price when unknown.
+pub struct HttpPriceSource {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl HttpPriceSource {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+impl PriceSource for HttpPriceSource {
+    async fn get_usd_price(
+        &self,
+        chain_id: i64,
+        token_address: Address,
+        token_symbol: &str,
+        at: DateTime<Utc>,
+    ) -> Result<Option<Decimal>, PriceSourceError> {
+        let response: HttpPriceResponse = self
+            .client
+            .get(format!("{}/price", self.base_url))
+            .query(&[
+                ("chain_id", chain_id.to_string()),
+                ("token_address", format!("{:?}", token_address)),
+                ("symbol", token_symbol.to_string()),
+                ("timestamp", at.timestamp().to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        response
+            .usd_price
+            .map(|price| Decimal::from_str(&price).map_err(|_| PriceSourceError::InvalidPrice(price)))
+            .transpose()
+    }
+}
+
+/// The price source picked at startup through `PRICE_SOURCE` (`file` by default, or `http`)
+pub enum ConfiguredPriceSource {
+    File(FilePriceSource),
+    Http(HttpPriceSource),
+}
+
+impl ConfiguredPriceSource {
+    pub fn from_env() -> Result<Self, PriceSourceError> {
+        let kind = std::env::var("PRICE_SOURCE").unwrap_or_else(|_| "file".to_string());
+
+        match kind.to_lowercase().as_str() {
+            "http" => {
+                let base_url = std::env::var("PRICE_API_URL").map_err(|_| {
+                    PriceSourceError::HistoryReadError("PRICE_API_URL must be set".to_string())
+                })?;
+
+                Ok(Self::Http(HttpPriceSource::new(base_url)))
+            }
+            _ => {
+                let path = std::env::var("PRICE_HISTORY_PATH")
+                    .unwrap_or_else(|_| "./config/price_history.json".to_string());
+
+                Ok(Self::File(FilePriceSource::load(&path)?))
+            }
+        }
+    }
+}
+
+impl PriceSource for ConfiguredPriceSource {
+    async fn get_usd_price(
+        &self,
+        chain_id: i64,
+        token_address: Address,
+        token_symbol: &str,
+        at: DateTime<Utc>,
+    ) -> Result<Option<Decimal>, PriceSourceError> {
+        match self {
+            Self::File(source) => {
+                source
+                    .get_usd_price(chain_id, token_address, token_symbol, at)
+                    .await
+            }
+            Self::Http(source) => {
+                source
+                    .get_usd_price(chain_id, token_address, token_symbol, at)
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_price_source_uses_latest_point_before() {
+        let source = FilePriceSource::from_json_str(
+            r#"{ "eth": [[2000, "2100.5"], [1000, "2000"]], "USDC": [[0, "1"]] }"#,
+        )
+        .unwrap();
+
+        let at = |t: i64| DateTime::from_timestamp(t, 0).unwrap();
+
+        assert_eq!(source.price_at("ETH", at(500)), None);
+        assert_eq!(source.price_at("ETH", at(1500)), Some(Decimal::from(2000)));
+        assert_eq!(
+            source.price_at("ETH", at(2000)),
+            Some(Decimal::from_str("2100.5").unwrap())
+        );
+        assert_eq!(source.price_at("usdc", at(99)), Some(Decimal::ONE));
+        assert_eq!(source.price_at("DAI", at(99)), None);
+    }
+}
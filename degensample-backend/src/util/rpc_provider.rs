@@ -0,0 +1,454 @@
+use crate::config::{NetworkConfig, NetworksConfig};
+use ethers::contract::ContractError;
+use ethers::providers::{
+    Http, JsonRpcError, Middleware, MiddlewareError, Provider, ProviderError, RpcError,
+};
+use log::warn;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Passes over every endpoint before a call is given up on
+const MAX_ROUNDS: u32 = 3;
+
+/// Wait before the second pass, doubled for each pass after that
+const BASE_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Longest an endpoint is pushed to the back of the line after repeated failures
+const MAX_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Weight of the latest outcome in an endpoint's success score
+const SCORE_SMOOTHING: f64 = 0.2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RpcPoolError {
+    #[error("No rpc endpoints configured for chain {0}")]
+    NoEndpoints(u64),
+
+    #[error("Invalid rpc url {0}")]
+    InvalidUrl(String),
+
+    #[error("All rpc endpoints failed for chain {chain_id}: {last_error}")]
+    AllEndpointsFailed { chain_id: u64, last_error: String },
+
+    #[error("Rpc endpoints for chain {0} did not agree on the result")]
+    QuorumNotReached(u64),
+
+    #[error("Rpc call on chain {chain_id} failed: {error}")]
+    CallFailed { chain_id: u64, error: String },
+}
+
+/// Errors of the calls made through a pool. Only a failing endpoint is failed over from
+/// and penalised. A revert or an answer that does not decode would be the same on any
+/// other endpoint, so the call fails straight away.
+pub trait RpcCallError: Display {
+    fn is_endpoint_failure(&self) -> bool;
+}
+
+impl RpcCallError for ProviderError {
+    fn is_endpoint_failure(&self) -> bool {
+        match self {
+            ProviderError::SerdeJson(_) | ProviderError::HexError(_) => false,
+            // Other error responses, e.g. rate limits or pruned state, are the node's
+            _ => !RpcError::as_error_response(self).is_some_and(JsonRpcError::is_revert),
+        }
+    }
+}
+
+impl<M: Middleware> RpcCallError for ContractError<M> {
+    fn is_endpoint_failure(&self) -> bool {
+        match self {
+            ContractError::ProviderError { e } => e.is_endpoint_failure(),
+            ContractError::MiddlewareError { e } => {
+                !e.as_error_response().is_some_and(JsonRpcError::is_revert)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct EndpointHealth {
+    /// Moving average of call outcomes, 1.0 when every recent call succeeded
+    score: f64,
+    consecutive_failures: u32,
+    cooldown_until: Option<Instant>,
+}
+
+impl EndpointHealth {
+    fn record_success(&mut self) {
+        self.score += SCORE_SMOOTHING * (1.0 - self.score);
+        self.consecutive_failures = 0;
+        self.cooldown_until = None;
+    }
+
+    fn record_failure(&mut self) {
+        self.score -= SCORE_SMOOTHING * self.score;
+        self.consecutive_failures += 1;
+
+        let cooldown = BASE_BACKOFF
+            .saturating_mul(2u32.saturating_pow(self.consecutive_failures))
+            .min(MAX_COOLDOWN);
+
+        self.cooldown_until = Some(Instant::now() + cooldown);
+    }
+
+    fn is_cooling_down(&self, now: Instant) -> bool {
+        self.cooldown_until.is_some_and(|until| until > now)
+    }
+}
+
+struct RpcEndpoint {
+    url: String,
+    provider: Arc<Provider<Http>>,
+    health: Mutex<EndpointHealth>,
+}
+
+/// Several rpc endpoints for one chain. Calls go to the healthiest endpoint first and fail
+/// over to the others, with backoff between passes. Endpoints that keep failing are cooled
+/// down so a dead provider does not slow every call.
+pub struct RpcProviderPool {
+    chain_id: u64,
+    endpoints: Vec<RpcEndpoint>,
+    quorum: bool,
+}
+
+impl RpcProviderPool {
+    pub fn new(chain_id: u64, urls: Vec<String>, quorum: bool) -> Result<Self, RpcPoolError> {
+        if urls.is_empty() {
+            return Err(RpcPoolError::NoEndpoints(chain_id));
+        }
+
+        let endpoints = urls
+            .into_iter()
+            .map(|url| {
+                let provider = Provider::<Http>::try_from(url.as_str())
+                    .map_err(|_| RpcPoolError::InvalidUrl(url.clone()))?;
+
+                Ok(RpcEndpoint {
+                    url,
+                    provider: Arc::new(provider),
+                    health: Mutex::new(EndpointHealth {
+                        score: 1.0,
+                        consecutive_failures: 0,
+                        cooldown_until: None,
+                    }),
+                })
+            })
+            .collect::<Result<Vec<_>, RpcPoolError>>()?;
+
+        if quorum && endpoints.len() < 2 {
+            warn!(
+                "rpc quorum is enabled for chain {} but only one endpoint is configured, critical reads will not be cross-checked",
+                chain_id
+            );
+        }
+
+        Ok(Self {
+            chain_id,
+            endpoints,
+            quorum,
+        })
+    }
+
+    pub fn from_network(network: &NetworkConfig) -> Result<Self, RpcPoolError> {
+        Self::new(
+            network.chain_id,
+            network.resolved_rpc_urls(),
+            network.rpc_quorum,
+        )
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id
+    }
+
+    /// Endpoint indexes, healthiest first. Cooling endpoints go last but are still tried.
+    fn ranked_endpoints(&self) -> Vec<usize> {
+        let now = Instant::now();
+
+        let mut ranked: Vec<(usize, bool, f64)> = self
+            .endpoints
+            .iter()
+            .enumerate()
+            .map(|(index, endpoint)| {
+                let health = endpoint.health.lock().unwrap();
+                (index, health.is_cooling_down(now), health.score)
+            })
+            .collect();
+
+        // stable, so configured order breaks ties
+        ranked.sort_by(|a, b| a.1.cmp(&b.1).then(b.2.total_cmp(&a.2)));
+
+        ranked.into_iter().map(|(index, _, _)| index).collect()
+    }
+
+    fn record(&self, index: usize, success: bool) {
+        let mut health = self.endpoints[index].health.lock().unwrap();
+
+        if success {
+            health.record_success();
+        } else {
+            health.record_failure();
+        }
+    }
+
+    /// The healthiest provider, for callers that need a plain `Provider` such as contract bindings
+    pub fn provider(&self) -> Arc<Provider<Http>> {
+        let index = self.ranked_endpoints()[0];
+
+        Arc::clone(&self.endpoints[index].provider)
+    }
+
+    /// The healthiest url, for components that build their own provider
+    pub fn primary_url(&self) -> &str {
+        &self.endpoints[self.ranked_endpoints()[0]].url
+    }
+
+    fn call_failed(&self, error: impl Display) -> RpcPoolError {
+        RpcPoolError::CallFailed {
+            chain_id: self.chain_id,
+            error: error.to_string(),
+        }
+    }
+
+    /// Runs `op` against the healthiest endpoint, failing over to the others
+    pub async fn call<T, E, F, Fut>(&self, op: F) -> Result<T, RpcPoolError>
+    where
+        F: Fn(Arc<Provider<Http>>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: RpcCallError,
+    {
+        let mut last_error = String::new();
+
+        for round in 0..MAX_ROUNDS {
+            if round > 0 {
+                tokio::time::sleep(BASE_BACKOFF * 2u32.pow(round - 1)).await;
+            }
+
+            for index in self.ranked_endpoints() {
+                match op(Arc::clone(&self.endpoints[index].provider)).await {
+                    Ok(result) => {
+                        self.record(index, true);
+                        return Ok(result);
+                    }
+                    Err(e) if e.is_endpoint_failure() => {
+                        self.record(index, false);
+                        last_error = e.to_string();
+                    }
+                    Err(e) => return Err(self.call_failed(e)),
+                }
+            }
+        }
+
+        Err(RpcPoolError::AllEndpointsFailed {
+            chain_id: self.chain_id,
+            last_error,
+        })
+    }
+
+    /// Runs `op` until two different endpoints return the same result.
+    /// Endpoints that answer differently are penalised like failures.
+    pub async fn call_with_quorum<T, E, F, Fut>(&self, op: F) -> Result<T, RpcPoolError>
+    where
+        T: PartialEq,
+        F: Fn(Arc<Provider<Http>>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: RpcCallError,
+    {
+        if self.endpoints.len() < 2 {
+            return self.call(op).await;
+        }
+
+        let mut answers: Vec<(usize, T)> = Vec::new();
+        let mut last_error = None;
+
+        for round in 0..MAX_ROUNDS {
+            if round > 0 {
+                tokio::time::sleep(BASE_BACKOFF * 2u32.pow(round - 1)).await;
+            }
+
+            for index in self.ranked_endpoints() {
+                if answers.iter().any(|(answered, _)| *answered == index) {
+                    continue;
+                }
+
+                let result = match op(Arc::clone(&self.endpoints[index].provider)).await {
+                    Ok(result) => result,
+                    Err(e) if e.is_endpoint_failure() => {
+                        self.record(index, false);
+                        last_error = Some(e.to_string());
+                        continue;
+                    }
+                    Err(e) => return Err(self.call_failed(e)),
+                };
+
+                if let Some(position) = answers.iter().position(|(_, answer)| *answer == result) {
+                    let (agreeing, _) = answers.swap_remove(position);
+
+                    self.record(agreeing, true);
+                    self.record(index, true);
+
+                    for (disagreeing, _) in answers {
+                        warn!(
+                            "rpc endpoint {} for chain {} disagreed with quorum",
+                            disagreeing, self.chain_id
+                        );
+                        self.record(disagreeing, false);
+                    }
+
+                    return Ok(result);
+                }
+
+                answers.push((index, result));
+            }
+        }
+
+        match last_error {
+            Some(last_error) if answers.len() < 2 => Err(RpcPoolError::AllEndpointsFailed {
+                chain_id: self.chain_id,
+                last_error,
+            }),
+            _ => Err(RpcPoolError::QuorumNotReached(self.chain_id)),
+        }
+    }
+
+    /// For reads that decide what gets recorded as paid: cross-checked across two
+    /// endpoints when the network has `rpc_quorum` enabled, plain failover otherwise.
+    pub async fn call_critical<T, E, F, Fut>(&self, op: F) -> Result<T, RpcPoolError>
+    where
+        T: PartialEq,
+        F: Fn(Arc<Provider<Http>>) -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: RpcCallError,
+    {
+        if self.quorum {
+            self.call_with_quorum(op).await
+        } else {
+            self.call(op).await
+        }
+    }
+}
+
+/// One provider pool per enabled network
+pub struct RpcProviders(HashMap<u64, RpcProviderPool>);
+
+impl RpcProviders {
+    /// Networks without any usable rpc url are skipped with a warning
+    pub fn from_networks(networks: &NetworksConfig) -> Self {
+        let mut pools = HashMap::new();
+
+        for network in networks.enabled() {
+            match RpcProviderPool::from_network(network) {
+                Ok(pool) => {
+                    pools.insert(network.chain_id, pool);
+                }
+                Err(e) => warn!("no rpc provider for {}: {}", network.name, e),
+            }
+        }
+
+        Self(pools)
+    }
+
+    pub fn get(&self, chain_id: u64) -> Option<&RpcProviderPool> {
+        self.0.get(&chain_id)
+    }
+
+    pub fn chain_ids(&self) -> Vec<u64> {
+        self.0.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_pool(urls: &[&str], quorum: bool) -> RpcProviderPool {
+        RpcProviderPool::new(1, urls.iter().map(|u| u.to_string()).collect(), quorum).unwrap()
+    }
+
+    /// Test errors are endpoint failures unless they are reverts
+    impl RpcCallError for &str {
+        fn is_endpoint_failure(&self) -> bool {
+            !self.contains("revert")
+        }
+    }
+
+    impl RpcCallError for String {
+        fn is_endpoint_failure(&self) -> bool {
+            self.as_str().is_endpoint_failure()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_call_fails_over_and_demotes_failing_endpoint() {
+        let pool = test_pool(&["http://rpc-a.test", "http://rpc-b.test"], false);
+
+        let result = pool
+            .call(|provider| async move {
+                let url = provider.url().to_string();
+                if url.contains("rpc-a") {
+                    Err("down")
+                } else {
+                    Ok(url)
+                }
+            })
+            .await
+            .unwrap();
+
+        assert!(result.contains("rpc-b"));
+        assert_eq!(pool.primary_url(), "http://rpc-b.test");
+    }
+
+    #[tokio::test]
+    async fn test_call_returns_revert_without_failing_over() {
+        let pool = test_pool(&["http://rpc-a.test", "http://rpc-b.test"], true);
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+
+        let result = pool
+            .call_critical(|_provider| {
+                attempts.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async { Err::<u64, _>("execution reverted") }
+            })
+            .await;
+
+        assert!(matches!(result, Err(RpcPoolError::CallFailed { .. })));
+        assert_eq!(attempts.into_inner(), 1);
+        assert_eq!(pool.primary_url(), "http://rpc-a.test");
+
+        let revert = ProviderError::JsonRpcClientError(Box::new(
+            ethers::providers::HttpClientError::JsonRpcError(JsonRpcError {
+                code: 3,
+                message: "execution reverted".to_string(),
+                data: None,
+            }),
+        ));
+        assert!(!revert.is_endpoint_failure());
+        assert!(ProviderError::CustomError("connection refused".to_string()).is_endpoint_failure());
+    }
+
+    #[tokio::test]
+    async fn test_call_with_quorum_waits_for_two_matching_answers() {
+        let pool = test_pool(
+            &["http://rpc-a.test", "http://rpc-b.test", "http://rpc-c.test"],
+            true,
+        );
+
+        let result = pool
+            .call_critical(|provider| async move {
+                let answer = if provider.url().as_str().contains("rpc-a") {
+                    1
+                } else {
+                    2
+                };
+                Ok::<_, String>(answer)
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(result, 2);
+        assert_ne!(pool.primary_url(), "http://rpc-a.test");
+    }
+}
@@ -0,0 +1,140 @@
+use crate::util::rpc_provider::RpcProviders;
+use actix_web::web::{self, Data};
+use actix_web::{App, HttpResponse, HttpServer};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/*
+
+Serves the rpc provider pools as plain JSON-RPC endpoints on localhost, for components such
+as vibegraph that only take a url and build their own provider. Their requests then go
+through the pool of the chain like the bots' own calls, failing over between endpoints
+while the process runs.
+
+  http://127.0.0.1:<port>/rpc/<chain_id>
+
+*/
+
+/// JSON-RPC error code for a request the pool could not answer
+const INTERNAL_ERROR_CODE: i64 = -32603;
+
+#[derive(Deserialize)]
+struct JsonRpcRequest {
+    id: Value,
+    method: String,
+    params: Option<Value>,
+}
+
+/// Starts the proxy on a free local port and returns the url of each chain
+pub fn start_rpc_proxy(providers: RpcProviders) -> std::io::Result<HashMap<u64, String>> {
+    let chain_ids = providers.chain_ids();
+    let providers = Data::new(providers);
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(Data::clone(&providers))
+            .route("/rpc/{chain_id}", web::post().to(forward))
+    })
+    .workers(1)
+    // stopping on ctrl-c is up to the process, not the proxy
+    .disable_signals()
+    .bind(("127.0.0.1", 0))?;
+
+    let address = server.addrs()[0];
+
+    tokio::spawn(server.run());
+
+    Ok(chain_ids
+        .into_iter()
+        .map(|chain_id| (chain_id, format!("http://{}/rpc/{}", address, chain_id)))
+        .collect())
+}
+
+async fn forward(
+    chain_id: web::Path<u64>,
+    web::Json(request): web::Json<JsonRpcRequest>,
+    providers: Data<RpcProviders>,
+) -> HttpResponse {
+    let chain_id = chain_id.into_inner();
+
+    let Some(pool) = providers.get(chain_id) else {
+        return error_response(request.id, format!("No rpc pool for chain {}", chain_id));
+    };
+
+    let method = request.method.as_str();
+    let params = request.params.unwrap_or_else(|| json!([]));
+
+    let result = pool
+        .call(|provider| {
+            let params = params.clone();
+            async move { provider.request::<_, Value>(method, params).await }
+        })
+        .await;
+
+    match result {
+        Ok(result) => HttpResponse::Ok().json(json!({
+            "jsonrpc": "2.0",
+            "id": request.id,
+            "result": result,
+        })),
+        Err(e) => error_response(request.id, e.to_string()),
+    }
+}
+
+fn error_response(id: Value, message: String) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": INTERNAL_ERROR_CODE, "message": message },
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::NetworksConfig;
+    use ethers::providers::{Http, Middleware, Provider};
+
+    /// A node that answers every request with block 16
+    fn start_fake_node() -> String {
+        let server = HttpServer::new(|| {
+            App::new().default_service(web::to(|body: web::Json<Value>| async move {
+                HttpResponse::Ok().json(json!({
+                    "jsonrpc": "2.0",
+                    "id": body["id"],
+                    "result": "0x10",
+                }))
+            }))
+        })
+        .workers(1)
+        .disable_signals()
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+
+        let address = server.addrs()[0];
+        tokio::spawn(server.run());
+
+        format!("http://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_proxy_fails_over_to_a_working_endpoint() {
+        let networks = NetworksConfig::from_json_str(
+            &json!([{
+                "name": "testnet",
+                "chain_id": 1,
+                "rpc_urls": ["http://127.0.0.1:9", start_fake_node()],
+                "confirmation_depth": 1,
+                "finality_depth": 1,
+            }])
+            .to_string(),
+        )
+        .unwrap();
+
+        let urls = start_rpc_proxy(RpcProviders::from_networks(&networks)).unwrap();
+        let provider = Provider::<Http>::try_from(urls[&1].as_str()).unwrap();
+
+        assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 16);
+    }
+}
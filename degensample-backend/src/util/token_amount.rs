@@ -0,0 +1,66 @@
+use ethers::types::U256;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Renders a raw token amount in whole units, e.g. 1500000 with 6 decimals -> "1.5".
+/// Trailing fractional zeros are dropped so the output is stable for display and CSV.
+pub fn format_units(amount: U256, decimals: u32) -> String {
+    let digits = amount.to_string();
+    let decimals = decimals as usize;
+
+    if decimals == 0 {
+        return digits;
+    }
+
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+
+    let fraction = fraction.trim_end_matches('0');
+
+    if fraction.is_empty() {
+        whole.to_string()
+    } else {
+        format!("{}.{}", whole, fraction)
+    }
+}
+
+/// USD value of a raw token amount given the price of one whole token.
+/// Returns None when the amount does not fit in a Decimal.
+pub fn usd_value(amount: U256, decimals: u32, usd_price: Decimal) -> Option<Decimal> {
+    let whole_units = Decimal::from_str(&format_units(amount, decimals)).ok()?;
+
+    whole_units.checked_mul(usd_price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_units() {
+        assert_eq!(format_units(U256::from(1_500_000), 6), "1.5");
+        assert_eq!(format_units(U256::from(1_000_000), 6), "1");
+        assert_eq!(format_units(U256::from(1), 6), "0.000001");
+        assert_eq!(format_units(U256::zero(), 18), "0");
+        assert_eq!(format_units(U256::from(42), 0), "42");
+        assert_eq!(
+            format_units(U256::exp10(18) * 3 + U256::exp10(17), 18),
+            "3.1"
+        );
+    }
+
+    #[test]
+    fn test_usd_value() {
+        let price = Decimal::from_str("2000.5").unwrap();
+
+        assert_eq!(
+            usd_value(U256::exp10(17) * 15, 18, price),
+            Some(Decimal::from_str("3000.75").unwrap())
+        );
+        assert_eq!(
+            usd_value(U256::from(2_500_000), 6, Decimal::ONE),
+            Some(Decimal::from_str("2.5").unwrap())
+        );
+        assert_eq!(usd_value(U256::MAX, 0, Decimal::ONE), None);
+    }
+}
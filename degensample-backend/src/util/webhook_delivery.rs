@@ -0,0 +1,214 @@
+use crate::db::postgres::models::webhook_deliveries_model::{DeliveryErrorKind, WebhookDelivery};
+use crate::types::domains::json::DomainJson;
+use crate::types::webhook_batches::acknowledged_event_ids;
+use crate::util::delivery_sink::{QueueSinks, QueueTarget, SinkMessage};
+use crate::util::http_request::EndpointUrlAndData;
+use crate::util::webhook_retry::parse_retry_after;
+use crate::util::webhook_url_guard::{GuardedResolver, WebhookUrlPolicy};
+use chrono::Utc;
+use reqwest::header::{HeaderMap, ACCEPT, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::redirect;
+use reqwest::Client;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Client for requests to user supplied webhook urls. Redirects are not followed and
+/// hosts resolve through `GuardedResolver`, unless the policy allows private targets.
+#[derive(Clone)]
+pub struct WebhookHttpClient {
+    client: Client,
+    policy: WebhookUrlPolicy,
+}
+
+impl WebhookHttpClient {
+    pub fn new(timeout: Duration, policy: WebhookUrlPolicy) -> Result<Self, reqwest::Error> {
+        let mut builder = Client::builder()
+            .timeout(timeout)
+            .redirect(redirect::Policy::none());
+
+        if !policy.allow_private_targets {
+            builder = builder.dns_resolver(Arc::new(GuardedResolver));
+        }
+
+        Ok(Self {
+            client: builder.build()?,
+            policy,
+        })
+    }
+
+    pub fn policy(&self) -> &WebhookUrlPolicy {
+        &self.policy
+    }
+}
+
+/// Most of a response body read from a receiver. Enough for a batch acknowledgement;
+/// anything longer is cut off and the connection dropped.
+const MAX_RESPONSE_READ_BYTES: usize = 64 * 1024;
+
+/// Reads up to `MAX_RESPONSE_READ_BYTES` of a response body, so a receiver answering with
+/// an endless body cannot exhaust memory. None when the body could not be read.
+async fn read_capped_body(mut response: reqwest::Response) -> Option<String> {
+    let mut body = Vec::new();
+
+    while body.len() < MAX_RESPONSE_READ_BYTES {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                let remaining = MAX_RESPONSE_READ_BYTES - body.len();
+                body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+            }
+            Ok(None) => break,
+            Err(_) => return None,
+        }
+    }
+
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Headers as recorded on a delivery. Custom headers of the webhook url may hold the
+/// receiver's credentials, so only the values of headers the relay sets are kept.
+fn headers_to_json(headers: &HeaderMap) -> serde_json::Value {
+    let map = headers
+        .iter()
+        .map(|(name, value)| {
+            let set_by_relay = name == CONTENT_TYPE
+                || name == ACCEPT
+                || name.as_str().starts_with("x-defirelay-");
+
+            let value = if set_by_relay {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            } else {
+                "[redacted]".to_string()
+            };
+
+            (name.to_string(), serde_json::Value::String(value))
+        })
+        .collect();
+
+    serde_json::Value::Object(map)
+}
+
+/// Sends a webhook request and describes how it went. Never fails: a request that
+/// could not be sent is still an attempt, recorded with its error kind.
+pub async fn deliver_webhook(
+    client: &WebhookHttpClient,
+    request: &EndpointUrlAndData,
+    webhook_trigger_id: i32,
+    webhook_url_id: i32,
+    attempt: i32,
+    redelivery: bool,
+) -> WebhookDelivery {
+    let created_at = Utc::now();
+    let started = Instant::now();
+
+    let (response_status, response_body, error_kind, retry_after) =
+        match client.policy.check_url(&request.url) {
+            Err(_) => (None, None, Some(DeliveryErrorKind::Blocked), None),
+            Ok(_) => match request.perform_req_with_client(&client.client).await {
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| parse_retry_after(value, Utc::now()));
+                    let body = read_capped_body(response).await;
+
+                    let error_kind =
+                        (!status.is_success()).then_some(DeliveryErrorKind::HttpStatus);
+
+                    (Some(status.as_u16() as i32), body, error_kind, retry_after)
+                }
+                Err(e) => (None, None, Some(DeliveryErrorKind::from(&e)), None),
+            },
+        };
+
+    let acknowledged = response_body.as_deref().and_then(acknowledged_event_ids);
+
+    WebhookDelivery {
+        webhook_trigger_id,
+        webhook_url_id,
+        attempt,
+        request_url: request.url.clone(),
+        request_headers: DomainJson(headers_to_json(&request.headers)),
+        response_status,
+        response_body: response_body.map(WebhookDelivery::truncate_response_body),
+        latency_ms: started.elapsed().as_millis() as i64,
+        error_kind: error_kind.map(|kind| kind.to_string()),
+        redelivery,
+        replay_requested_by: None,
+        replay_scope: None,
+        created_at,
+        retry_after,
+        acknowledged,
+    }
+}
+
+/// Publishes a webhook request to a message broker, recorded like an HTTP delivery.
+/// The broker's receipt, or the error, takes the place of the response body.
+pub async fn deliver_to_queue(
+    sinks: &QueueSinks,
+    target: &QueueTarget,
+    request: &EndpointUrlAndData,
+    webhook_trigger_id: i32,
+    webhook_url_id: i32,
+    attempt: i32,
+    redelivery: bool,
+) -> WebhookDelivery {
+    let created_at = Utc::now();
+    let started = Instant::now();
+
+    let (response_body, error_kind) =
+        match sinks.publish(target, &SinkMessage::from_request(request)).await {
+            Ok(receipt) => (receipt, None),
+            Err(e) => (e.to_string(), Some(DeliveryErrorKind::from(&e))),
+        };
+
+    WebhookDelivery {
+        webhook_trigger_id,
+        webhook_url_id,
+        attempt,
+        request_url: request.url.clone(),
+        request_headers: DomainJson(headers_to_json(&request.headers)),
+        response_status: None,
+        response_body: Some(WebhookDelivery::truncate_response_body(response_body)),
+        latency_ms: started.elapsed().as_millis() as i64,
+        error_kind: error_kind.map(|kind| kind.to_string()),
+        redelivery,
+        replay_requested_by: None,
+        replay_scope: None,
+        created_at,
+        retry_after: None,
+        acknowledged: None,
+    }
+}
+
+/// Sends a verification request and checks that the receiver echoed its challenge,
+/// either as the whole response body or as `{"challenge": "..."}`
+pub async fn send_verification_challenge(
+    client: &WebhookHttpClient,
+    request: &EndpointUrlAndData,
+    challenge: &str,
+) -> bool {
+    if client.policy.check_url(&request.url).is_err() {
+        return false;
+    }
+
+    let Ok(response) = request.perform_req_with_client(&client.client).await else {
+        return false;
+    };
+
+    if !response.status().is_success() {
+        return false;
+    }
+
+    let Some(body) = read_capped_body(response).await else {
+        return false;
+    };
+
+    let echoed = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|json| json.get("challenge")?.as_str().map(str::to_string))
+        .unwrap_or_else(|| body.trim().to_string());
+
+    echoed == challenge
+}
@@ -0,0 +1,142 @@
+use crate::db::postgres::executor::DbExecutor;
+use crate::db::postgres::models::domain_events_model::{DomainEvent, DomainEventsModel};
+use crate::db::postgres::models::invoices_model::{Invoice, InvoicesModel};
+use crate::db::postgres::models::payments_model::PaymentSummary;
+use crate::db::postgres::models::webhook_triggers_model::{WebhookTrigger, WebhookTriggersModel};
+use crate::db::postgres::models::webhook_urls_model::WebhookUrlsModel;
+use crate::types::domains::bytes32::DomainBytes32;
+use crate::types::webhook_scopes::{ScopedEvent, WebhookScopes};
+use degen_sql::db::postgres::models::model::PostgresModelError;
+use degen_sql::db::postgres::postgres_db::Database;
+use log::{info, warn};
+
+/*
+
+Turns domain events into webhook triggers.
+
+Every owner of an event gets a trigger on each of their verified webhook urls whose
+scopes match it. Urls that are disabled still get one, and receive it once re-enabled.
+Triggers and the published mark commit together, and the triggers are keyed on the event
+and webhook url, so an event is never sent to the same url twice.
+
+*/
+
+/// The payload of events whose fields can be scoped on
+enum ScopableEvent {
+    Payment(Box<PaymentSummary>),
+    Invoice(Box<Invoice>),
+    Other,
+}
+
+impl ScopableEvent {
+    fn from_event(event: &DomainEvent) -> Self {
+        let payload = event.payload.0.clone();
+
+        let parsed = match event.event_type.as_str() {
+            "payment_summary" => serde_json::from_value(payload).map(Self::Payment),
+            "invoice_created" | "invoice_expired" => {
+                serde_json::from_value(payload).map(Self::Invoice)
+            }
+            _ => Ok(Self::Other),
+        };
+
+        parsed.unwrap_or_else(|e| {
+            warn!(
+                "{} event is unreadable, only its type is scoped: {}",
+                event.event_type, e
+            );
+            Self::Other
+        })
+    }
+
+    fn scoped<'a>(
+        &'a self,
+        event_type: &'a str,
+        template_uuid: Option<&'a DomainBytes32>,
+    ) -> ScopedEvent<'a> {
+        match self {
+            Self::Payment(payment) => ScopedEvent::from_payment(payment, template_uuid),
+            Self::Invoice(invoice) => ScopedEvent::from_invoice(event_type, invoice),
+            Self::Other => ScopedEvent::untyped(event_type),
+        }
+    }
+}
+
+/// Fans out a batch of unpublished events. Returns how many events were published.
+pub async fn fan_out_domain_events(
+    limit: i64,
+    psql_db: &Database,
+) -> Result<usize, PostgresModelError> {
+    let mut client = psql_db.connect().await?;
+    let transaction = client.transaction().await?;
+
+    let events = DomainEventsModel::claim_unpublished(limit, &transaction).await?;
+
+    for event in &events {
+        let event_id: i32 = event.id.clone().into();
+
+        let created = create_triggers_for_event(event_id, &event.entry, &transaction).await?;
+
+        DomainEventsModel::mark_published(event_id, &transaction).await?;
+
+        info!(
+            "created {} webhook triggers for {} event {}",
+            created, event.entry.event_type, event_id
+        );
+    }
+
+    transaction.commit().await?;
+
+    Ok(events.len())
+}
+
+async fn create_triggers_for_event(
+    event_id: i32,
+    event: &DomainEvent,
+    psql_db: &impl DbExecutor,
+) -> Result<usize, PostgresModelError> {
+    let scopable = ScopableEvent::from_event(event);
+
+    // looked up once, and only if some webhook is scoped to templates
+    let mut template_uuid: Option<Option<DomainBytes32>> = None;
+
+    let mut created = 0;
+
+    for owner in &event.owner_wallet_addresses {
+        let webhooks = WebhookUrlsModel::find_verified_by_owner_address(owner, psql_db).await?;
+
+        for webhook in webhooks {
+            let scopes = WebhookScopes::from_stored(webhook.entry.scopes.as_ref());
+
+            if let ScopableEvent::Payment(payment) = &scopable {
+                if scopes.uses_templates() && template_uuid.is_none() {
+                    template_uuid =
+                        Some(InvoicesModel::find_template_uuid(&payment.uuid, psql_db).await?);
+                }
+            }
+
+            let scoped = scopable.scoped(
+                &event.event_type,
+                template_uuid.as_ref().and_then(Option::as_ref),
+            );
+
+            if !scopes.matches(&scoped) {
+                continue;
+            }
+
+            let webhook_id: i32 = webhook.id.into();
+
+            let trigger = WebhookTrigger::with_event_data(webhook_id, event.clone())
+                .with_dedupe_key(event.trigger_dedupe_key(event_id, webhook_id));
+
+            if WebhookTriggersModel::insert_if_new(trigger, psql_db)
+                .await?
+                .is_some()
+            {
+                created += 1;
+            }
+        }
+    }
+
+    Ok(created)
+}
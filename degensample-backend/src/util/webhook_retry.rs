@@ -0,0 +1,159 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use std::time::Duration;
+
+/*
+
+WEBHOOK_MAX_ATTEMPTS=8
+WEBHOOK_RETRY_BASE_SECS=30
+WEBHOOK_RETRY_MAX_SECS=21600
+WEBHOOK_DISABLE_AFTER_FAILURES=20     consecutive failed deliveries before a url is disabled
+
+*/
+
+/// When a failed webhook delivery is tried again, and when it is given up on
+#[derive(Debug, Clone)]
+pub struct WebhookRetryPolicy {
+    pub max_attempts: i32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Failed deliveries in a row, across all of a url's triggers, before the url is disabled
+    pub disable_after_failures: i32,
+}
+
+impl Default for WebhookRetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 8,
+            base_delay: Duration::from_secs(30),
+            max_delay: Duration::from_secs(6 * 60 * 60),
+            disable_after_failures: 20,
+        }
+    }
+}
+
+impl WebhookRetryPolicy {
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let env_u64 = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+
+        Self {
+            max_attempts: std::env::var("WEBHOOK_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.max_attempts),
+            base_delay: env_u64("WEBHOOK_RETRY_BASE_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.base_delay),
+            max_delay: env_u64("WEBHOOK_RETRY_MAX_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.max_delay),
+            disable_after_failures: std::env::var("WEBHOOK_DISABLE_AFTER_FAILURES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(defaults.disable_after_failures),
+        }
+    }
+
+    /// Upper bound of the wait after `attempts` failed deliveries: base * 2^(attempts - 1), capped
+    pub fn max_delay_after(&self, attempts: i32) -> Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 31) as u32;
+
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(exponent))
+            .min(self.max_delay)
+    }
+
+    /// Wait before the next try, jittered between half and all of `max_delay_after`
+    /// so endpoints that come back up are not hit by every queued trigger at once
+    pub fn delay_after(&self, attempts: i32) -> Duration {
+        let max_delay = self.max_delay_after(attempts);
+
+        let jitter = rand::thread_rng().gen_range(0.5..=1.0);
+
+        max_delay.mul_f64(jitter)
+    }
+
+    /// `delay_after`, but never sooner than the endpoint asked for with Retry-After.
+    /// The endpoint cannot push the wait past `max_delay`.
+    pub fn delay_after_response(&self, attempts: i32, retry_after: Option<Duration>) -> Duration {
+        let delay = self.delay_after(attempts);
+
+        match retry_after {
+            Some(retry_after) => delay.max(retry_after.min(self.max_delay)),
+            None => delay,
+        }
+    }
+}
+
+/// Reads a Retry-After header, either delay-seconds or an HTTP date
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+
+    Some((at.with_timezone(&Utc) - now).to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_delay_grows_and_caps() {
+        let policy = WebhookRetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+            disable_after_failures: 20,
+        };
+
+        assert_eq!(policy.max_delay_after(1), Duration::from_secs(10));
+        assert_eq!(policy.max_delay_after(2), Duration::from_secs(20));
+        assert_eq!(policy.max_delay_after(3), Duration::from_secs(40));
+        assert_eq!(policy.max_delay_after(4), Duration::from_secs(60));
+        assert_eq!(policy.max_delay_after(100), Duration::from_secs(60));
+
+        let delay = policy.delay_after(2);
+        assert!(delay >= Duration::from_secs(10) && delay <= Duration::from_secs(20));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let now = DateTime::parse_from_rfc3339("2015-10-21T07:28:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(parse_retry_after("120", now), Some(Duration::from_secs(120)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:30:00 GMT", now),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        let policy = WebhookRetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_secs(10),
+            max_delay: Duration::from_secs(60),
+            disable_after_failures: 20,
+        };
+
+        assert_eq!(
+            policy.delay_after_response(1, Some(Duration::from_secs(45))),
+            Duration::from_secs(45)
+        );
+        assert_eq!(
+            policy.delay_after_response(1, Some(Duration::from_secs(3600))),
+            Duration::from_secs(60)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+/*
+
+Every webhook POST carries
+
+  X-DefiRelay-Event-Id:   whevt_1234          stable across retries, use it to drop duplicates
+  X-DefiRelay-Timestamp:  1700000000          unix seconds when the request was signed
+  X-DefiRelay-Signature:  v1=<hex>[,v1=<hex>] HMAC-SHA256 of "{timestamp}.{raw body}"
+
+While a secret is being rotated the signature header holds one entry per active secret,
+so a receiver that still has the old secret keeps verifying until the grace period ends.
+
+Receivers can verify with `verify_signature`, passing the raw request body bytes.
+
+*/
+
+pub const EVENT_ID_HEADER: &str = "X-DefiRelay-Event-Id";
+pub const TIMESTAMP_HEADER: &str = "X-DefiRelay-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-DefiRelay-Signature";
+
+const SIGNATURE_VERSION: &str = "v1";
+const SECRET_PREFIX: &str = "whsec_";
+
+/// How far a signed timestamp may drift from the receiver's clock, in seconds
+pub const DEFAULT_TOLERANCE_SECS: i64 = 300;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum WebhookSignatureError {
+    #[error("Timestamp header is not a unix timestamp")]
+    InvalidTimestamp,
+
+    #[error("Timestamp is outside the allowed tolerance")]
+    TimestampOutOfTolerance,
+
+    #[error("Signature header has no v1 signature")]
+    MissingSignature,
+
+    #[error("No signature matches the payload")]
+    SignatureMismatch,
+}
+
+/// A fresh endpoint secret, `whsec_` followed by 64 hex characters
+pub fn generate_signing_secret() -> String {
+    let mut random_bytes = [0u8; 32];
+    rand::thread_rng().fill(&mut random_bytes);
+
+    format!("{}{}", SECRET_PREFIX, hex::encode(random_bytes))
+}
+
+/// Random token a new webhook url has to echo back to prove it is ready for events
+pub fn generate_verification_challenge() -> String {
+    let mut random_bytes = [0u8; 16];
+    rand::thread_rng().fill(&mut random_bytes);
+
+    hex::encode(random_bytes)
+}
+
+fn mac_for(secret: &str, timestamp: i64, body: &[u8]) -> HmacSha256 {
+    // HMAC accepts keys of any length, so this cannot fail
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac key");
+
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{body}`
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    hex::encode(mac_for(secret, timestamp, body).finalize().into_bytes())
+}
+
+/// Value of the signature header, one `v1=` entry per secret
+pub fn signature_header(secrets: &[&str], timestamp: i64, body: &[u8]) -> String {
+    secrets
+        .iter()
+        .map(|secret| format!("{}={}", SIGNATURE_VERSION, sign(secret, timestamp, body)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Checks a delivery against the endpoint secret. `now` is the receiver's unix time.
+pub fn verify_signature(
+    secret: &str,
+    timestamp_header: &str,
+    signature_header: &str,
+    body: &[u8],
+    now: i64,
+    tolerance_secs: i64,
+) -> Result<(), WebhookSignatureError> {
+    let timestamp: i64 = timestamp_header
+        .trim()
+        .parse()
+        .map_err(|_| WebhookSignatureError::InvalidTimestamp)?;
+
+    // abs_diff cannot overflow on a hostile timestamp like i64::MIN
+    if now.abs_diff(timestamp) > u64::try_from(tolerance_secs).unwrap_or(0) {
+        return Err(WebhookSignatureError::TimestampOutOfTolerance);
+    }
+
+    let signatures: Vec<Vec<u8>> = signature_header
+        .split(',')
+        .filter_map(|entry| entry.trim().split_once('='))
+        .filter(|(version, _)| *version == SIGNATURE_VERSION)
+        .filter_map(|(_, signature)| hex::decode(signature).ok())
+        .collect();
+
+    if signatures.is_empty() {
+        return Err(WebhookSignatureError::MissingSignature);
+    }
+
+    // verify_slice compares in constant time
+    let matched = signatures.iter().any(|signature| {
+        mac_for(secret, timestamp, body)
+            .verify_slice(signature)
+            .is_ok()
+    });
+
+    if matched {
+        Ok(())
+    } else {
+        Err(WebhookSignatureError::SignatureMismatch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = br#"{"event_type":"payment_summary"}"#;
+
+    #[test]
+    fn test_signature_round_trip() {
+        let secret = generate_signing_secret();
+        let header = signature_header(&[&secret], 1_700_000_000, BODY);
+
+        assert_eq!(
+            verify_signature(&secret, "1700000000", &header, BODY, 1_700_000_010, 300),
+            Ok(())
+        );
+        assert_eq!(
+            verify_signature(&secret, "1700000000", &header, b"{}", 1_700_000_010, 300),
+            Err(WebhookSignatureError::SignatureMismatch)
+        );
+        assert_eq!(
+            verify_signature(&secret, "1700000000", &header, BODY, 1_700_001_000, 300),
+            Err(WebhookSignatureError::TimestampOutOfTolerance)
+        );
+        assert_eq!(
+            verify_signature(
+                &secret,
+                &i64::MIN.to_string(),
+                &header,
+                BODY,
+                1_700_000_010,
+                300
+            ),
+            Err(WebhookSignatureError::TimestampOutOfTolerance)
+        );
+    }
+
+    #[test]
+    fn test_rotated_secrets_both_verify() {
+        let old_secret = generate_signing_secret();
+        let new_secret = generate_signing_secret();
+        let header = signature_header(&[&new_secret, &old_secret], 1_700_000_000, BODY);
+
+        assert!(
+            verify_signature(&old_secret, "1700000000", &header, BODY, 1_700_000_000, 300).is_ok()
+        );
+        assert!(
+            verify_signature(&new_secret, "1700000000", &header, BODY, 1_700_000_000, 300).is_ok()
+        );
+        assert_eq!(
+            verify_signature(
+                &generate_signing_secret(),
+                "1700000000",
+                &header,
+                BODY,
+                1_700_000_000,
+                300
+            ),
+            Err(WebhookSignatureError::SignatureMismatch)
+        );
+    }
+}
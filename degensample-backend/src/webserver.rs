@@ -1,4 +1,5 @@
 use defirelay_backend::app_state::AppState;
+use defirelay_backend::util::account_event_hub::AccountEventHub;
 use degen_sql::db::postgres::postgres_db::Database;
 use dotenvy::dotenv;
 use std::sync::Arc;
@@ -13,12 +14,16 @@ mod controllers;
 use crate::controllers::web_controller::WebController;
 
 use controllers::api_key_controller::ApiKeyController;
+use controllers::invoice_templates_controller::InvoiceTemplatesController;
+use controllers::invoices_controller::InvoicesController;
  
  
  
 use controllers::payments_controller::PaymentsController;
  
 use controllers::session_controller::SessionController; 
+use controllers::stream_controller::StreamController;
+use controllers::token_symbols_controller::TokenSymbolsController;
 use controllers::users_controller::UsersController;
 use controllers::webhook_urls_controller::WebhookUrlsController;
 
@@ -56,6 +61,8 @@ async fn main() -> io::Result<()> {
 
     println!("connected to db.");
 
+    let account_events = AccountEventHub::start(Arc::clone(&database)).await;
+
     //setup and launch the http server
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -69,6 +76,7 @@ async fn main() -> io::Result<()> {
 
         let app_state = AppState {
             database: Arc::clone(&database),
+            account_events: Arc::clone(&account_events),
         };
 
         App::new()
@@ -77,13 +85,17 @@ async fn main() -> io::Result<()> {
             .wrap(actix_web::middleware::Logger::default()) // Enable logger middleware
             .configure(SessionController::config) // log in with eth
             .configure(ApiKeyController::config) //create api keys
+            .configure(InvoicesController::config) //draft invoices and their payment txs
+            .configure(InvoiceTemplatesController::config) //reusable invoice configurations
             
             .configure(PaymentsController::config) //find info abt payments
+            .configure(TokenSymbolsController::config) //token symbols and decimals per chain
            
        
             .configure(UsersController::config) //user stats and management
             
             .configure(WebhookUrlsController::config) //manage webhook URLs
+            .configure(StreamController::config) //live account events for dashboards
             //.configure(ApiClientKeysController::config)
            // .configure(ApiWorkspacesController::config)
            // .configure(ApiCreditRefillsController::config)   
@@ -270,7 +270,7 @@ const PaymentsContent = () => {
     },
     {
       header: 'Amount',
-      accessor: (row) => row.entry.totalAmount,
+      accessor: (row) => row.entry.amount,
       sortable: false, // Can't sort by this field in the backend
       className: 'whitespace-nowrap',
       essential: true, // Always keep this column, even on small screens
@@ -281,12 +281,12 @@ const PaymentsContent = () => {
       )
     },
     {
-      header: 'Recipients',
-      accessor: (row) => row.entry.pay_to_array.length,
+      header: 'Recipient',
+      accessor: (row) => row.entry.to_address,
       sortable: false, // Can't sort by this field in the backend
-      className: 'whitespace-nowrap',
+      className: 'whitespace-nowrap font-mono text-sm',
       responsive: 'lg', // Show this column at lg breakpoint and above
-      format: (value) => `${value} recipient(s)`
+      format: (value) => truncateAddress(value)
     },
     {
       header: 'Network',